@@ -1,3 +1,22 @@
+// Public key of the development license server, only accepted by debug builds.
+const DEVELOPMENT_LICENSE_PUBLIC_KEY: &str = "Y7SGoUg0RNOju5KOSGNHgYKd8Sk0lMKQIHIK0ECBTw8";
+
 fn main() {
+    // Release builds verify license tokens with the production key, which has
+    // to be given when building, e.g. by the release pipeline.
+    println!("cargo:rerun-if-env-changed=LICENSE_PUBLIC_KEY");
+    let public_key = match std::env::var("LICENSE_PUBLIC_KEY") {
+        Ok(public_key) => public_key,
+        Err(_) if std::env::var("PROFILE").as_deref() == Ok("release") => {
+            panic!("LICENSE_PUBLIC_KEY must be set to build a release");
+        }
+        Err(_) => DEVELOPMENT_LICENSE_PUBLIC_KEY.to_string(),
+    };
+    // 32 bytes in unpadded base64url
+    if public_key.len() != 43 {
+        panic!("LICENSE_PUBLIC_KEY is not an Ed25519 public key");
+    }
+    println!("cargo:rustc-env=LICENSE_PUBLIC_KEY={}", public_key);
+
     tauri_build::build()
 }
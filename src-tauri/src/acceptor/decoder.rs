@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::time::{ Duration, Instant };
+
+use tracing::warn;
+
+use crate::settings::appconfigmodels::{ Acceptor, AcceptorMode };
+
+// Turns the bytes read from an acceptor into the centavos of each coin or
+// bill.
+pub struct Decoder {
+    mode: AcceptorMode,
+    pulse_gap: Duration,
+    centavos: HashMap<u8, u64>,
+    pulses: u8,
+    last_pulse: Option<Instant>,
+}
+
+impl Decoder {
+    pub fn new(config: &Acceptor) -> Self {
+        Decoder {
+            mode: config.mode,
+            pulse_gap: Duration::from_millis(config.pulse_gap_ms),
+            centavos: config.denominations
+                .iter()
+                .map(|denomination| (denomination.signal, denomination.centavos))
+                .collect(),
+            pulses: 0,
+            last_pulse: None,
+        }
+    }
+
+    // Denominations completed by `bytes`, read at `now`.
+    pub fn feed(&mut self, bytes: &[u8], now: Instant) -> Vec<u64> {
+        match self.mode {
+            AcceptorMode::Serial => bytes
+                .iter()
+                .filter_map(|code| self.coin(*code))
+                .collect(),
+            AcceptorMode::Pulse => {
+                let previous = self.idle(now);
+                self.pulses = self.pulses.saturating_add(bytes.len() as u8);
+                self.last_pulse = Some(now);
+                previous.into_iter().collect()
+            }
+        }
+    }
+
+    // Called when nothing was read, completes a pulse train once the line
+    // stayed quiet for the pulse gap.
+    pub fn idle(&mut self, now: Instant) -> Option<u64> {
+        let last_pulse = self.last_pulse?;
+        if now.duration_since(last_pulse) < self.pulse_gap {
+            return None;
+        }
+
+        let pulses = std::mem::take(&mut self.pulses);
+        self.last_pulse = None;
+        self.coin(pulses)
+    }
+
+    fn coin(&self, signal: u8) -> Option<u64> {
+        let centavos = self.centavos.get(&signal).copied();
+        if centavos.is_none() {
+            warn!(signal, "Acceptor sent an unknown signal, ignoring it");
+        }
+        centavos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pulse_trains_end_after_the_gap() {
+        let mut decoder = Decoder::new(&Acceptor::default());
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        // A 5 peso coin sends 5 pulses, 50ms apart
+        for ms in [0, 50, 100, 150] {
+            assert!(decoder.feed(&[0], at(ms)).is_empty());
+        }
+        assert!(decoder.feed(&[0], at(200)).is_empty());
+        assert_eq!(decoder.idle(at(250)), None);
+        assert_eq!(decoder.idle(at(300)), Some(500));
+        assert_eq!(decoder.idle(at(400)), None);
+
+        // A new train right after the gap completes the previous one
+        assert!(decoder.feed(&[0], at(500)).is_empty());
+        assert_eq!(decoder.feed(&[0, 0, 0, 0, 0], at(700)), vec![100]);
+        assert_eq!(decoder.idle(at(800)), Some(500));
+
+        // No denomination sends 3 pulses
+        decoder.feed(&[0, 0, 0], at(900));
+        assert_eq!(decoder.idle(at(1000)), None);
+    }
+
+    #[test]
+    fn test_serial_codes_map_to_denominations() {
+        let mut decoder = Decoder::new(&Acceptor {
+            mode: AcceptorMode::Serial,
+            ..Acceptor::default()
+        });
+        assert_eq!(decoder.feed(&[1, 10, 7, 20], Instant::now()), vec![100, 1000, 2000]);
+        assert_eq!(decoder.idle(Instant::now()), None);
+    }
+}
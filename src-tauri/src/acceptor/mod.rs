@@ -0,0 +1,86 @@
+//! Driver for coin and bill acceptors wired to a serial port of this PC.
+//! Payments end up on the same credit channel as `/addtime` requests.
+
+pub mod decoder;
+#[cfg(test)]
+pub mod simulator;
+
+use std::io::{ ErrorKind, Read };
+use std::time::{ Duration, Instant };
+
+use anyhow::{ Context, Result };
+use tauri::AppHandle;
+use tracing::{ info, warn };
+
+use crate::http_server::handler::CreditSender;
+use crate::payment::{ self, Payment };
+use crate::settings::appconfigmodels::{ Acceptor, Pricing };
+use decoder::Decoder;
+
+// Wait before opening the port again after it failed or was unplugged.
+const REOPEN_DELAY: Duration = Duration::from_secs(5);
+
+// Reads the acceptor on its own thread for as long as the app runs.
+pub fn start(config: Acceptor, pricing: Pricing, tx: CreditSender, app_handle: AppHandle) {
+    std::thread::spawn(move || {
+        let mut decoder = Decoder::new(&config);
+        while !tx.is_closed() {
+            let result = open(&config).and_then(|mut port| {
+                info!(port = config.port, "Acceptor connected");
+                read_inserted(&mut port, &mut decoder, |centavos| {
+                    let payment = Payment::cash(config.source, centavos);
+                    if let Err(e) = payment::validate(&pricing, &payment) {
+                        warn!(port = config.port, "Refusing payment: {}", e);
+                        return Ok(());
+                    }
+                    info!(centavos, source = config.source.as_str(), "Payment inserted");
+                    tx
+                        .blocking_send((payment, app_handle.clone(), None))
+                        .map_err(|_| anyhow::anyhow!("Credit channel is closed"))
+                })
+            });
+            if let Err(e) = result {
+                warn!(port = config.port, "Acceptor stopped: {:#}", e);
+            }
+            std::thread::sleep(REOPEN_DELAY);
+        }
+    });
+}
+
+fn open(config: &Acceptor) -> Result<Box<dyn serialport::SerialPort>> {
+    // Short reads so the end of a pulse train is noticed in time
+    let timeout = Duration::from_millis((config.pulse_gap_ms / 2).max(10));
+    serialport
+        ::new(&config.port, config.baud_rate)
+        .timeout(timeout)
+        .open()
+        .with_context(|| format!("Failed to open {}", config.port))
+}
+
+// Feeds everything read from `port` to the decoder and hands every inserted
+// denomination, in centavos, to `on_inserted` until the port closes or fails.
+// A read timeout means the line is quiet.
+pub fn read_inserted<R: Read>(
+    port: &mut R,
+    decoder: &mut Decoder,
+    mut on_inserted: impl FnMut(u64) -> Result<()>
+) -> Result<()> {
+    let mut buffer = [0u8; 64];
+    loop {
+        let inserted = match port.read(&mut buffer) {
+            Ok(0) => {
+                return Err(anyhow::anyhow!("Serial port was closed"));
+            }
+            Ok(read) => decoder.feed(&buffer[..read], Instant::now()),
+            Err(e) if e.kind() == ErrorKind::TimedOut => {
+                decoder.idle(Instant::now()).into_iter().collect()
+            }
+            Err(e) => {
+                return Err(e).with_context(|| "Failed to read from the acceptor");
+            }
+        };
+        for centavos in inserted {
+            on_inserted(centavos)?;
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+use std::io::{ self, ErrorKind, Read };
+use std::time::Duration;
+
+pub enum Signal {
+    Bytes(Vec<u8>),
+    // The line stays quiet, reads time out like a real port.
+    Silence(Duration),
+}
+
+// Serial port that plays back a script, then reports being unplugged.
+pub struct SimulatedAcceptor {
+    script: VecDeque<Signal>,
+}
+
+impl SimulatedAcceptor {
+    pub fn new(script: Vec<Signal>) -> Self {
+        SimulatedAcceptor { script: script.into() }
+    }
+
+    // Pulse trains of the given lengths, each followed by `gap` of silence.
+    pub fn pulses(trains: &[u8], gap: Duration) -> Self {
+        Self::new(
+            trains
+                .iter()
+                .flat_map(|pulses| {
+                    (0..*pulses)
+                        .map(|_| Signal::Bytes(vec![0]))
+                        .chain(std::iter::once(Signal::Silence(gap)))
+                })
+                .collect()
+        )
+    }
+}
+
+impl Read for SimulatedAcceptor {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match self.script.pop_front() {
+            Some(Signal::Bytes(bytes)) => {
+                buffer[..bytes.len()].copy_from_slice(&bytes);
+                Ok(bytes.len())
+            }
+            Some(Signal::Silence(duration)) => {
+                std::thread::sleep(duration);
+                Err(io::Error::new(ErrorKind::TimedOut, "Operation timed out"))
+            }
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acceptor::decoder::Decoder;
+    use crate::acceptor::read_inserted;
+    use crate::payment::PaymentSource;
+    use crate::settings::appconfigmodels::{ Acceptor, AcceptorMode, Denomination };
+
+    fn inserted(port: &mut SimulatedAcceptor, config: &Acceptor) -> Vec<u64> {
+        let mut centavos = Vec::new();
+        let result = read_inserted(port, &mut Decoder::new(config), |denomination| {
+            centavos.push(denomination);
+            Ok(())
+        });
+        assert!(result.is_err(), "the simulated port ends unplugged");
+        centavos
+    }
+
+    #[test]
+    fn test_pulse_acceptor_inserts_coins() {
+        let config = Acceptor { pulse_gap_ms: 20, ..Acceptor::default() };
+        let mut port = SimulatedAcceptor::pulses(&[1, 5, 3, 10], Duration::from_millis(30));
+        assert_eq!(inserted(&mut port, &config), vec![100, 500, 1000]);
+    }
+
+    #[test]
+    fn test_serial_bill_acceptor_inserts_bills() {
+        let config = Acceptor {
+            mode: AcceptorMode::Serial,
+            source: PaymentSource::Bill,
+            denominations: vec![
+                Denomination { signal: 2, centavos: 2000 },
+                Denomination { signal: 5, centavos: 5000 }
+            ],
+            ..Acceptor::default()
+        };
+        let mut port = SimulatedAcceptor::new(
+            vec![
+                Signal::Bytes(vec![5]),
+                Signal::Silence(Duration::ZERO),
+                Signal::Bytes(vec![2, 9])
+            ]
+        );
+        assert_eq!(inserted(&mut port, &config), vec![5000, 2000]);
+    }
+}
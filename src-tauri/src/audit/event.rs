@@ -0,0 +1,212 @@
+use serde::{ Deserialize, Serialize };
+
+use crate::payment::PaymentSource;
+
+// Actor of the changes made from the admin settings of this PC.
+pub const ADMIN_ACTOR: &str = "admin";
+
+// Something an owner may want to check later, e.g. who added time. Stored as
+// `{ "type": ..., ... }` inside each audit entry.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditEvent {
+    // Payment turned into time on this station.
+    PaymentReceived {
+        amount_centavos: u64,
+        denomination_centavos: Option<u64>,
+        source: PaymentSource,
+        seconds: u64,
+    },
+    // `/addtime` request received from a paired client.
+    RemotePayment {
+        amount_centavos: u64,
+        source: PaymentSource,
+        address: String,
+    },
+    // Time added or removed by hand from the station.
+    ManualAdjustment {
+        seconds: i64,
+        reason: String,
+        // `admin` on the station itself, otherwise the paired counter it came
+        // from.
+        #[serde(default)]
+        actor: String,
+    },
+    // Time added or removed on a paired station from the counter, recorded
+    // on the counter. Ending a session removes the time that was left.
+    CounterAdjustment {
+        station: String,
+        seconds: i64,
+        reason: String,
+    },
+    // Voucher used on `station`, recorded where it was generated and where
+    // the time was added.
+    VoucherRedeemed {
+        code: String,
+        station: String,
+        seconds: u64,
+    },
+    VoucherBatchCreated {
+        batch_id: i64,
+        codes: u64,
+        seconds: u64,
+    },
+    // Member balance handed to `station` for a session, or given back when
+    // signing out. Recorded where the account is kept and on the station.
+    MemberSignedIn {
+        username: String,
+        station: String,
+        seconds: u64,
+    },
+    MemberSignedOut {
+        username: String,
+        station: String,
+        seconds: u64,
+    },
+    // Balance bought at the counter.
+    MemberToppedUp {
+        username: String,
+        amount_centavos: u64,
+        seconds: u64,
+    },
+    // Message sent from the counter to one station, or to `all`.
+    MessageSent {
+        to: String,
+        text: String,
+    },
+    // Bonus time added on top of a payment.
+    PromotionApplied {
+        name: String,
+        bonus_seconds: u64,
+    },
+    // Time left taken off `station` as a single use code, by the customer or
+    // after the station stopped mid session.
+    TimeSaved {
+        code: String,
+        station: String,
+        seconds: u64,
+    },
+    TimeRecovered {
+        code: String,
+        station: String,
+        seconds: u64,
+    },
+    AdminLogin,
+    AdminLoginFailed,
+    ConfigChanged {
+        section: String,
+        details: String,
+    },
+    LicenseChanged {
+        action: String,
+        serial_number: String,
+    },
+    SessionEnded,
+}
+
+impl AuditEvent {
+    // Same names as the serialized `type`, used to filter queries.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AuditEvent::PaymentReceived { .. } => "payment_received",
+            AuditEvent::RemotePayment { .. } => "remote_payment",
+            AuditEvent::ManualAdjustment { .. } => "manual_adjustment",
+            AuditEvent::CounterAdjustment { .. } => "counter_adjustment",
+            AuditEvent::VoucherRedeemed { .. } => "voucher_redeemed",
+            AuditEvent::VoucherBatchCreated { .. } => "voucher_batch_created",
+            AuditEvent::MemberSignedIn { .. } => "member_signed_in",
+            AuditEvent::MemberSignedOut { .. } => "member_signed_out",
+            AuditEvent::MemberToppedUp { .. } => "member_topped_up",
+            AuditEvent::MessageSent { .. } => "message_sent",
+            AuditEvent::PromotionApplied { .. } => "promotion_applied",
+            AuditEvent::TimeSaved { .. } => "time_saved",
+            AuditEvent::TimeRecovered { .. } => "time_recovered",
+            AuditEvent::AdminLogin => "admin_login",
+            AuditEvent::AdminLoginFailed => "admin_login_failed",
+            AuditEvent::ConfigChanged { .. } => "config_changed",
+            AuditEvent::LicenseChanged { .. } => "license_changed",
+            AuditEvent::SessionEnded => "session_ended",
+        }
+    }
+
+    pub fn license_changed(action: &str, serial_number: &str) -> Self {
+        AuditEvent::LicenseChanged {
+            action: action.to_string(),
+            serial_number: serial_number.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_matches_serialized_type() {
+        let events = [
+            AuditEvent::PaymentReceived {
+                amount_centavos: 5000,
+                denomination_centavos: Some(5000),
+                source: PaymentSource::Bill,
+                seconds: 250,
+            },
+            AuditEvent::RemotePayment {
+                amount_centavos: 100,
+                source: PaymentSource::Coin,
+                address: "192.168.1.50".to_string(),
+            },
+            AuditEvent::ManualAdjustment {
+                seconds: -60,
+                reason: "refund".to_string(),
+                actor: ADMIN_ACTOR.to_string(),
+            },
+            AuditEvent::CounterAdjustment {
+                station: "station-hwid".to_string(),
+                seconds: 900,
+                reason: "Coin jammed".to_string(),
+            },
+            AuditEvent::VoucherRedeemed {
+                code: "ABCD-EFGH-JKMN".to_string(),
+                station: "station-01".to_string(),
+                seconds: 3600,
+            },
+            AuditEvent::VoucherBatchCreated { batch_id: 1, codes: 50, seconds: 3600 },
+            AuditEvent::MemberSignedIn {
+                username: "juan".to_string(),
+                station: "station-01".to_string(),
+                seconds: 3600,
+            },
+            AuditEvent::MemberSignedOut {
+                username: "juan".to_string(),
+                station: "station-01".to_string(),
+                seconds: 1800,
+            },
+            AuditEvent::MemberToppedUp {
+                username: "juan".to_string(),
+                amount_centavos: 5000,
+                seconds: 250,
+            },
+            AuditEvent::MessageSent { to: "all".to_string(), text: "Closing soon".to_string() },
+            AuditEvent::PromotionApplied { name: "Early bird".to_string(), bonus_seconds: 60 },
+            AuditEvent::TimeSaved {
+                code: "ABCD-EFGH-JKMN".to_string(),
+                station: "station-01".to_string(),
+                seconds: 1800,
+            },
+            AuditEvent::TimeRecovered {
+                code: "ABCD-EFGH-JKMN".to_string(),
+                station: "station-01".to_string(),
+                seconds: 900,
+            },
+            AuditEvent::AdminLogin,
+            AuditEvent::AdminLoginFailed,
+            AuditEvent::ConfigChanged { section: "server".to_string(), details: String::new() },
+            AuditEvent::license_changed("activated", "SERIAL-123"),
+            AuditEvent::SessionEnded,
+        ];
+        for event in events {
+            let json = serde_json::to_value(&event).unwrap();
+            assert_eq!(json["type"], event.kind());
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{ Context, Result };
+use chrono::{ DateTime, Utc };
+use ring::hmac;
+use ring::rand::{ SecureRandom, SystemRandom };
+use serde::{ Deserialize, Serialize };
+
+use super::event::AuditEvent;
+use crate::db::DatabaseState;
+
+// `previous_hash` of the first entry.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// One audit entry. `hash` covers the other fields, including the
+// hash of the entry before it, so editing or removing an entry breaks the chain
+// from that point on. It is keyed with the audit key, kept outside of the
+// database, so the chain cannot be rebuilt from the database alone.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub sequence: u64,
+    pub timestamp: DateTime<Utc>,
+    pub event: AuditEvent,
+    pub previous_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn new(
+        sequence: u64,
+        timestamp: DateTime<Utc>,
+        event: AuditEvent,
+        previous_hash: String,
+        key: &hmac::Key
+    ) -> Result<Self> {
+        let mut entry = AuditEntry {
+            sequence,
+            timestamp,
+            event,
+            previous_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash(key)?;
+        Ok(entry)
+    }
+
+    fn compute_hash(&self, key: &hmac::Key) -> Result<String> {
+        let content = serde_json::to_string(&(self.sequence, self.timestamp, &self.event))?;
+        let mut context = hmac::Context::with_key(key);
+        context.update(self.previous_hash.as_bytes());
+        context.update(content.as_bytes());
+        Ok(
+            context
+                .sign()
+                .as_ref()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect()
+        )
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct AuditQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    // `AuditEvent::kind`, e.g. `manual_adjustment`.
+    pub event_type: Option<String>,
+}
+
+// Where the chain breaks, `None` when every entry is intact.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AuditVerification {
+    pub entries: u64,
+    pub first_invalid_sequence: Option<u64>,
+}
+
+// Reads the key the audit entries are signed with, creating it on first use.
+// On unix only its owner can read it. On Windows it gets the ACL of the
+// config directory, so that directory must be closed to other users.
+pub fn load_key(path: &Path) -> Result<hmac::Key> {
+    if path.exists() {
+        let key = fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+        return Ok(hmac::Key::new(hmac::HMAC_SHA256, &key));
+    }
+
+    let mut key = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut key)
+        .map_err(|_| anyhow::anyhow!("Failed to generate the audit key"))?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(&key))
+        .with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(hmac::Key::new(hmac::HMAC_SHA256, &key))
+}
+
+// Append-only, hash-chained audit log kept in the database.
+pub struct AuditLog {
+    database: DatabaseState,
+    key: hmac::Key,
+    // Sequence and hash of the last entry.
+    head: Mutex<(u64, String)>,
+}
+
+impl AuditLog {
+    pub fn open(database: DatabaseState, key: hmac::Key) -> Result<Self> {
+        let head = database.audit().last()?.unwrap_or((0, GENESIS_HASH.to_string()));
+        Ok(AuditLog { database, key, head: Mutex::new(head) })
+    }
+
+    // Failing to audit must not stop the station from working, so errors are
+    // only logged.
+    pub fn record(&self, event: AuditEvent) {
+        if let Err(e) = self.append(event, Utc::now()) {
+            tracing::error!("Failed to write audit entry: {:#}", e);
+        }
+    }
+
+    fn append(&self, event: AuditEvent, timestamp: DateTime<Utc>) -> Result<AuditEntry> {
+        let mut head = self.head.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        let entry = AuditEntry::new(head.0 + 1, timestamp, event, head.1.clone(), &self.key)?;
+        self.database.audit().append(&entry).with_context(|| "Failed to write audit log")?;
+
+        *head = (entry.sequence, entry.hash.clone());
+        Ok(entry)
+    }
+
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>> {
+        self.database.audit().query(query)
+    }
+
+    pub fn verify(&self) -> Result<AuditVerification> {
+        let _head = self.head.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        let entries = self.database.audit().all()?;
+
+        let mut previous_hash = GENESIS_HASH.to_string();
+        let mut first_invalid_sequence = None;
+        for (index, entry) in entries.iter().enumerate() {
+            let sequence = (index as u64) + 1;
+            match entry {
+                Ok(entry) if
+                    entry.sequence == sequence &&
+                    entry.previous_hash == previous_hash &&
+                    entry.compute_hash(&self.key)? == entry.hash
+                => {
+                    previous_hash = entry.hash.clone();
+                }
+                _ => {
+                    first_invalid_sequence = Some(sequence);
+                    break;
+                }
+            }
+        }
+
+        Ok(AuditVerification { entries: entries.len() as u64, first_invalid_sequence })
+    }
+}
+
+#[cfg(test)]
+pub mod testing {
+    use ring::hmac;
+
+    pub fn key() -> hmac::Key {
+        hmac::Key::new(hmac::HMAC_SHA256, &[3u8; 32])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::payment::PaymentSource;
+    use chrono::Duration;
+    use std::sync::Arc;
+
+    fn audit_log() -> AuditLog {
+        AuditLog::open(Arc::new(Database::open_in_memory().unwrap()), testing::key()).unwrap()
+    }
+
+    #[test]
+    fn test_entries_are_chained_and_survive_reopening() {
+        let log = audit_log();
+        let first = log.append(AuditEvent::AdminLogin, Utc::now()).unwrap();
+        let reopened = AuditLog::open(log.database.clone(), testing::key()).unwrap();
+        let second = reopened.append(AuditEvent::SessionEnded, Utc::now()).unwrap();
+
+        assert_eq!(first.previous_hash, GENESIS_HASH);
+        assert_eq!(second.sequence, 2);
+        assert_eq!(second.previous_hash, first.hash);
+        assert_eq!(reopened.verify().unwrap(), AuditVerification {
+            entries: 2,
+            first_invalid_sequence: None,
+        });
+    }
+
+    #[test]
+    fn test_verify_detects_edited_entry() {
+        let log = audit_log();
+        let coin = |amount_centavos| AuditEvent::PaymentReceived {
+            amount_centavos,
+            denomination_centavos: Some(100),
+            source: PaymentSource::Coin,
+            seconds: amount_centavos / 20,
+        };
+        log.append(coin(100), Utc::now()).unwrap();
+        log.append(coin(200), Utc::now()).unwrap();
+        log.append(AuditEvent::SessionEnded, Utc::now()).unwrap();
+
+        log.database
+            .execute_batch(
+                "UPDATE audit_entries
+                 SET event = replace(event, '\"amount_centavos\":200', '\"amount_centavos\":2000')"
+            )
+            .unwrap();
+        assert_eq!(log.verify().unwrap().first_invalid_sequence, Some(2));
+
+        // Dropping the edited entry does not help either
+        log.database.execute_batch("DELETE FROM audit_entries WHERE sequence = 2").unwrap();
+        assert_eq!(log.verify().unwrap().first_invalid_sequence, Some(2));
+    }
+
+    #[test]
+    fn test_chain_rebuilt_without_the_key_does_not_verify() {
+        let log = audit_log();
+        log.append(AuditEvent::AdminLogin, Utc::now()).unwrap();
+
+        let other_key = hmac::Key::new(hmac::HMAC_SHA256, b"guessed key");
+        let forged = AuditLog::open(log.database.clone(), other_key).unwrap();
+        assert_eq!(forged.verify().unwrap().first_invalid_sequence, Some(1));
+    }
+
+    #[test]
+    fn test_key_is_created_once() {
+        let path = std::env::temp_dir().join(format!("cps-audit-{}.key", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let database: DatabaseState = Arc::new(Database::open_in_memory().unwrap());
+        let log = AuditLog::open(database.clone(), load_key(&path).unwrap()).unwrap();
+        log.append(AuditEvent::AdminLogin, Utc::now()).unwrap();
+
+        let reopened = AuditLog::open(database, load_key(&path).unwrap()).unwrap();
+        assert_eq!(reopened.verify().unwrap().first_invalid_sequence, None);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_query_by_date_range_and_type() {
+        let log = audit_log();
+        let now = Utc::now();
+        log.append(AuditEvent::AdminLoginFailed, now - Duration::days(2)).unwrap();
+        log.append(AuditEvent::AdminLogin, now - Duration::days(1)).unwrap();
+        log.append(
+            AuditEvent::ManualAdjustment {
+                seconds: 600,
+                reason: "friend".to_string(),
+                actor: "admin".to_string(),
+            },
+            now
+        ).unwrap();
+
+        let recent = log
+            .query(&AuditQuery { from: Some(now - Duration::hours(36)), ..AuditQuery::default() })
+            .unwrap();
+        assert_eq!(recent.len(), 2);
+
+        let failed = log
+            .query(&AuditQuery {
+                to: Some(now),
+                event_type: Some("admin_login_failed".to_string()),
+                ..AuditQuery::default()
+            })
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].event, AuditEvent::AdminLoginFailed);
+    }
+}
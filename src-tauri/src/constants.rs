@@ -3,3 +3,12 @@ pub const HANDLER_TIMER_DONE: &str = "handler_timer_done";
 pub const HANDLER_TIMER_UPDATE: &str = "handler_timer_update";
 pub const HANDLER_ADDTIME: &str = "handler_addtime";
 pub const HANDLER_SETTINGS_ROUTE: &str = "handler_settings_route";
+pub const HANDLER_SERVER_STATUS: &str = "handler_server_status";
+pub const HANDLER_LICENSE_LIMIT: &str = "handler_license_limit";
+pub const HANDLER_LICENSE_REVOKED: &str = "handler_license_revoked";
+pub const HANDLER_ACTIVATION_PROGRESS: &str = "handler_activation_progress";
+
+pub const HANDLER_MEMBER: &str = "handler_member";
+pub const HANDLER_TIME_SAVED: &str = "handler_time_saved";
+pub const HANDLER_MESSAGE: &str = "handler_message";
+pub const HANDLER_PAIRING_CODE: &str = "handler_pairing_code";
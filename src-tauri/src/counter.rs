@@ -0,0 +1,174 @@
+use anyhow::{ Context, Result };
+use chrono::{ DateTime, Utc };
+use serde::Serialize;
+use tokio::task::JoinSet;
+use tracing::warn;
+
+use crate::db::{ Database, DatabaseState };
+use crate::http_server::client;
+use crate::http_server::v1::models::SalesReportQuery;
+use crate::reports::summary::{ Granularity, ReportRow };
+use crate::reports::{ self, ReportFormat };
+use crate::settings::appconfigmodels::{ Client, Role };
+use crate::station::StationStatus;
+
+// Paired station as listed on the counter window.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StationOverview {
+    pub hwid: String,
+    pub address: String,
+    // `None` when the station could not be reached, see `error`.
+    pub station: Option<String>,
+    pub remaining_seconds: u64,
+    pub member: Option<String>,
+    pub pending_messages: Vec<String>,
+    pub acknowledged_messages: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl StationOverview {
+    fn new(client: Client, status: Result<StationStatus>) -> Self {
+        let mut overview = StationOverview {
+            hwid: client.hwid,
+            address: client.address,
+            station: None,
+            remaining_seconds: 0,
+            member: None,
+            pending_messages: Vec::new(),
+            acknowledged_messages: Vec::new(),
+            error: None,
+        };
+        match status {
+            Ok(status) => {
+                overview.station = Some(status.station);
+                overview.remaining_seconds = status.remaining_seconds;
+                overview.member = status.member;
+                overview.pending_messages = status.pending_messages;
+                overview.acknowledged_messages = status.acknowledged_messages;
+            }
+            Err(e) => {
+                warn!(station = overview.address, "Failed to ask for the station status: {:#}", e);
+                overview.error = Some(format!("{:#}", e));
+            }
+        }
+        overview
+    }
+}
+
+// Sales of the counter and every paired station it could reach.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CounterSales {
+    pub rows: Vec<ReportRow>,
+    // Addresses of the stations missing from `rows`.
+    pub unreachable: Vec<String>,
+}
+
+pub fn ensure_counter(role: Role) -> Result<()> {
+    if role != Role::Counter {
+        return Err(anyhow::anyhow!("Only the counter manages stations, see the role setting"));
+    }
+    Ok(())
+}
+
+// Asks every paired station what it is doing, all at once so a station that
+// is switched off does not hold up the others.
+pub async fn overview(database: &DatabaseState) -> Result<Vec<StationOverview>> {
+    let mut requests = JoinSet::new();
+    for station in database.clients().all()?.into_values() {
+        let database = database.clone();
+        requests.spawn(async move {
+            let status = client::station_status(&database, &station).await;
+            StationOverview::new(station, status)
+        });
+    }
+
+    let mut stations = Vec::new();
+    while let Some(overview) = requests.join_next().await {
+        stations.push(overview?);
+    }
+    stations.sort_by(|a, b| (&a.station, &a.address).cmp(&(&b.station, &b.address)));
+    Ok(stations)
+}
+
+// Every paired station when `hwid` is `None`.
+pub fn recipients(database: &Database, hwid: Option<&str>) -> Result<Vec<Client>> {
+    match hwid {
+        Some(hwid) => Ok(vec![find_station(database, hwid)?]),
+        None => Ok(database.clients().all()?.into_values().collect()),
+    }
+}
+
+pub fn find_station(database: &Database, hwid: &str) -> Result<Client> {
+    database
+        .clients()
+        .all()?
+        .remove(hwid)
+        .with_context(|| format!("Station {} is not paired with the counter", hwid))
+}
+
+pub async fn sales(
+    database: &DatabaseState,
+    granularity: Granularity,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>
+) -> Result<CounterSales> {
+    // Member top-ups are sold at the counter itself
+    let mut rows = reports::sales_report(database, granularity, from, to)?;
+
+    let query = SalesReportQuery { granularity, from, to, format: ReportFormat::Json };
+    let mut requests = JoinSet::new();
+    for station in database.clients().all()?.into_values() {
+        let database = database.clone();
+        let query = query.clone();
+        requests.spawn(async move {
+            let sales = client::station_sales(&database, &station, &query).await;
+            (station, sales)
+        });
+    }
+
+    let mut unreachable = Vec::new();
+    while let Some(joined) = requests.join_next().await {
+        match joined? {
+            (_, Ok(station_rows)) => rows.extend(station_rows),
+            (station, Err(e)) => {
+                warn!(station = station.address, "Failed to ask for the station sales: {:#}", e);
+                unreachable.push(station.address);
+            }
+        }
+    }
+    rows.sort_by(|a, b| (&a.period, &a.station).cmp(&(&b.period, &b.station)));
+    unreachable.sort();
+    Ok(CounterSales { rows, unreachable })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_unreachable_station_is_listed_with_its_error() {
+        let database: DatabaseState = Arc::new(Database::open_in_memory().unwrap());
+        let station = Client {
+            // Nothing listens on port 1
+            address: "http://127.0.0.1:1".to_string(),
+            hwid: "station-hwid".to_string(),
+            cert_fingerprint: String::new(),
+            credential: String::new(),
+        };
+        database.clients().upsert(&station).unwrap();
+
+        let stations = overview(&database).await.unwrap();
+        assert_eq!(stations.len(), 1);
+        assert_eq!(stations[0].hwid, "station-hwid");
+        assert!(stations[0].station.is_none());
+        assert!(stations[0].error.is_some());
+
+        let sales = sales(&database, Granularity::Day, None, None).await.unwrap();
+        assert!(sales.rows.is_empty());
+        assert_eq!(sales.unreachable, vec!["http://127.0.0.1:1".to_string()]);
+
+        assert!(find_station(&database, "other-hwid").is_err());
+        assert!(ensure_counter(Role::Station).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::{ Context, Result };
+use rusqlite::{ params, Row };
+
+use super::Database;
+use crate::audit::store::{ AuditEntry, AuditQuery };
+
+const SELECT_ENTRIES: &str =
+    "SELECT sequence, timestamp, event, previous_hash, hash FROM audit_entries";
+
+// Rows of the hash-chained audit log. Chaining and verification live in
+// `AuditLog`, this only stores the entries.
+pub struct AuditRepository<'a> {
+    pub(super) database: &'a Database,
+}
+
+impl AuditRepository<'_> {
+    pub fn append(&self, entry: &AuditEntry) -> Result<()> {
+        self.database.connection()?.execute(
+            "INSERT INTO audit_entries (sequence, timestamp, event_type, event, previous_hash, hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                entry.sequence,
+                entry.timestamp,
+                entry.event.kind(),
+                serde_json::to_string(&entry.event)?,
+                entry.previous_hash,
+                entry.hash
+            ]
+        )?;
+        Ok(())
+    }
+
+    // Sequence and hash of the newest entry.
+    pub fn last(&self) -> Result<Option<(u64, String)>> {
+        let connection = self.database.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT sequence, hash FROM audit_entries ORDER BY sequence DESC LIMIT 1"
+        )?;
+        let mut rows = statement.query([])?;
+        match rows.next()? {
+            Some(row) => Ok(Some((row.get(0)?, row.get(1)?))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>> {
+        let connection = self.database.connection()?;
+        let mut statement = connection.prepare(
+            &format!(
+                "{} WHERE (?1 IS NULL OR timestamp >= ?1) AND (?2 IS NULL OR timestamp < ?2)
+                 AND (?3 IS NULL OR event_type = ?3) ORDER BY sequence",
+                SELECT_ENTRIES
+            )
+        )?;
+        let entries = statement
+            .query_map(params![query.from, query.to, query.event_type], entry_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries.into_iter().filter_map(|entry| entry.ok()).collect())
+    }
+
+    // Every entry in sequence order. An event that no longer parses is kept as
+    // an error so verification can report it.
+    pub fn all(&self) -> Result<Vec<Result<AuditEntry>>> {
+        let connection = self.database.connection()?;
+        let mut statement = connection.prepare(&format!("{} ORDER BY sequence", SELECT_ENTRIES))?;
+        let entries = statement.query_map([], entry_from_row)?.collect::<rusqlite::Result<_>>()?;
+        Ok(entries)
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.last()?.is_none())
+    }
+}
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<Result<AuditEntry>> {
+    let sequence = row.get(0)?;
+    let timestamp = row.get(1)?;
+    let event: String = row.get(2)?;
+    let previous_hash = row.get(3)?;
+    let hash = row.get(4)?;
+    Ok(
+        serde_json
+            ::from_str(&event)
+            .with_context(|| "Malformed audit entry")
+            .map(|event| AuditEntry { sequence, timestamp, event, previous_hash, hash })
+    )
+}
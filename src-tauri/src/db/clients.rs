@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use rusqlite::params;
+
+use super::Database;
+use crate::settings::appconfigmodels::Client;
+
+// Stations paired with this one, keyed by hardware id.
+pub struct ClientRepository<'a> {
+    pub(super) database: &'a Database,
+}
+
+impl ClientRepository<'_> {
+    // Pairing again with a known station replaces its address, pinned
+    // certificate and credential.
+    pub fn upsert(&self, client: &Client) -> Result<()> {
+        self.database.connection()?.execute(
+            "INSERT INTO clients (hwid, address, cert_fingerprint, credential)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (hwid) DO UPDATE
+             SET address = excluded.address, cert_fingerprint = excluded.cert_fingerprint,
+                 credential = excluded.credential",
+            params![client.hwid, client.address, client.cert_fingerprint, client.credential]
+        )?;
+        Ok(())
+    }
+
+    pub fn all(&self) -> Result<HashMap<String, Client>> {
+        let connection = self.database.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT hwid, address, cert_fingerprint, credential FROM clients"
+        )?;
+        let clients = statement
+            .query_map([], |row| {
+                Ok(Client {
+                    hwid: row.get(0)?,
+                    address: row.get(1)?,
+                    cert_fingerprint: row.get(2)?,
+                    credential: row.get(3)?,
+                })
+            })?
+            .map(|client| client.map(|client| (client.hwid.clone(), client)))
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(clients)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upsert_replaces_existing_client() {
+        let database = Database::open_in_memory().unwrap();
+        let client = Client {
+            address: "192.168.1.50".to_string(),
+            hwid: "station-hwid".to_string(),
+            cert_fingerprint: "aa".to_string(),
+            credential: "secret".to_string(),
+        };
+        database.clients().upsert(&client).unwrap();
+        database
+            .clients()
+            .upsert(&Client { cert_fingerprint: "bb".to_string(), ..client })
+            .unwrap();
+
+        let clients = database.clients().all().unwrap();
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients["station-hwid"].cert_fingerprint, "bb");
+    }
+}
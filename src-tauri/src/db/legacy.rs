@@ -0,0 +1,205 @@
+//! One-time import of the history older versions kept in JSON files. Each
+//! file is renamed to `*.imported` afterwards so it is only read once and
+//! still around if something went wrong.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{ Context, Result };
+use chrono::{ DateTime, Utc };
+use serde::Deserialize;
+use tracing::{ info, warn };
+
+use super::transactions::NewTransaction;
+use super::Database;
+use crate::audit::store::AuditEntry;
+use crate::payment::{ Payment, PaymentSource };
+use crate::settings::appconfigmodels::Client;
+
+// Line of the old `sales.jsonl`, when every credit was a ₱1 coin.
+#[derive(Deserialize)]
+struct LegacySalesRecord {
+    timestamp: DateTime<Utc>,
+    station: String,
+    #[serde(flatten)]
+    event: LegacySalesEvent,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum LegacySalesEvent {
+    Credit {
+        pesos: u64,
+        seconds: u64,
+    },
+    SessionStarted,
+}
+
+pub fn import_clients(database: &Database, clients: HashMap<String, Client>) -> Result<()> {
+    for client in clients.values() {
+        database.clients().upsert(client)?;
+    }
+    if !clients.is_empty() {
+        info!(clients = clients.len(), "Moved paired stations from the config to the database");
+    }
+    Ok(())
+}
+
+// The entries keep their sequence and hashes so the chain still verifies.
+pub fn import_audit_log(database: &Database, path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    if !database.audit().is_empty()? {
+        warn!("Audit entries already in the database, not importing {:?}", path);
+        return Ok(());
+    }
+
+    let entries: Vec<AuditEntry> = read_lines(path)?;
+    for entry in &entries {
+        database.audit().append(entry)?;
+    }
+    info!(entries = entries.len(), "Imported audit log");
+    mark_imported(path)
+}
+
+pub fn import_sales_ledger(database: &Database, path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    if !database.transactions().is_empty()? || !database.sessions().is_empty()? {
+        warn!("Sales already in the database, not importing {:?}", path);
+        return Ok(());
+    }
+
+    let records: Vec<LegacySalesRecord> = read_lines(path)?;
+    for record in &records {
+        match record.event {
+            LegacySalesEvent::Credit { pesos, seconds } => {
+                database.transactions().insert(&NewTransaction {
+                    station: &record.station,
+                    created_at: record.timestamp,
+                    payment: Payment {
+                        amount_centavos: pesos * 100,
+                        ..Payment::cash(PaymentSource::Coin, 100)
+                    },
+                    seconds,
+                    promotions: &[],
+                })?;
+            }
+            LegacySalesEvent::SessionStarted => {
+                database.sessions().start(&record.station, record.timestamp)?;
+            }
+        }
+    }
+    info!(records = records.len(), "Imported sales ledger");
+    mark_imported(path)
+}
+
+// Lines that no longer parse are skipped, the original file is kept anyway.
+fn read_lines<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Vec<T>> {
+    Ok(
+        fs
+            ::read_to_string(path)
+            .with_context(|| format!("Failed to read {:?}", path))?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| {
+                serde_json
+                    ::from_str(line)
+                    .inspect_err(|e| warn!("Skipping malformed line in {:?}: {}", path, e))
+                    .ok()
+            })
+            .collect()
+    )
+}
+
+fn mark_imported(path: &Path) -> Result<()> {
+    fs
+        ::rename(path, path.with_extension("jsonl.imported"))
+        .with_context(|| format!("Failed to rename {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::event::AuditEvent;
+    use crate::audit::store::{ testing, AuditLog };
+    use std::sync::Arc;
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        let nanos = std::time::SystemTime
+            ::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("cps-{}-{}.jsonl", name, nanos))
+    }
+
+    #[test]
+    fn test_import_keeps_the_audit_chain_and_sales() {
+        // Write the old files from a log that is then thrown away
+        let source = Arc::new(Database::open_in_memory().unwrap());
+        let log = AuditLog::open(source.clone(), testing::key()).unwrap();
+        log.record(AuditEvent::AdminLogin);
+        log.record(AuditEvent::SessionEnded);
+        let audit_path = temp_file("audit");
+        let lines: Vec<String> = source
+            .audit()
+            .all()
+            .unwrap()
+            .into_iter()
+            .map(|entry| serde_json::to_string(&entry.unwrap()).unwrap())
+            .collect();
+        fs::write(&audit_path, lines.join("\n")).unwrap();
+
+        let sales_path = temp_file("sales");
+        let lines = [
+            serde_json::json!({
+                "timestamp": "2026-10-18T15:30:00Z",
+                "station": "station-01",
+                "type": "session_started"
+            }),
+            serde_json::json!({
+                "timestamp": "2026-10-18T15:30:00Z",
+                "station": "station-01",
+                "type": "credit",
+                "credits": 2,
+                "pesos": 2,
+                "seconds": 10
+            }),
+        ];
+        let lines: Vec<String> = lines
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        fs::write(&sales_path, lines.join("\n")).unwrap();
+
+        let database = Arc::new(Database::open_in_memory().unwrap());
+        import_audit_log(&database, &audit_path).unwrap();
+        import_sales_ledger(&database, &sales_path).unwrap();
+
+        let log = AuditLog::open(database.clone(), testing::key()).unwrap();
+        log.record(AuditEvent::AdminLoginFailed);
+        let verification = log.verify().unwrap();
+        assert_eq!(verification.entries, 3);
+        assert_eq!(verification.first_invalid_sequence, None);
+        let transactions = database.transactions().created_between(None, None).unwrap();
+        assert_eq!(transactions[0].payment.amount_centavos, 200);
+        assert_eq!(transactions[0].seconds, 10);
+        assert_eq!(database.sessions().started_between(None, None).unwrap().len(), 1);
+
+        assert!(!audit_path.exists());
+        assert!(!sales_path.exists());
+
+        // A ledger left behind after importing it is not counted twice
+        fs::copy(sales_path.with_extension("jsonl.imported"), &sales_path).unwrap();
+        import_sales_ledger(&database, &sales_path).unwrap();
+        assert_eq!(database.transactions().created_between(None, None).unwrap().len(), 1);
+        fs::remove_file(&sales_path).unwrap();
+        for path in [audit_path, sales_path] {
+            fs::remove_file(path.with_extension("jsonl.imported")).unwrap();
+        }
+    }
+}
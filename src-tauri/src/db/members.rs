@@ -0,0 +1,208 @@
+use anyhow::Result;
+use chrono::{ DateTime, Utc };
+use rusqlite::{ params, OptionalExtension };
+use serde::Serialize;
+
+use super::Database;
+use crate::member::{ Credentials, MemberError };
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Member {
+    pub username: String,
+    pub balance_seconds: u64,
+    pub created_at: DateTime<Utc>,
+    // Station holding the balance while the member is signed in.
+    pub signed_in_station: Option<String>,
+}
+
+// Member accounts kept on this station. Usernames are not case sensitive.
+pub struct MemberRepository<'a> {
+    pub(super) database: &'a Database,
+}
+
+impl MemberRepository<'_> {
+    pub fn create(
+        &self,
+        username: &str,
+        credentials: &Credentials,
+        created_at: DateTime<Utc>
+    ) -> Result<()> {
+        let created = self.database.connection()?.execute(
+            "INSERT INTO members (username, pin_salt, pin_hash, created_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (username) DO NOTHING",
+            params![username, credentials.salt, credentials.hash, created_at]
+        )?;
+        if created == 0 {
+            return Err(anyhow::anyhow!("Username {} is already taken", username));
+        }
+        Ok(())
+    }
+
+    pub fn all(&self) -> Result<Vec<Member>> {
+        let connection = self.database.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT username, balance_seconds, created_at, signed_in_station
+             FROM members ORDER BY username"
+        )?;
+        let members = statement
+            .query_map([], |row| {
+                Ok(Member {
+                    username: row.get(0)?,
+                    balance_seconds: row.get(1)?,
+                    created_at: row.get(2)?,
+                    signed_in_station: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(members)
+    }
+
+    pub fn credentials(&self, username: &str) -> Result<Option<Credentials>> {
+        let credentials = self.database
+            .connection()?
+            .query_row(
+                "SELECT pin_salt, pin_hash FROM members WHERE username = ?1",
+                params![username],
+                |row| Ok(Credentials { salt: row.get(0)?, hash: row.get(1)? })
+            )
+            .optional()?;
+        Ok(credentials)
+    }
+
+    pub fn set_credentials(&self, username: &str, credentials: &Credentials) -> Result<bool> {
+        let updated = self.database.connection()?.execute(
+            "UPDATE members SET pin_salt = ?2, pin_hash = ?3 WHERE username = ?1",
+            params![username, credentials.salt, credentials.hash]
+        )?;
+        Ok(updated == 1)
+    }
+
+    // Hands the whole balance to `station` for a session. Only `token` sent by
+    // `peer`, the paired station asking (`None` for this one), can give it
+    // back. The same station may sign in again, e.g. after a crash.
+    pub fn check_out(
+        &self,
+        username: &str,
+        station: &str,
+        peer: Option<&str>,
+        token: &str
+    ) -> Result<Result<u64, MemberError>> {
+        let mut connection = self.database.connection()?;
+        let transaction = connection.transaction()?;
+        let member: Option<(u64, Option<String>)> = transaction
+            .query_row(
+                "SELECT balance_seconds, signed_in_station FROM members WHERE username = ?1",
+                params![username],
+                |row| Ok((row.get(0)?, row.get(1)?))
+            )
+            .optional()?;
+        let balance = match member {
+            None => {
+                return Ok(Err(MemberError::NotFound));
+            }
+            Some((_, Some(other))) if other != station => {
+                return Ok(Err(MemberError::SignedIn { station: other }));
+            }
+            Some((balance, _)) => balance,
+        };
+
+        transaction.execute(
+            "UPDATE members
+             SET balance_seconds = 0, signed_in_station = ?2, session_token = ?3,
+                 session_peer = ?4
+             WHERE username = ?1",
+            params![username, station, token, peer]
+        )?;
+        transaction.commit()?;
+        Ok(Ok(balance))
+    }
+
+    // Ends the session started with `token` by `peer`, adding the time left
+    // to the balance. Returns the new balance.
+    pub fn check_in(
+        &self,
+        username: &str,
+        peer: Option<&str>,
+        token: &str,
+        seconds: u64
+    ) -> Result<Result<u64, MemberError>> {
+        let balance = self.database
+            .connection()?
+            .query_row(
+                "UPDATE members
+                 SET balance_seconds = balance_seconds + ?4, signed_in_station = NULL,
+                     session_token = NULL, session_peer = NULL
+                 WHERE username = ?1 AND session_peer IS ?2 AND session_token = ?3
+                 RETURNING balance_seconds",
+                params![username, peer, token, seconds],
+                |row| row.get(0)
+            )
+            .optional()?;
+        Ok(balance.ok_or(MemberError::NotSignedIn))
+    }
+
+    // Returns the new balance.
+    pub fn top_up(&self, username: &str, seconds: u64) -> Result<Result<u64, MemberError>> {
+        let balance = self.database
+            .connection()?
+            .query_row(
+                "UPDATE members SET balance_seconds = balance_seconds + ?2 WHERE username = ?1
+                 RETURNING balance_seconds",
+                params![username, seconds],
+                |row| row.get(0)
+            )
+            .optional()?;
+        Ok(balance.ok_or(MemberError::NotFound))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balance_is_held_by_one_station_at_a_time() {
+        let database = Database::open_in_memory().unwrap();
+        let credentials = Credentials { salt: vec![1; 16], hash: vec![2; 32] };
+        let members = database.members();
+        members.create("Juan", &credentials, Utc::now()).unwrap();
+        assert!(members.create("juan", &credentials, Utc::now()).is_err());
+        assert_eq!(members.top_up("JUAN", 3600).unwrap(), Ok(3600));
+
+        assert_eq!(members.check_out("juan", "station-01", None, "token-1").unwrap(), Ok(3600));
+        assert_eq!(
+            members.check_out("juan", "station-02", None, "token-2").unwrap(),
+            Err(MemberError::SignedIn { station: "station-01".to_string() })
+        );
+        assert_eq!(members.all().unwrap()[0].balance_seconds, 0);
+
+        let not_signed_in = Err(MemberError::NotSignedIn);
+        assert_eq!(members.check_in("juan", None, "token-2", 600).unwrap(), not_signed_in);
+        assert_eq!(members.check_in("juan", None, "token-1", 1200).unwrap(), Ok(1200));
+        assert_eq!(members.check_in("juan", None, "token-1", 1200).unwrap(), not_signed_in);
+        assert_eq!(members.all().unwrap()[0].signed_in_station, None);
+        assert_eq!(members.top_up("pedro", 60).unwrap(), Err(MemberError::NotFound));
+    }
+
+    #[test]
+    fn test_only_the_station_holding_the_balance_gives_it_back() {
+        let database = Database::open_in_memory().unwrap();
+        let credentials = Credentials { salt: vec![1; 16], hash: vec![2; 32] };
+        let members = database.members();
+        members.create("juan", &credentials, Utc::now()).unwrap();
+        members.top_up("juan", 600).unwrap().unwrap();
+        let peer = Some("station-02-hwid");
+        assert_eq!(members.check_out("juan", "station-02", peer, "token-1").unwrap(), Ok(600));
+
+        // Someone who got hold of the token cannot inflate the balance
+        let not_signed_in = Err(MemberError::NotSignedIn);
+        let inflated = u64::MAX / 2;
+        assert_eq!(members.check_in("juan", None, "token-1", inflated).unwrap(), not_signed_in);
+        assert_eq!(
+            members.check_in("juan", Some("other-hwid"), "token-1", inflated).unwrap(),
+            not_signed_in
+        );
+        assert_eq!(members.all().unwrap()[0].balance_seconds, 0);
+        assert_eq!(members.check_in("juan", peer, "token-1", 300).unwrap(), Ok(300));
+    }
+}
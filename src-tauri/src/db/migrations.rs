@@ -0,0 +1,176 @@
+use anyhow::{ Context, Result };
+use rusqlite::Connection;
+
+// Applied in order, each exactly once. The number of applied migrations is
+// kept in `PRAGMA user_version`. Never edit a migration that has shipped, add
+// a new one instead.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE clients (
+        hwid TEXT PRIMARY KEY,
+        address TEXT NOT NULL,
+        cert_fingerprint TEXT NOT NULL DEFAULT ''
+    );
+
+    CREATE TABLE sessions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        station TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        ended_at TEXT
+    );
+    CREATE INDEX sessions_started_at ON sessions (started_at);
+
+    CREATE TABLE transactions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        station TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        credits INTEGER NOT NULL,
+        pesos INTEGER NOT NULL,
+        seconds INTEGER NOT NULL
+    );
+    CREATE INDEX transactions_created_at ON transactions (created_at);
+
+    CREATE TABLE audit_entries (
+        sequence INTEGER PRIMARY KEY,
+        timestamp TEXT NOT NULL,
+        event_type TEXT NOT NULL,
+        event TEXT NOT NULL,
+        previous_hash TEXT NOT NULL,
+        hash TEXT NOT NULL
+    );
+    CREATE INDEX audit_entries_timestamp ON audit_entries (timestamp);
+    ",
+    // Amounts in centavos with their denomination and source instead of
+    // whole peso credits. Older rows were all ₱1 coins.
+    "
+    ALTER TABLE transactions ADD COLUMN amount_centavos INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE transactions ADD COLUMN denomination_centavos INTEGER;
+    ALTER TABLE transactions ADD COLUMN source TEXT NOT NULL DEFAULT 'coin';
+    UPDATE transactions SET amount_centavos = pesos * 100, denomination_centavos = 100;
+    ALTER TABLE transactions DROP COLUMN credits;
+    ALTER TABLE transactions DROP COLUMN pesos;
+    ",
+    "
+    CREATE TABLE voucher_batches (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        label TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        seconds INTEGER NOT NULL,
+        expires_at TEXT NOT NULL
+    );
+
+    CREATE TABLE vouchers (
+        code TEXT PRIMARY KEY,
+        batch_id INTEGER NOT NULL REFERENCES voucher_batches (id),
+        redeemed_at TEXT,
+        redeemed_by TEXT
+    );
+    CREATE INDEX vouchers_batch_id ON vouchers (batch_id);
+    ",
+    // While a member is signed in their balance is held by that station and
+    // only the session token can give it back.
+    "
+    CREATE TABLE members (
+        username TEXT PRIMARY KEY COLLATE NOCASE,
+        pin_salt BLOB NOT NULL,
+        pin_hash BLOB NOT NULL,
+        balance_seconds INTEGER NOT NULL DEFAULT 0,
+        created_at TEXT NOT NULL,
+        signed_in_station TEXT,
+        session_token TEXT
+    );
+    ",
+    // Time left on the running session, written now and then so a station
+    // that crashed can give it back.
+    "
+    ALTER TABLE sessions ADD COLUMN remaining_seconds INTEGER NOT NULL DEFAULT 0;
+    ",
+    // Promotions that added to `seconds`, as a JSON array.
+    "
+    ALTER TABLE transactions ADD COLUMN promotions TEXT NOT NULL DEFAULT '[]';
+    ",
+    // Credential a paired station handed out, sent back on every request to
+    // it. `peers` are the stations and counters that paired with this one,
+    // only the hash of their credential is kept.
+    "
+    ALTER TABLE clients ADD COLUMN credential TEXT NOT NULL DEFAULT '';
+    CREATE TABLE peers (
+        hwid TEXT PRIMARY KEY,
+        address TEXT NOT NULL,
+        credential_hash BLOB NOT NULL UNIQUE,
+        paired_at TEXT NOT NULL
+    );
+    ",
+    // Paired station the balance was handed to over HTTP, `NULL` when the
+    // member signed in here. Only that station can give it back.
+    "
+    ALTER TABLE members ADD COLUMN session_peer TEXT;
+    "
+];
+
+pub fn migrate(connection: &mut Connection) -> Result<()> {
+    let applied: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if applied > MIGRATIONS.len() {
+        return Err(
+            anyhow::anyhow!(
+                "Database schema version {} is newer than this app supports ({})",
+                applied,
+                MIGRATIONS.len()
+            )
+        );
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let transaction = connection.transaction()?;
+        transaction
+            .execute_batch(migration)
+            .with_context(|| format!("Failed to apply database migration {}", index + 1))?;
+        transaction.pragma_update(None, "user_version", index + 1)?;
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credits_become_one_peso_coins() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(MIGRATIONS[0]).unwrap();
+        connection.pragma_update(None, "user_version", 1).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO transactions (station, created_at, credits, pesos, seconds)
+                 VALUES ('station-01', '2026-10-18 15:30:00+00:00', 5, 5, 25)"
+            )
+            .unwrap();
+
+        migrate(&mut connection).unwrap();
+        let row: (u64, Option<u64>, String, u64) = connection
+            .query_row(
+                "SELECT amount_centavos, denomination_centavos, source, seconds FROM transactions",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            )
+            .unwrap();
+        assert_eq!(row, (500, Some(100), "coin".to_string(), 25));
+    }
+
+    #[test]
+    fn test_migrations_apply_once() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        migrate(&mut connection).unwrap();
+
+        let version: usize = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+
+        connection.pragma_update(None, "user_version", MIGRATIONS.len() + 1).unwrap();
+        assert!(migrate(&mut connection).is_err());
+    }
+}
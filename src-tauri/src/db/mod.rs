@@ -0,0 +1,95 @@
+//! Embedded SQLite store for history: paired clients and peers, sessions,
+//! payment transactions, vouchers, member accounts and the audit trail.
+//! `appconfig.json` only keeps settings.
+//!
+//! Each table is reached through its repository, e.g.
+//! `database.transactions().insert(...)`, so callers never write SQL.
+
+pub mod audit;
+pub mod clients;
+pub mod legacy;
+pub mod members;
+pub mod migrations;
+pub mod peers;
+pub mod sessions;
+pub mod transactions;
+pub mod vouchers;
+
+use std::path::Path;
+use std::sync::{ Arc, Mutex, MutexGuard };
+
+use anyhow::{ Context, Result };
+use rusqlite::Connection;
+
+use audit::AuditRepository;
+use clients::ClientRepository;
+use members::MemberRepository;
+use peers::PeerRepository;
+use sessions::SessionRepository;
+use transactions::TransactionRepository;
+use vouchers::VoucherRepository;
+
+pub type DatabaseState = Arc<Database>;
+
+pub struct Database {
+    connection: Mutex<Connection>,
+}
+
+impl Database {
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path).with_context(||
+            format!("Failed to open database {:?}", path)
+        )?;
+        // WAL keeps reports from blocking the writes of a running session
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        Self::with_connection(connection)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut connection: Connection) -> Result<Self> {
+        connection.pragma_update(None, "foreign_keys", "ON")?;
+        migrations::migrate(&mut connection)?;
+        Ok(Database { connection: Mutex::new(connection) })
+    }
+
+    #[cfg(test)]
+    pub fn execute_batch(&self, sql: &str) -> Result<()> {
+        Ok(self.connection()?.execute_batch(sql)?)
+    }
+
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.connection.lock().map_err(|e| anyhow::anyhow!(e.to_string()))
+    }
+
+    pub fn clients(&self) -> ClientRepository<'_> {
+        ClientRepository { database: self }
+    }
+
+    pub fn sessions(&self) -> SessionRepository<'_> {
+        SessionRepository { database: self }
+    }
+
+    pub fn transactions(&self) -> TransactionRepository<'_> {
+        TransactionRepository { database: self }
+    }
+
+    pub fn audit(&self) -> AuditRepository<'_> {
+        AuditRepository { database: self }
+    }
+
+    pub fn vouchers(&self) -> VoucherRepository<'_> {
+        VoucherRepository { database: self }
+    }
+
+    pub fn members(&self) -> MemberRepository<'_> {
+        MemberRepository { database: self }
+    }
+
+    pub fn peers(&self) -> PeerRepository<'_> {
+        PeerRepository { database: self }
+    }
+}
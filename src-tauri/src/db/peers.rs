@@ -0,0 +1,67 @@
+use anyhow::Result;
+use chrono::{ DateTime, Utc };
+use ring::digest::{ digest, SHA256 };
+use rusqlite::{ params, OptionalExtension };
+
+use super::Database;
+
+// Stations and counters that paired with this one, keyed by hardware id.
+// Only the hash of the credential they were given is kept.
+pub struct PeerRepository<'a> {
+    pub(super) database: &'a Database,
+}
+
+impl PeerRepository<'_> {
+    // Pairing again replaces the credential given before.
+    pub fn add(
+        &self,
+        hwid: &str,
+        address: &str,
+        credential: &str,
+        paired_at: DateTime<Utc>
+    ) -> Result<()> {
+        self.database.connection()?.execute(
+            "INSERT INTO peers (hwid, address, credential_hash, paired_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (hwid) DO UPDATE
+             SET address = excluded.address, credential_hash = excluded.credential_hash,
+                 paired_at = excluded.paired_at",
+            params![hwid, address, hash(credential), paired_at]
+        )?;
+        Ok(())
+    }
+
+    // Hardware id of the peer given `credential`.
+    pub fn find(&self, credential: &str) -> Result<Option<String>> {
+        let hwid = self.database
+            .connection()?
+            .query_row(
+                "SELECT hwid FROM peers WHERE credential_hash = ?1",
+                params![hash(credential)],
+                |row| row.get(0)
+            )
+            .optional()?;
+        Ok(hwid)
+    }
+}
+
+fn hash(credential: &str) -> Vec<u8> {
+    digest(&SHA256, credential.as_bytes()).as_ref().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pairing_again_replaces_the_credential() {
+        let database = Database::open_in_memory().unwrap();
+        let peers = database.peers();
+        peers.add("counter-hwid", "192.168.1.2", "first", Utc::now()).unwrap();
+        assert_eq!(peers.find("first").unwrap(), Some("counter-hwid".to_string()));
+
+        peers.add("counter-hwid", "192.168.1.2", "second", Utc::now()).unwrap();
+        assert_eq!(peers.find("first").unwrap(), None);
+        assert_eq!(peers.find("second").unwrap(), Some("counter-hwid".to_string()));
+        assert_eq!(peers.find("").unwrap(), None);
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::Result;
+use chrono::{ DateTime, Utc };
+use rusqlite::params;
+
+use super::Database;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: i64,
+    pub station: String,
+    pub started_at: DateTime<Utc>,
+    // `None` while the session is still running.
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+pub struct SessionRepository<'a> {
+    pub(super) database: &'a Database,
+}
+
+impl SessionRepository<'_> {
+    pub fn start(&self, station: &str, started_at: DateTime<Utc>) -> Result<i64> {
+        let connection = self.database.connection()?;
+        connection.execute(
+            "INSERT INTO sessions (station, started_at) VALUES (?1, ?2)",
+            params![station, started_at]
+        )?;
+        Ok(connection.last_insert_rowid())
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        let count: i64 = self.database
+            .connection()?
+            .query_row("SELECT COUNT(*) FROM sessions", [], |row| row.get(0))?;
+        Ok(count == 0)
+    }
+
+    pub fn end(&self, id: i64, ended_at: DateTime<Utc>) -> Result<()> {
+        self.database
+            .connection()?
+            .execute(
+                "UPDATE sessions SET ended_at = ?2, remaining_seconds = 0 WHERE id = ?1",
+                params![id, ended_at]
+            )?;
+        Ok(())
+    }
+
+    pub fn checkpoint(&self, id: i64, remaining_seconds: u64) -> Result<()> {
+        self.database
+            .connection()?
+            .execute(
+                "UPDATE sessions SET remaining_seconds = ?2 WHERE id = ?1 AND ended_at IS NULL",
+                params![id, remaining_seconds]
+            )?;
+        Ok(())
+    }
+
+    // Ends the sessions of `station` that were never ended, i.e. the station
+    // stopped mid session, and returns the time they had left. Each session is
+    // only given back once.
+    pub fn end_unfinished(&self, station: &str, ended_at: DateTime<Utc>) -> Result<u64> {
+        let mut connection = self.database.connection()?;
+        let transaction = connection.transaction()?;
+        let remaining: u64 = transaction.query_row(
+            "SELECT COALESCE(SUM(remaining_seconds), 0) FROM sessions
+             WHERE station = ?1 AND ended_at IS NULL",
+            params![station],
+            |row| row.get(0)
+        )?;
+        transaction.execute(
+            "UPDATE sessions SET ended_at = ?2, remaining_seconds = 0
+             WHERE station = ?1 AND ended_at IS NULL",
+            params![station, ended_at]
+        )?;
+        transaction.commit()?;
+        Ok(remaining)
+    }
+
+    // Sessions started between `from` (inclusive) and `to` (exclusive).
+    pub fn started_between(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>
+    ) -> Result<Vec<Session>> {
+        let connection = self.database.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT id, station, started_at, ended_at FROM sessions
+             WHERE (?1 IS NULL OR started_at >= ?1) AND (?2 IS NULL OR started_at < ?2)
+             ORDER BY started_at, id"
+        )?;
+        let sessions = statement
+            .query_map(params![from, to], |row| {
+                Ok(Session {
+                    id: row.get(0)?,
+                    station: row.get(1)?,
+                    started_at: row.get(2)?,
+                    ended_at: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(sessions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_sessions_are_started_ended_and_filtered() {
+        let database = Database::open_in_memory().unwrap();
+        let now = Utc::now();
+        let old = database.sessions().start("station-01", now - Duration::days(1)).unwrap();
+        let current = database.sessions().start("station-01", now).unwrap();
+        database.sessions().end(old, now - Duration::hours(23)).unwrap();
+
+        let all = database.sessions().started_between(None, None).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].ended_at, Some(now - Duration::hours(23)));
+        assert_eq!(all[1].ended_at, None);
+
+        let recent = database
+            .sessions()
+            .started_between(Some(now - Duration::hours(1)), None)
+            .unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].id, current);
+    }
+
+    #[test]
+    fn test_unfinished_sessions_give_back_their_time_once() {
+        let database = Database::open_in_memory().unwrap();
+        let now = Utc::now();
+        let sessions = database.sessions();
+        let crashed = sessions.start("station-01", now).unwrap();
+        sessions.checkpoint(crashed, 900).unwrap();
+        let ended = sessions.start("station-01", now).unwrap();
+        sessions.checkpoint(ended, 600).unwrap();
+        sessions.end(ended, now).unwrap();
+        let other = sessions.start("station-02", now).unwrap();
+        sessions.checkpoint(other, 300).unwrap();
+
+        assert_eq!(sessions.end_unfinished("station-01", now).unwrap(), 900);
+        assert_eq!(sessions.end_unfinished("station-01", now).unwrap(), 0);
+        sessions.checkpoint(crashed, 900).unwrap();
+        assert_eq!(sessions.end_unfinished("station-01", now).unwrap(), 0);
+        assert_eq!(sessions.end_unfinished("station-02", now).unwrap(), 300);
+    }
+}
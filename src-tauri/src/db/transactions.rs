@@ -0,0 +1,172 @@
+use anyhow::Result;
+use chrono::{ DateTime, Utc };
+use rusqlite::types::{ FromSql, FromSqlError, FromSqlResult, ToSqlOutput, Type, ValueRef };
+use rusqlite::{ params, ToSql };
+
+use super::Database;
+use crate::payment::{ Payment, PaymentSource };
+use crate::promotion::AppliedPromotion;
+
+// Payment turned into time on a station.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub id: i64,
+    pub station: String,
+    pub created_at: DateTime<Utc>,
+    pub payment: Payment,
+    // Including the bonus time of `promotions`.
+    pub seconds: u64,
+    pub promotions: Vec<AppliedPromotion>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewTransaction<'a> {
+    pub station: &'a str,
+    pub created_at: DateTime<Utc>,
+    pub payment: Payment,
+    pub seconds: u64,
+    pub promotions: &'a [AppliedPromotion],
+}
+
+pub struct TransactionRepository<'a> {
+    pub(super) database: &'a Database,
+}
+
+impl TransactionRepository<'_> {
+    pub fn insert(&self, transaction: &NewTransaction) -> Result<i64> {
+        let promotions = serde_json::to_string(transaction.promotions)?;
+        let connection = self.database.connection()?;
+        connection.execute(
+            "INSERT INTO transactions
+             (station, created_at, amount_centavos, denomination_centavos, source, seconds,
+              promotions)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                transaction.station,
+                transaction.created_at,
+                transaction.payment.amount_centavos,
+                transaction.payment.denomination_centavos,
+                transaction.payment.source,
+                transaction.seconds,
+                promotions
+            ]
+        )?;
+        Ok(connection.last_insert_rowid())
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        let count: i64 = self.database
+            .connection()?
+            .query_row("SELECT COUNT(*) FROM transactions", [], |row| row.get(0))?;
+        Ok(count == 0)
+    }
+
+    pub fn last_created_at(&self, station: &str) -> Result<Option<DateTime<Utc>>> {
+        let last = self.database
+            .connection()?
+            .query_row(
+                "SELECT MAX(created_at) FROM transactions WHERE station = ?1",
+                params![station],
+                |row| row.get(0)
+            )?;
+        Ok(last)
+    }
+
+    // Transactions made between `from` (inclusive) and `to` (exclusive).
+    pub fn created_between(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>
+    ) -> Result<Vec<Transaction>> {
+        let connection = self.database.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT id, station, created_at, amount_centavos, denomination_centavos, source,
+                    seconds, promotions
+             FROM transactions
+             WHERE (?1 IS NULL OR created_at >= ?1) AND (?2 IS NULL OR created_at < ?2)
+             ORDER BY created_at, id"
+        )?;
+        let transactions = statement
+            .query_map(params![from, to], |row| {
+                Ok(Transaction {
+                    id: row.get(0)?,
+                    station: row.get(1)?,
+                    created_at: row.get(2)?,
+                    payment: Payment {
+                        amount_centavos: row.get(3)?,
+                        denomination_centavos: row.get(4)?,
+                        source: row.get(5)?,
+                    },
+                    seconds: row.get(6)?,
+                    promotions: serde_json
+                        ::from_str(&row.get::<_, String>(7)?)
+                        .map_err(|e| {
+                            rusqlite::Error::FromSqlConversionFailure(7, Type::Text, e.into())
+                        })?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(transactions)
+    }
+}
+
+impl ToSql for PaymentSource {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for PaymentSource {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let source = value.as_str()?;
+        PaymentSource::parse(source).ok_or_else(|| {
+            FromSqlError::Other(format!("Unknown payment source {}", source).into())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_transactions_are_filtered_by_time() {
+        let database = Database::open_in_memory().unwrap();
+        let now = Utc::now();
+        let cashier = Payment {
+            amount_centavos: 3750,
+            denomination_centavos: None,
+            source: PaymentSource::Cashier,
+        };
+        let promotions = [AppliedPromotion { name: "Early bird".to_string(), bonus_seconds: 60 }];
+        for (created_at, payment, promotions) in [
+            (now - Duration::days(1), Payment::cash(PaymentSource::Bill, 5000), &promotions[..]),
+            (now, cashier, &[]),
+        ] {
+            database
+                .transactions()
+                .insert(&NewTransaction {
+                    station: "station-01",
+                    created_at,
+                    payment,
+                    seconds: 5,
+                    promotions,
+                })
+                .unwrap();
+        }
+
+        let all = database.transactions().created_between(None, None).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].payment, Payment::cash(PaymentSource::Bill, 5000));
+        assert_eq!(all[0].promotions, promotions);
+        assert_eq!(all[1].payment, cashier);
+
+        let yesterday = database
+            .transactions()
+            .created_between(None, Some(now - Duration::hours(1)))
+            .unwrap();
+        assert_eq!(yesterday.len(), 1);
+        assert_eq!(yesterday[0].created_at, now - Duration::days(1));
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::Result;
+use chrono::{ DateTime, Utc };
+use rusqlite::{ params, OptionalExtension };
+use serde::Serialize;
+
+use super::Database;
+use crate::voucher::VoucherError;
+
+// Codes generated together, sharing their time value and expiry.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct VoucherBatch {
+    pub id: i64,
+    pub label: String,
+    pub created_at: DateTime<Utc>,
+    pub seconds: u64,
+    pub expires_at: DateTime<Utc>,
+    pub codes: u64,
+    pub redeemed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewVoucherBatch<'a> {
+    pub label: &'a str,
+    pub created_at: DateTime<Utc>,
+    pub seconds: u64,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Voucher {
+    pub code: String,
+    pub redeemed_at: Option<DateTime<Utc>>,
+}
+
+pub struct VoucherRepository<'a> {
+    pub(super) database: &'a Database,
+}
+
+impl VoucherRepository<'_> {
+    // Stores the batch and its codes in one go, returns the batch id.
+    pub fn create_batch(&self, batch: &NewVoucherBatch, codes: &[String]) -> Result<i64> {
+        let mut connection = self.database.connection()?;
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO voucher_batches (label, created_at, seconds, expires_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![batch.label, batch.created_at, batch.seconds, batch.expires_at]
+        )?;
+        let batch_id = transaction.last_insert_rowid();
+        {
+            let mut statement = transaction.prepare(
+                "INSERT INTO vouchers (code, batch_id) VALUES (?1, ?2)"
+            )?;
+            for code in codes {
+                statement.execute(params![code, batch_id])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(batch_id)
+    }
+
+    pub fn batches(&self) -> Result<Vec<VoucherBatch>> {
+        let connection = self.database.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT b.id, b.label, b.created_at, b.seconds, b.expires_at,
+                    COUNT(v.code), COUNT(v.redeemed_at)
+             FROM voucher_batches b LEFT JOIN vouchers v ON v.batch_id = b.id
+             GROUP BY b.id ORDER BY b.id DESC"
+        )?;
+        let batches = statement
+            .query_map([], |row| {
+                Ok(VoucherBatch {
+                    id: row.get(0)?,
+                    label: row.get(1)?,
+                    created_at: row.get(2)?,
+                    seconds: row.get(3)?,
+                    expires_at: row.get(4)?,
+                    codes: row.get(5)?,
+                    redeemed: row.get(6)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(batches)
+    }
+
+    pub fn codes(&self, batch_id: i64) -> Result<Vec<Voucher>> {
+        let connection = self.database.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT code, redeemed_at FROM vouchers WHERE batch_id = ?1 ORDER BY rowid"
+        )?;
+        let vouchers = statement
+            .query_map(params![batch_id], |row| {
+                Ok(Voucher { code: row.get(0)?, redeemed_at: row.get(1)? })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(vouchers)
+    }
+
+    // Marks the voucher as used by `station` and returns its time value. The
+    // update only matches an unused, unexpired code, so two stations racing
+    // for the same code cannot both get the time.
+    pub fn redeem(
+        &self,
+        code: &str,
+        station: &str,
+        now: DateTime<Utc>
+    ) -> Result<Result<u64, VoucherError>> {
+        let connection = self.database.connection()?;
+        let redeemed = connection.execute(
+            "UPDATE vouchers SET redeemed_at = ?2, redeemed_by = ?3
+             WHERE code = ?1 AND redeemed_at IS NULL
+             AND batch_id IN (SELECT id FROM voucher_batches WHERE expires_at > ?2)",
+            params![code, now, station]
+        )?;
+
+        let voucher: Option<(u64, DateTime<Utc>, Option<DateTime<Utc>>)> = connection
+            .query_row(
+                "SELECT b.seconds, b.expires_at, v.redeemed_at
+                 FROM vouchers v JOIN voucher_batches b ON b.id = v.batch_id
+                 WHERE v.code = ?1",
+                params![code],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            )
+            .optional()?;
+
+        Ok(match voucher {
+            Some((seconds, _, _)) if redeemed == 1 => Ok(seconds),
+            Some((_, _, Some(at))) => Err(VoucherError::AlreadyRedeemed { at }),
+            Some((_, expires_at, None)) => Err(VoucherError::Expired { at: expires_at }),
+            None => Err(VoucherError::NotFound),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_voucher_is_redeemed_once_before_expiry() {
+        let database = Database::open_in_memory().unwrap();
+        let now = Utc::now();
+        let batch = NewVoucherBatch {
+            label: "3 hours",
+            created_at: now,
+            seconds: 3 * 3600,
+            expires_at: now + Duration::days(30),
+        };
+        let codes = ["AAAA2222BBBB".to_string(), "CCCC3333DDDD".to_string()];
+        let batch_id = database.vouchers().create_batch(&batch, &codes).unwrap();
+
+        let vouchers = database.vouchers();
+        assert_eq!(vouchers.redeem("AAAA2222BBBB", "station-01", now).unwrap(), Ok(3 * 3600));
+        assert_eq!(
+            vouchers.redeem("AAAA2222BBBB", "station-02", now).unwrap(),
+            Err(VoucherError::AlreadyRedeemed { at: now })
+        );
+        assert_eq!(
+            vouchers.redeem("CCCC3333DDDD", "station-02", now + Duration::days(31)).unwrap(),
+            Err(VoucherError::Expired { at: now + Duration::days(30) })
+        );
+        assert_eq!(
+            vouchers.redeem("EEEE4444FFFF", "station-02", now).unwrap(),
+            Err(VoucherError::NotFound)
+        );
+
+        let batches = vouchers.batches().unwrap();
+        assert_eq!((batches[0].id, batches[0].codes, batches[0].redeemed), (batch_id, 2, 1));
+        let codes = vouchers.codes(batch_id).unwrap();
+        assert_eq!(codes[0].redeemed_at, Some(now));
+        assert_eq!(codes[1].redeemed_at, None);
+    }
+}
@@ -0,0 +1,418 @@
+use std::time::Duration;
+
+use anyhow::{ Context, Result };
+use reqwest::StatusCode;
+use tracing::warn;
+
+use super::peer::authorize;
+use super::tls::{ pinned_http_client, PinnedCertVerifier };
+use super::v1::models::{ RegisterRequest, SalesReportQuery };
+use super::v2::{
+    self,
+    models::{
+        AdjustTimeRequest,
+        AdjustTimeResponse,
+        EndSessionRequest,
+        EndSessionResponse,
+        MemberSignInRequest,
+        MemberSignInResponse,
+        MemberSignOutRequest,
+        MemberSignOutResponse,
+        RegisterResponse,
+        RedeemVoucherRequest,
+        RedeemVoucherResponse,
+        StationMessageRequest,
+        StationMessageResponse,
+    },
+};
+use crate::db::Database;
+use crate::member::{ MemberError, MemberSession };
+use crate::message::StationMessage;
+use crate::reports::summary::ReportRow;
+use crate::settings::appconfigmodels::Client;
+use crate::station::StationStatus;
+use crate::voucher::VoucherError;
+
+// A station that is switched off should not keep the counter waiting.
+const STATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Pairs with a station at `base_url` (e.g. `https://192.168.1.10:3000`).
+// Over TLS the station certificate is trusted on first use and its
+// fingerprint is returned in the `Client` record so later connections can be
+// pinned to it, along with the credential the station handed out.
+pub async fn pair_with_station(base_url: &str, request: &RegisterRequest) -> Result<Client> {
+    let base_url = base_url.trim_end_matches('/');
+    let url = format!("{}{}/register", base_url, v2::PREFIX);
+
+    let (http_client, verifier) = pinned_http_client(None)?;
+    let response = http_client
+        .post(url)
+        .json(request)
+        .timeout(STATION_TIMEOUT)
+        .send().await
+        .with_context(|| "Failed to reach the station")?;
+    let response: RegisterResponse = check_status(response)?
+        .json().await
+        .with_context(|| "Failed to parse the station response")?;
+
+    paired_client(base_url, response, verifier.seen_fingerprint().unwrap_or_default())
+}
+
+// Record of the station at `base_url` that answered the pairing with
+// `response` over a connection showing `cert_fingerprint`.
+fn paired_client(
+    base_url: &str,
+    response: RegisterResponse,
+    cert_fingerprint: String
+) -> Result<Client> {
+    if !response.status {
+        return Err(anyhow::anyhow!("Pairing rejected: {}", response.text));
+    }
+    // Paired stations are told apart by their hardware id
+    if response.server_hwid.is_empty() {
+        return Err(anyhow::anyhow!("Station did not say who it is"));
+    }
+    if !response.server_cert_fingerprint.is_empty() &&
+       response.server_cert_fingerprint != cert_fingerprint {
+        return Err(anyhow::anyhow!("Station certificate does not match the one it reported"));
+    }
+
+    Ok(Client {
+        address: base_url.to_string(),
+        hwid: response.server_hwid,
+        cert_fingerprint,
+        credential: response.credential,
+    })
+}
+
+// Asks a paired station to redeem a voucher it generated, for `redeemer`.
+// With `pass_on` it also asks the stations paired with it.
+pub async fn redeem_voucher(
+    database: &Database,
+    station: &Client,
+    code: &str,
+    redeemer: &str,
+    pass_on: bool
+) -> Result<u64, VoucherError> {
+    let url = format!("{}{}/vouchers/redeem", station.address, v2::PREFIX);
+    let request = RedeemVoucherRequest {
+        code: code.to_string(),
+        station: redeemer.to_string(),
+        pass_on,
+    };
+    let response: RedeemVoucherResponse = send(database, station, |http_client| {
+        http_client.post(url).json(&request)
+    }).await
+        .and_then(check_status)
+        .map_err(|e| VoucherError::unavailable(format!("{:#}", e)))?
+        .json().await
+        .map_err(VoucherError::unavailable)?;
+
+    match response.error {
+        Some(error) => Err(error),
+        None => Ok(response.seconds),
+    }
+}
+
+// Asks a paired station to sign in a member it keeps the account of, for
+// `signer`. Returns the session token and the balance.
+pub async fn sign_in_member(
+    database: &Database,
+    station: &Client,
+    username: &str,
+    pin: &str,
+    signer: &str
+) -> Result<(String, u64), MemberError> {
+    let request = MemberSignInRequest {
+        username: username.to_string(),
+        pin: pin.to_string(),
+        station: signer.to_string(),
+    };
+    let response: MemberSignInResponse = post_to_member_station(
+        database,
+        station,
+        "/members/signin",
+        &request
+    ).await?;
+
+    match response.error {
+        Some(error) => Err(error),
+        None => Ok((response.token, response.seconds)),
+    }
+}
+
+// Gives the time left back to an account kept on a paired station. Returns
+// the new balance.
+pub async fn sign_out_member(
+    database: &Database,
+    station: &Client,
+    session: &MemberSession,
+    seconds: u64,
+    signer: &str
+) -> Result<u64, MemberError> {
+    let request = MemberSignOutRequest {
+        username: session.username.clone(),
+        token: session.token.clone(),
+        seconds,
+        station: signer.to_string(),
+    };
+    let response: MemberSignOutResponse = post_to_member_station(
+        database,
+        station,
+        "/members/signout",
+        &request
+    ).await?;
+
+    match response.error {
+        Some(error) => Err(error),
+        None => Ok(response.balance_seconds),
+    }
+}
+
+async fn post_to_member_station<T: serde::de::DeserializeOwned>(
+    database: &Database,
+    station: &Client,
+    path: &str,
+    request: &impl serde::Serialize
+) -> Result<T, MemberError> {
+    let url = format!("{}{}{}", station.address, v2::PREFIX, path);
+    send(database, station, |http_client| http_client.post(url).json(request)).await
+        .and_then(check_status)
+        .map_err(|e| MemberError::unavailable(format!("{:#}", e)))?
+        .json().await
+        .map_err(MemberError::unavailable)
+}
+
+// Asks a paired station what it is doing, for the counter.
+pub async fn station_status(database: &Database, station: &Client) -> Result<StationStatus> {
+    let url = format!("{}{}/station", station.address, v2::PREFIX);
+    send(database, station, |http_client| http_client.get(url)).await?
+        .error_for_status()?
+        .json().await
+        .with_context(|| "Failed to parse the station response")
+}
+
+// Adds (or with a negative value removes) time on a paired station. Returns
+// the time left on it.
+pub async fn adjust_station_time(
+    database: &Database,
+    station: &Client,
+    seconds: i64,
+    reason: &str
+) -> Result<u64> {
+    let request = AdjustTimeRequest { seconds, reason: reason.to_string() };
+    let response: AdjustTimeResponse = post_to_station(
+        database,
+        station,
+        "/station/adjust",
+        &request
+    ).await?;
+    if !response.status {
+        return Err(anyhow::anyhow!("Station refused: {}", response.text));
+    }
+    Ok(response.remaining_seconds)
+}
+
+// Ends the session on a paired station. Returns the time taken off.
+pub async fn end_station_session(
+    database: &Database,
+    station: &Client,
+    reason: &str
+) -> Result<u64> {
+    let request = EndSessionRequest { reason: reason.to_string() };
+    let response: EndSessionResponse = post_to_station(
+        database,
+        station,
+        "/station/end",
+        &request
+    ).await?;
+    if !response.status {
+        return Err(anyhow::anyhow!("Station refused: {}", response.text));
+    }
+    Ok(response.seconds)
+}
+
+// Shows `message` on a paired station until the customer acknowledges it.
+pub async fn send_station_message(
+    database: &Database,
+    station: &Client,
+    message: &StationMessage
+) -> Result<()> {
+    let request = StationMessageRequest {
+        id: message.id.clone(),
+        text: message.text.clone(),
+        bring_to_front: message.bring_to_front,
+    };
+    let response: StationMessageResponse = post_to_station(
+        database,
+        station,
+        "/station/message",
+        &request
+    ).await?;
+    if !response.status {
+        return Err(anyhow::anyhow!("Station refused: {}", response.text));
+    }
+    Ok(())
+}
+
+pub async fn station_sales(
+    database: &Database,
+    station: &Client,
+    query: &SalesReportQuery
+) -> Result<Vec<ReportRow>> {
+    let url = format!("{}{}/reports/sales", station.address, v2::PREFIX);
+    send(database, station, |http_client| http_client.get(url).query(query)).await?
+        .error_for_status()?
+        .json().await
+        .with_context(|| "Failed to parse the station response")
+}
+
+async fn post_to_station<T: serde::de::DeserializeOwned>(
+    database: &Database,
+    station: &Client,
+    path: &str,
+    request: &impl serde::Serialize
+) -> Result<T> {
+    let url = format!("{}{}{}", station.address, v2::PREFIX, path);
+    let response = send(database, station, |http_client| {
+        http_client.post(url).json(request)
+    }).await?;
+    check_status(response)?
+        .json().await
+        .with_context(|| "Failed to parse the station response")
+}
+
+// The handlers answer in JSON even when they refuse. Anything else was turned
+// away before reaching them, e.g. by the credential check or the rate limit.
+fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    let json = response.headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if status.is_success() || json {
+        return Ok(response);
+    }
+    Err(match status {
+        StatusCode::UNAUTHORIZED => anyhow::anyhow!("Not paired with the station, pair it again"),
+        StatusCode::FORBIDDEN => {
+            anyhow::anyhow!("The station refuses this PC for a while after repeated failures")
+        }
+        StatusCode::TOO_MANY_REQUESTS => anyhow::anyhow!("The station is busy, try again"),
+        status => anyhow::anyhow!("The station answered {}", status),
+    })
+}
+
+// Sends the request `build` makes for a paired station, with its credential.
+async fn send(
+    database: &Database,
+    station: &Client,
+    build: impl FnOnce(reqwest::Client) -> reqwest::RequestBuilder
+) -> Result<reqwest::Response> {
+    let pinned = Some(station.cert_fingerprint.as_str()).filter(|pinned| !pinned.is_empty());
+    let (http_client, verifier) = pinned_http_client(pinned)?;
+    let response = authorize(build(http_client), &station.credential)
+        .timeout(STATION_TIMEOUT)
+        .send().await
+        .with_context(|| "Failed to reach the station")?;
+    remember_certificate(database, station, &verifier);
+    Ok(response)
+}
+
+// A station paired while it served plain HTTP is pinned to the certificate
+// it shows on its first TLS connection, as it would have been when pairing.
+fn remember_certificate(database: &Database, station: &Client, verifier: &PinnedCertVerifier) {
+    if !station.cert_fingerprint.is_empty() {
+        return;
+    }
+    let Some(cert_fingerprint) = verifier.seen_fingerprint() else {
+        return;
+    };
+    let pinned = Client { cert_fingerprint, ..station.clone() };
+    if let Err(e) = database.clients().upsert(&pinned) {
+        warn!(station = station.address, "Failed to pin the station certificate: {:#}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accepted(hwid: &str, address: &str) -> RegisterResponse {
+        RegisterResponse {
+            status: true,
+            server_hwid: hwid.to_string(),
+            server_address: address.to_string(),
+            server_cert_fingerprint: String::new(),
+            credential: format!("{}-credential", hwid),
+            text: "Registration successful".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_every_paired_station_is_listed() {
+        let database = Database::open_in_memory().unwrap();
+        let stations = [
+            ("http://192.168.1.11:3000", accepted("station-a", "192.168.1.11:3000")),
+            ("http://192.168.1.12:3000", accepted("station-b", "192.168.1.12:3000")),
+        ];
+        for (base_url, response) in stations {
+            let client = paired_client(base_url, response, String::new()).unwrap();
+            database.clients().upsert(&client).unwrap();
+        }
+
+        let clients = database.clients().all().unwrap();
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients["station-a"].address, "http://192.168.1.11:3000");
+        assert_eq!(clients["station-b"].credential, "station-b-credential");
+
+        let anonymous = accepted("", "192.168.1.13:3000");
+        assert!(paired_client("http://192.168.1.13:3000", anonymous, String::new()).is_err());
+    }
+
+    #[test]
+    fn test_refusals_before_the_handler_say_why() {
+        let answer = |status: u16, content_type: &str| {
+            let response = axum::http::Response
+                ::builder()
+                .status(status)
+                .header("content-type", content_type)
+                .body("Not paired with this station")
+                .unwrap();
+            check_status(reqwest::Response::from(response))
+        };
+        let not_paired = answer(401, "text/plain; charset=utf-8").unwrap_err();
+        assert!(not_paired.to_string().contains("pair it again"));
+        assert!(answer(403, "text/plain; charset=utf-8").is_err());
+        assert!(answer(422, "application/json").is_ok());
+        assert!(answer(200, "application/json").is_ok());
+    }
+
+    #[test]
+    fn test_station_paired_over_http_is_pinned_on_first_tls_connection() {
+        use rustls::client::danger::ServerCertVerifier;
+        use rustls::pki_types::{ ServerName, UnixTime };
+
+        let database = Database::open_in_memory().unwrap();
+        let response = accepted("station-a", "192.168.1.11:3000");
+        let station = paired_client("https://192.168.1.11:3000", response, String::new()).unwrap();
+        database.clients().upsert(&station).unwrap();
+
+        // Plain HTTP shows no certificate
+        let verifier = PinnedCertVerifier::new(None);
+        remember_certificate(&database, &station, &verifier);
+        assert!(database.clients().all().unwrap()["station-a"].cert_fingerprint.is_empty());
+
+        let dir = std::env::temp_dir().join("cps_client_pin");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let identity = crate::http_server::tls::load_or_generate(&dir, "127.0.0.1").unwrap();
+        let name = ServerName::try_from("127.0.0.1").unwrap();
+        verifier.verify_server_cert(&identity.cert, &[], &name, &[], UnixTime::now()).unwrap();
+        remember_certificate(&database, &station, &verifier);
+
+        let pinned = database.clients().all().unwrap().remove("station-a").unwrap();
+        assert_eq!(pinned.cert_fingerprint, identity.fingerprint);
+        assert_eq!(pinned.credential, "station-a-credential");
+    }
+}
@@ -1,88 +1,328 @@
-use axum::{ Json, Router };
-use tauri::{ AppHandle, Emitter };
-use tokio::sync::mpsc;
+use std::net::{ IpAddr, SocketAddr, TcpListener };
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{ DefaultBodyLimit, FromRef };
+use axum::middleware;
+use axum::routing::get;
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use serde::Serialize;
+use tauri::{ AppHandle, Emitter, Manager };
+use tauri::async_runtime::JoinHandle;
+use tokio::sync::{ mpsc, oneshot, Mutex };
 use anyhow::{ Context, Result };
+use tracing::{ error, info };
 
-use super::models::{ RegisterRequest, RegisterResponse, AddTimeRequest, AddTimeResponse };
+use super::metrics::{ self, Metrics };
+use super::ratelimit::{ self, RateLimiter };
+use super::{ probes, tls, v1, v2 };
+use crate::constants;
+use crate::audit::event::AuditEvent;
+use crate::audit::store::AuditLog;
+use crate::payment::{ self, Payment, PaymentError };
+use crate::promotion::Credit;
+use crate::settings::appconfig;
+use crate::settings::appconfigmodels::{ Pricing, RateLimit };
+use crate::settings::uuidmodel::UniqueId;
 
-pub async fn start_server(
-    app_handle: AppHandle,
-    tx: mpsc::Sender<(u64, AppHandle)>,
-    ip_address: String,
-    port: u16
-) -> Result<()> {
-    let app_handle_register = app_handle.clone();
-    let app_handle_add_time = app_handle.clone();
-
-    let tx_add_time = tx.clone();
-
-    let app = Router::new()
-        .route(
-            "/api/v1/register",
-            axum::routing::post(move |payload| { register_handler(payload, app_handle_register) })
-        )
-        .route(
-            "/api/v1/addtime",
-            axum::routing::post(move |payload| {
-                add_time_handler(payload, app_handle_add_time, tx_add_time)
-            })
-        );
-
-    let address = format!("{}:{}", ip_address, port);
-    let listener = tokio::net::TcpListener
-        ::bind(address).await
-        .with_context(|| "Failed to bind to address and port")?;
-
-    axum::serve(listener, app).await.with_context(|| "Failed to start server")?;
-
-    Ok(())
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+pub type CreditSender = mpsc::Sender<(Payment, AppHandle, Option<CreditReply>)>;
+pub type CreditReceiver = mpsc::Receiver<(Payment, AppHandle, Option<CreditReply>)>;
+// Answered with the time the payment was turned into.
+pub type CreditReply = oneshot::Sender<Credit>;
+
+#[derive(Clone)]
+pub struct ServerState {
+    pub app_handle: AppHandle,
+    pub tx: CreditSender,
+    pub cert_fingerprint: Option<String>,
+    // Hardware id and bound address of this station, told to clients that
+    // pair with it.
+    pub hwid: String,
+    pub address: String,
+    pub metrics: Arc<Metrics>,
+    pub limiter: Arc<RateLimiter>,
 }
 
-async fn register_handler(
-    Json(payload): Json<RegisterRequest>,
-    app_handle: AppHandle
-) -> Json<RegisterResponse> {
-    let pair_id = payload.pair_id;
-    let _address = payload.address;
-    let _hwid = payload.hwid;
-
-    if pair_id != "pair-id-123" {
-        return Json(RegisterResponse {
-            status: false,
-            server_hwid: "".to_string(),
-            server_address: "".to_string(),
-            text: "Invalid pair_id".to_string(),
-        });
+impl FromRef<ServerState> for Arc<Metrics> {
+    fn from_ref(state: &ServerState) -> Self {
+        state.metrics.clone()
     }
+}
 
-    let status = true;
-    let server_hwid = "server-hwid-123".to_string();
-    let server_address = "127.0.0.1:3000".to_string();
-    let text = "Registration successful".to_string();
+// Payment sent by a paired client to `/addtime`, checked against the accepted
+// denominations before it turns into time. Shared by every API version.
+pub async fn add_payment(
+    state: &ServerState,
+    payment: Payment,
+    address: IpAddr
+) -> Result<Credit, PaymentError> {
+    payment::validate_remote(&state.app_handle.state::<Pricing>(), &payment)?;
 
-    let register_response = RegisterResponse {
-        status,
-        server_hwid,
-        server_address,
-        text,
-    };
+    state.app_handle.state::<AuditLog>().record(AuditEvent::RemotePayment {
+        amount_centavos: payment.amount_centavos,
+        source: payment.source,
+        address: address.to_string(),
+    });
+    let (reply, credited) = oneshot::channel();
+    state.tx
+        .send((payment, state.app_handle.clone(), Some(reply))).await
+        .map_err(|_| PaymentError::NotCredited)?;
+    credited.await.map_err(|_| PaymentError::NotCredited)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerOptions {
+    pub ip_address: String,
+    pub port: u16,
+    pub use_tls: bool,
+    pub rate_limit: RateLimit,
+    // Whether the license tier allows serving `/metrics`.
+    pub metrics: bool,
+}
 
-    let _ = app_handle.emit("register_request", register_response.clone());
+impl ServerOptions {
+    fn address(&self) -> String {
+        format!("{}:{}", self.ip_address, self.port)
+    }
+}
 
-    Json(register_response)
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct ServerStatus {
+    pub running: bool,
+    pub address: String,
+    pub tls: bool,
+    pub error: Option<String>,
 }
 
-async fn add_time_handler(
-    Json(payload): Json<AddTimeRequest>,
+struct RunningServer {
+    options: ServerOptions,
+    handle: Handle,
+    task: JoinHandle<()>,
+}
+
+// Owns the embedded HTTP server so it can be stopped and bound again when the
+// network settings change. Every state change is reported to the UI through
+// `HANDLER_SERVER_STATUS`.
+pub struct ServerController {
     app_handle: AppHandle,
-    tx: mpsc::Sender<(u64, AppHandle)>
-) -> Json<AddTimeResponse> {
-    let response = AddTimeResponse {
-        status: true,
-        text: "Time added successfully".to_string(),
-    };
+    tx: CreditSender,
+    metrics: Arc<Metrics>,
+    limiter: Arc<RateLimiter>,
+    running: Mutex<Option<RunningServer>>,
+    status: Arc<std::sync::Mutex<ServerStatus>>,
+}
+
+impl ServerController {
+    pub fn new(app_handle: AppHandle, tx: CreditSender, metrics: Arc<Metrics>) -> Self {
+        ServerController {
+            app_handle,
+            tx,
+            metrics,
+            limiter: Arc::new(RateLimiter::new(RateLimit::default())),
+            running: Mutex::new(None),
+            status: Arc::new(std::sync::Mutex::new(ServerStatus::default())),
+        }
+    }
+
+    pub fn status(&self) -> ServerStatus {
+        match self.status.lock() {
+            Ok(status) => status.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    pub async fn start(&self, options: ServerOptions) -> Result<()> {
+        let mut running = self.running.lock().await;
+        if running.is_some() {
+            return Err(anyhow::anyhow!("Server is already running"));
+        }
+
+        match self.spawn(&options) {
+            Ok(server) => {
+                *running = Some(server);
+                self.report(ServerStatus {
+                    running: true,
+                    address: options.address(),
+                    tls: options.use_tls,
+                    error: None,
+                });
+                Ok(())
+            }
+            Err(e) => {
+                self.report(ServerStatus {
+                    running: false,
+                    address: options.address(),
+                    tls: options.use_tls,
+                    error: Some(format!("{:#}", e)),
+                });
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn stop(&self) {
+        let server = self.running.lock().await.take();
+        if let Some(server) = server {
+            server.handle.graceful_shutdown(Some(SHUTDOWN_GRACE_PERIOD));
+            let _ = server.task.await;
+            self.report(ServerStatus {
+                running: false,
+                address: server.options.address(),
+                tls: server.options.use_tls,
+                error: None,
+            });
+        }
+    }
 
-    let _ = tx.send((payload.credits.clone() as u64, app_handle)).await;
+    // Rebinds with `options`. When the new address cannot be bound the
+    // previous one is restored so the station keeps accepting coins.
+    pub async fn restart(&self, options: ServerOptions) -> Result<()> {
+        let previous = self.running
+            .lock().await
+            .as_ref()
+            .map(|server| server.options.clone());
+        if previous.as_ref() == Some(&options) {
+            return Ok(());
+        }
+
+        self.stop().await;
+        if let Err(e) = self.start(options).await {
+            if let Some(previous) = previous {
+                let _ = self.start(previous).await;
+            }
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    fn spawn(&self, options: &ServerOptions) -> Result<RunningServer> {
+        let identity = if options.use_tls {
+            Some(tls::load_or_generate(&appconfig::config_dir(), &options.ip_address)?)
+        } else {
+            None
+        };
+
+        self.limiter.configure(options.rate_limit.clone());
+
+        let state = ServerState {
+            app_handle: self.app_handle.clone(),
+            tx: self.tx.clone(),
+            cert_fingerprint: identity.as_ref().map(|identity| identity.fingerprint.clone()),
+            hwid: UniqueId::default()?.id,
+            address: options.address(),
+            metrics: self.metrics.clone(),
+            limiter: self.limiter.clone(),
+        };
+        let app = router(state, options.metrics);
+
+        // Bind synchronously so a port conflict is reported right away instead
+        // of failing silently inside the spawned task.
+        let listener = TcpListener::bind(options.address()).with_context(||
+            format!("Failed to bind to {}", options.address())
+        )?;
+        listener.set_nonblocking(true).with_context(|| "Failed to configure listener")?;
+
+        let handle = Handle::new();
+        let server_handle = handle.clone();
+        let app_handle = self.app_handle.clone();
+        let status_cell = self.status.clone();
+        let status = ServerStatus {
+            running: false,
+            address: options.address(),
+            tls: options.use_tls,
+            error: None,
+        };
+
+        let task = match identity {
+            Some(identity) => {
+                let config = RustlsConfig::from_config(Arc::new(identity.server_config()?));
+                tauri::async_runtime::spawn(async move {
+                    let result = axum_server
+                        ::from_tcp_rustls(listener, config)
+                        .handle(server_handle)
+                        .serve(app.into_make_service_with_connect_info::<SocketAddr>()).await;
+                    report_exit(&app_handle, &status_cell, status, result);
+                })
+            }
+            None => {
+                tauri::async_runtime::spawn(async move {
+                    let result = axum_server
+                        ::from_tcp(listener)
+                        .handle(server_handle)
+                        .serve(app.into_make_service_with_connect_info::<SocketAddr>()).await;
+                    report_exit(&app_handle, &status_cell, status, result);
+                })
+            }
+        };
+
+        Ok(RunningServer {
+            options: options.clone(),
+            handle,
+            task,
+        })
+    }
+
+    fn report(&self, status: ServerStatus) {
+        publish_status(&self.app_handle, &self.status, status);
+    }
+}
+
+fn publish_status(
+    app_handle: &AppHandle,
+    status_cell: &std::sync::Mutex<ServerStatus>,
+    status: ServerStatus
+) {
+    info!(
+        running = status.running,
+        address = %status.address,
+        tls = status.tls,
+        error = ?status.error,
+        "Server status changed"
+    );
+    if let Ok(mut current) = status_cell.lock() {
+        *current = status.clone();
+    }
+    let _ = app_handle.emit(constants::HANDLER_SERVER_STATUS, status);
+}
+
+fn report_exit(
+    app_handle: &AppHandle,
+    status_cell: &std::sync::Mutex<ServerStatus>,
+    status: ServerStatus,
+    result: std::io::Result<()>
+) {
+    if let Err(e) = result {
+        error!(error = %e, "Server stopped with error");
+        publish_status(app_handle, status_cell, ServerStatus {
+            error: Some(e.to_string()),
+            ..status
+        });
+    }
+}
+
+fn router(state: ServerState, serve_metrics: bool) -> Router {
+    // Every API version is nested under its own prefix so a new version
+    // can be added without touching the routes of the older ones. Probes and
+    // metrics are operational endpoints and stay unversioned. Rate limiting
+    // runs inside the metrics layer so rejected requests are still counted.
+    let max_body_bytes = state.limiter.max_body_bytes();
+    let mut router = Router::new()
+        .nest(v1::PREFIX, v1::router())
+        .nest(v2::PREFIX, v2::router())
+        .route("/healthz", get(probes::health_handler))
+        .route("/readyz", get(probes::ready_handler));
+    if serve_metrics {
+        router = router.route("/metrics", get(metrics::metrics_handler));
+    }
 
-    Json(response)
+    router
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .layer(middleware::from_fn_with_state(state.clone(), ratelimit::limit_requests))
+        .layer(middleware::from_fn_with_state(state.clone(), metrics::track_requests))
+        .with_state(state)
 }
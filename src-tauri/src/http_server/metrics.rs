@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::sync::{ Arc, Mutex };
+use std::time::Instant;
+
+use axum::extract::{ MatchedPath, Request, State };
+use axum::http::header;
+use axum::middleware::Next;
+use axum::response::{ IntoResponse, Response };
+
+const LATENCY_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+#[derive(Default)]
+struct RouteStats {
+    latency: Histogram,
+    responses: BTreeMap<u16, u64>,
+    errors: u64,
+}
+
+// Counters exposed on `/metrics` in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    sessions: AtomicU64,
+    session_active: AtomicU64,
+    centavos_received: AtomicU64,
+    seconds_sold: AtomicU64,
+    routes: Mutex<BTreeMap<String, RouteStats>>,
+}
+
+impl Metrics {
+    pub fn record_payment(&self, amount_centavos: u64, seconds: u64) {
+        self.centavos_received.fetch_add(amount_centavos, Ordering::Relaxed);
+        self.seconds_sold.fetch_add(seconds, Ordering::Relaxed);
+    }
+
+    pub fn session_started(&self) {
+        self.sessions.fetch_add(1, Ordering::Relaxed);
+        self.session_active.store(1, Ordering::Relaxed);
+    }
+
+    pub fn session_ended(&self) {
+        self.session_active.store(0, Ordering::Relaxed);
+    }
+
+    pub fn record_request(&self, route: &str, status: u16, seconds: f64) {
+        let mut routes = match self.routes.lock() {
+            Ok(routes) => routes,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let stats = routes.entry(route.to_string()).or_default();
+
+        for (bucket, upper_bound) in stats.latency.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= upper_bound {
+                *bucket += 1;
+            }
+        }
+        stats.latency.count += 1;
+        stats.latency.sum += seconds;
+
+        *stats.responses.entry(status).or_default() += 1;
+        if status >= 400 {
+            stats.errors += 1;
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let totals = [
+            ("pisonet_sessions_total", "counter", "Sessions started.", &self.sessions),
+            (
+                "pisonet_session_active",
+                "gauge",
+                "1 while a customer session is running.",
+                &self.session_active,
+            ),
+            (
+                "pisonet_centavos_received_total",
+                "counter",
+                "Centavos paid for station time.",
+                &self.centavos_received,
+            ),
+            ("pisonet_seconds_sold_total", "counter", "Seconds of station time sold.", &self.seconds_sold),
+        ];
+        for (name, kind, help, value) in totals {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+
+        let routes = match self.routes.lock() {
+            Ok(routes) => routes,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let _ = writeln!(out, "# HELP pisonet_http_requests_total HTTP requests handled.");
+        let _ = writeln!(out, "# TYPE pisonet_http_requests_total counter");
+        for (route, stats) in routes.iter() {
+            for (status, count) in &stats.responses {
+                let _ = writeln!(
+                    out,
+                    "pisonet_http_requests_total{{route=\"{}\",status=\"{}\"}} {}",
+                    route,
+                    status,
+                    count
+                );
+            }
+        }
+
+        let _ = writeln!(out, "# HELP pisonet_http_errors_total HTTP requests answered with 4xx or 5xx.");
+        let _ = writeln!(out, "# TYPE pisonet_http_errors_total counter");
+        for (route, stats) in routes.iter() {
+            let _ = writeln!(out, "pisonet_http_errors_total{{route=\"{}\"}} {}", route, stats.errors);
+        }
+
+        let _ = writeln!(out, "# HELP pisonet_http_request_duration_seconds HTTP request latency.");
+        let _ = writeln!(out, "# TYPE pisonet_http_request_duration_seconds histogram");
+        for (route, stats) in routes.iter() {
+            for (count, upper_bound) in stats.latency.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "pisonet_http_request_duration_seconds_bucket{{route=\"{}\",le=\"{}\"}} {}",
+                    route,
+                    upper_bound,
+                    count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "pisonet_http_request_duration_seconds_bucket{{route=\"{}\",le=\"+Inf\"}} {}",
+                route,
+                stats.latency.count
+            );
+            let _ = writeln!(
+                out,
+                "pisonet_http_request_duration_seconds_sum{{route=\"{}\"}} {}",
+                route,
+                stats.latency.sum
+            );
+            let _ = writeln!(
+                out,
+                "pisonet_http_request_duration_seconds_count{{route=\"{}\"}} {}",
+                route,
+                stats.latency.count
+            );
+        }
+
+        out
+    }
+}
+
+pub async fn track_requests(
+    State(metrics): State<Arc<Metrics>>,
+    request: Request,
+    next: Next
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    metrics.record_request(&route, response.status().as_u16(), started.elapsed().as_secs_f64());
+
+    response
+}
+
+pub async fn metrics_handler(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_includes_sales_counters() {
+        let metrics = Metrics::default();
+        metrics.session_started();
+        metrics.record_payment(300, 15);
+        metrics.record_payment(200, 10);
+
+        let text = metrics.render();
+        assert!(text.contains("pisonet_sessions_total 1\n"));
+        assert!(text.contains("pisonet_session_active 1\n"));
+        assert!(text.contains("pisonet_centavos_received_total 500\n"));
+        assert!(text.contains("pisonet_seconds_sold_total 25\n"));
+
+        metrics.session_ended();
+        assert!(metrics.render().contains("pisonet_session_active 0\n"));
+    }
+
+    #[test]
+    fn test_render_includes_latency_histogram_and_errors() {
+        let metrics = Metrics::default();
+        metrics.record_request("/api/v1/addtime", 200, 0.02);
+        metrics.record_request("/api/v1/addtime", 422, 0.2);
+
+        let text = metrics.render();
+        assert!(
+            text.contains(
+                "pisonet_http_request_duration_seconds_bucket{route=\"/api/v1/addtime\",le=\"0.025\"} 1\n"
+            )
+        );
+        assert!(
+            text.contains(
+                "pisonet_http_request_duration_seconds_bucket{route=\"/api/v1/addtime\",le=\"+Inf\"} 2\n"
+            )
+        );
+        assert!(text.contains("pisonet_http_requests_total{route=\"/api/v1/addtime\",status=\"422\"} 1\n"));
+        assert!(text.contains("pisonet_http_errors_total{route=\"/api/v1/addtime\"} 1\n"));
+    }
+}
@@ -1,2 +1,9 @@
+pub mod client;
 pub mod handler;
-pub mod models;
\ No newline at end of file
+pub mod metrics;
+pub mod peer;
+pub mod probes;
+pub mod ratelimit;
+pub mod tls;
+pub mod v1;
+pub mod v2;
\ No newline at end of file
@@ -0,0 +1,68 @@
+use async_trait::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::{ header, HeaderMap, StatusCode };
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::rand::{ SecureRandom, SystemRandom };
+use tauri::Manager;
+use tracing::error;
+
+use super::handler::ServerState;
+use crate::db::DatabaseState;
+
+// Secret handed to a station or counter when it pairs with this one. It is
+// sent back as `Authorization: Bearer <credential>` on every request that
+// changes this station or reads its sales.
+pub fn new_credential() -> anyhow::Result<String> {
+    let mut credential = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut credential)
+        .map_err(|_| anyhow::anyhow!("Failed to generate pairing credential"))?;
+    Ok(URL_SAFE_NO_PAD.encode(credential))
+}
+
+// Hardware id of the paired station or counter that sent the request.
+// Handlers taking it answer `401` to anyone else.
+pub struct PairedPeer(pub String);
+
+#[async_trait]
+impl FromRequestParts<ServerState> for PairedPeer {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &ServerState
+    ) -> Result<Self, Self::Rejection> {
+        let credential = credential(&parts.headers).ok_or((
+            StatusCode::UNAUTHORIZED,
+            "Not paired with this station",
+        ))?;
+
+        match state.app_handle.state::<DatabaseState>().peers().find(credential) {
+            Ok(Some(hwid)) => Ok(PairedPeer(hwid)),
+            Ok(None) => Err((StatusCode::UNAUTHORIZED, "Not paired with this station")),
+            Err(e) => {
+                error!("Failed to look up the pairing credential: {:#}", e);
+                Err((StatusCode::INTERNAL_SERVER_ERROR, "Pairing credentials unavailable"))
+            }
+        }
+    }
+}
+
+// Credential sent as `Authorization: Bearer <credential>`, not checked yet.
+pub fn credential(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .filter(|credential| !credential.is_empty())
+}
+
+// Adds the credential the station handed out when pairing, if any.
+pub fn authorize(request: reqwest::RequestBuilder, credential: &str) -> reqwest::RequestBuilder {
+    if credential.is_empty() {
+        return request;
+    }
+    request.bearer_auth(credential)
+}
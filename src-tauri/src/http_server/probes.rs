@@ -0,0 +1,68 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Serialize;
+use tauri::{ AppHandle, Manager };
+
+use super::handler::ServerState;
+use crate::settings::uuidmodel::UniqueId;
+use crate::AppConfigState;
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Readiness {
+    pub ready: bool,
+    pub config_loaded: bool,
+    pub license_valid: bool,
+    pub windows_present: bool,
+}
+
+impl Readiness {
+    pub fn new(config_loaded: bool, license_valid: bool, windows_present: bool) -> Self {
+        Readiness {
+            ready: config_loaded && license_valid && windows_present,
+            config_loaded,
+            license_valid,
+            windows_present,
+        }
+    }
+}
+
+pub async fn health_handler() -> &'static str {
+    "ok"
+}
+
+pub async fn ready_handler(State(state): State<ServerState>) -> (StatusCode, Json<Readiness>) {
+    let readiness = check_readiness(&state.app_handle);
+    let status = if readiness.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(readiness))
+}
+
+fn check_readiness(app_handle: &AppHandle) -> Readiness {
+    let windows_present = app_handle.get_webview_window("main").is_some();
+
+    let license = UniqueId::default()
+        .ok()
+        .and_then(|device| {
+            let config = app_handle.try_state::<AppConfigState>()?;
+            let config = config.lock().ok()?;
+            Some(config.get_license(device.id.as_str()))
+        });
+
+    match license {
+        Some(Ok(license)) => Readiness::new(true, license.authorized, windows_present),
+        _ => Readiness::new(false, false, windows_present),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ready_only_when_every_check_passes() {
+        assert!(Readiness::new(true, true, true).ready);
+        assert!(!Readiness::new(true, false, true).ready);
+        assert!(!Readiness::new(false, true, true).ready);
+        assert!(!Readiness::new(true, true, false).ready);
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::net::{ IpAddr, SocketAddr };
+use std::sync::{ Mutex, RwLock };
+use std::time::{ Duration, Instant };
+
+use axum::extract::{ ConnectInfo, Request, State };
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{ IntoResponse, Response };
+use tauri::Manager;
+
+use super::handler::ServerState;
+use super::peer;
+use crate::db::DatabaseState;
+use crate::settings::appconfigmodels::RateLimit;
+
+const WINDOW: Duration = Duration::from_secs(60);
+const PRUNE_THRESHOLD: usize = 1024;
+
+// Who a failed check is held against. A ban only refuses the same kind of
+// check, so a customer typing a wrong PIN does not lock out the station.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Offender {
+    // Failed pairings come before any credential, so by source address.
+    Address(IpAddr),
+    // Wrong voucher codes sent by a paired station.
+    Voucher {
+        peer: String,
+    },
+    // Wrong PINs for one member sent by a paired station.
+    Member {
+        peer: String,
+        username: String,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Rejection {
+    Banned,
+    TooManyRequests,
+}
+
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+impl Window {
+    // Counts one hit and returns the number of hits in the current window.
+    fn hit(&mut self, now: Instant) -> u32 {
+        if now.duration_since(self.started) >= WINDOW {
+            self.started = now;
+            self.count = 0;
+        }
+        self.count += 1;
+        self.count
+    }
+}
+
+struct Ban {
+    started: Instant,
+    length: Duration,
+}
+
+impl Ban {
+    fn active(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.started) < self.length
+    }
+}
+
+#[derive(Default)]
+struct Buckets {
+    per_ip: HashMap<IpAddr, Window>,
+    // Keyed by the hardware id of the paired peer that sent the request.
+    per_client: HashMap<String, Window>,
+    failures: HashMap<Offender, Window>,
+    banned: HashMap<Offender, Ban>,
+}
+
+impl Buckets {
+    fn prune(&mut self, now: Instant) {
+        let current = |window: &Window| now.duration_since(window.started) < WINDOW;
+        if self.per_ip.len() > PRUNE_THRESHOLD {
+            self.per_ip.retain(|_, window| current(window));
+        }
+        if self.per_client.len() > PRUNE_THRESHOLD {
+            self.per_client.retain(|_, window| current(window));
+        }
+        if self.failures.len() > PRUNE_THRESHOLD {
+            self.failures.retain(|_, window| current(window));
+        }
+        if self.banned.len() > PRUNE_THRESHOLD {
+            self.banned.retain(|_, ban| ban.active(now));
+        }
+    }
+}
+
+// Fixed window request limits per source IP and per paired peer, plus a
+// temporary ban list for offenders that keep failing pairing or other checks.
+// The limiter outlives server restarts so a rebind does not clear bans.
+pub struct RateLimiter {
+    config: RwLock<RateLimit>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimit) -> Self {
+        RateLimiter {
+            config: RwLock::new(config),
+            buckets: Mutex::new(Buckets::default()),
+        }
+    }
+
+    pub fn configure(&self, config: RateLimit) {
+        if let Ok(mut current) = self.config.write() {
+            *current = config;
+        }
+    }
+
+    pub fn max_body_bytes(&self) -> usize {
+        self.config().max_body_bytes
+    }
+
+    // `peer` is the hardware id of the paired peer the request authenticated
+    // as, if any.
+    pub fn check(&self, ip: IpAddr, peer: Option<&str>, now: Instant) -> Result<(), Rejection> {
+        let config = self.config();
+        if self.is_banned(&Offender::Address(ip), now) {
+            return Err(Rejection::Banned);
+        }
+
+        let mut buckets = self.buckets();
+        buckets.prune(now);
+
+        let ip_hits = buckets.per_ip
+            .entry(ip)
+            .or_insert(Window { started: now, count: 0 })
+            .hit(now);
+        if ip_hits > config.requests_per_minute_per_ip {
+            return Err(Rejection::TooManyRequests);
+        }
+
+        if let Some(peer) = peer {
+            let client_hits = buckets.per_client
+                .entry(peer.to_string())
+                .or_insert(Window { started: now, count: 0 })
+                .hit(now);
+            if client_hits > config.requests_per_minute_per_client {
+                return Err(Rejection::TooManyRequests);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_banned(&self, offender: &Offender, now: Instant) -> bool {
+        let mut buckets = self.buckets();
+        match buckets.banned.get(offender) {
+            Some(ban) if ban.active(now) => true,
+            Some(_) => {
+                buckets.banned.remove(offender);
+                false
+            }
+            None => false,
+        }
+    }
+
+    // Records a failed pairing, voucher or PIN check. Once an offender
+    // reaches `failures_before_ban` within a window it is banned for
+    // `ban_minutes`.
+    pub fn record_failure(&self, offender: Offender, now: Instant) {
+        let config = self.config();
+        let mut buckets = self.buckets();
+        buckets.prune(now);
+
+        let failures = buckets.failures
+            .entry(offender.clone())
+            .or_insert(Window { started: now, count: 0 })
+            .hit(now);
+        if failures >= config.failures_before_ban {
+            tracing::warn!(?offender, failures, "Banning after repeated failed attempts");
+            buckets.failures.remove(&offender);
+            let length = Duration::from_secs(config.ban_minutes.saturating_mul(60));
+            buckets.banned.insert(offender, Ban { started: now, length });
+        }
+    }
+
+    pub fn record_success(&self, offender: &Offender) {
+        self.buckets().failures.remove(offender);
+    }
+
+    fn config(&self) -> RateLimit {
+        match self.config.read() {
+            Ok(config) => config.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    fn buckets(&self) -> std::sync::MutexGuard<'_, Buckets> {
+        match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+pub async fn limit_requests(
+    State(state): State<ServerState>,
+    ConnectInfo(source): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next
+) -> Response {
+    // Only a credential this station handed out counts as a paired peer, so a
+    // client cannot pick whose budget it spends
+    let peer = peer
+        ::credential(request.headers())
+        .and_then(|credential| {
+            state.app_handle.state::<DatabaseState>().peers().find(credential).ok().flatten()
+        });
+
+    match state.limiter.check(source.ip(), peer.as_deref(), Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(Rejection::Banned) => (StatusCode::FORBIDDEN, "Temporarily banned").into_response(),
+        Err(Rejection::TooManyRequests) => {
+            (StatusCode::TOO_MANY_REQUESTS, "Too many requests").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(RateLimit {
+            requests_per_minute_per_ip: 3,
+            requests_per_minute_per_client: 2,
+            max_body_bytes: 1024,
+            failures_before_ban: 2,
+            ban_minutes: 1,
+        })
+    }
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([192, 168, 1, last])
+    }
+
+    #[test]
+    fn test_limits_requests_per_ip_within_a_window() {
+        let limiter = limiter();
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.check(ip(1), None, now).is_ok());
+        }
+        assert_eq!(limiter.check(ip(1), None, now), Err(Rejection::TooManyRequests));
+        assert!(limiter.check(ip(2), None, now).is_ok());
+        assert!(limiter.check(ip(1), None, now + WINDOW).is_ok());
+    }
+
+    #[test]
+    fn test_limits_requests_per_peer_across_ips() {
+        let limiter = limiter();
+        let now = Instant::now();
+        assert!(limiter.check(ip(1), Some("station-a"), now).is_ok());
+        assert!(limiter.check(ip(2), Some("station-a"), now).is_ok());
+        assert_eq!(limiter.check(ip(3), Some("station-a"), now), Err(Rejection::TooManyRequests));
+        assert!(limiter.check(ip(3), Some("station-b"), now).is_ok());
+    }
+
+    #[test]
+    fn test_bans_source_after_repeated_failures_until_expiry() {
+        let limiter = limiter();
+        let now = Instant::now();
+        limiter.record_failure(Offender::Address(ip(1)), now);
+        assert!(limiter.check(ip(1), None, now).is_ok());
+        limiter.record_failure(Offender::Address(ip(1)), now);
+        assert_eq!(limiter.check(ip(1), None, now), Err(Rejection::Banned));
+        assert!(limiter.check(ip(1), None, now + Duration::from_secs(61)).is_ok());
+    }
+
+    #[test]
+    fn test_ban_only_refuses_the_same_check() {
+        let limiter = limiter();
+        let now = Instant::now();
+        let member = |username: &str| Offender::Member {
+            peer: "station-a".to_string(),
+            username: username.to_string(),
+        };
+        limiter.record_failure(member("juan"), now);
+        limiter.record_failure(member("juan"), now);
+        assert!(limiter.is_banned(&member("juan"), now));
+        assert!(!limiter.is_banned(&member("maria"), now));
+        assert!(!limiter.is_banned(&Offender::Voucher { peer: "station-a".to_string() }, now));
+        assert!(limiter.check(ip(1), Some("station-a"), now).is_ok());
+    }
+
+    #[test]
+    fn test_success_clears_failures() {
+        let limiter = limiter();
+        let now = Instant::now();
+        limiter.record_failure(Offender::Address(ip(1)), now);
+        limiter.record_success(&Offender::Address(ip(1)));
+        limiter.record_failure(Offender::Address(ip(1)), now);
+        assert!(limiter.check(ip(1), None, now).is_ok());
+    }
+
+    #[test]
+    fn test_long_ban_does_not_overflow() {
+        let limiter = limiter();
+        limiter.configure(RateLimit { ban_minutes: u64::MAX, ..RateLimit::default() });
+        let now = Instant::now();
+        for _ in 0..RateLimit::default().failures_before_ban {
+            limiter.record_failure(Offender::Address(ip(1)), now);
+        }
+        assert!(limiter.is_banned(&Offender::Address(ip(1)), now + Duration::from_secs(86_400)));
+    }
+
+    #[test]
+    fn test_failures_and_bans_are_pruned() {
+        let limiter = limiter();
+        let now = Instant::now();
+        for last in 0..=PRUNE_THRESHOLD {
+            let address = IpAddr::from([10, 0, (last / 256) as u8, (last % 256) as u8]);
+            limiter.record_failure(Offender::Address(address), now);
+            limiter.record_failure(Offender::Address(address), now);
+            limiter.record_failure(Offender::Address(address), now);
+        }
+        assert!(limiter.buckets().banned.len() > PRUNE_THRESHOLD);
+        assert!(limiter.buckets().failures.len() > PRUNE_THRESHOLD);
+
+        limiter.record_failure(Offender::Address(ip(1)), now + WINDOW);
+        assert_eq!(limiter.buckets().banned.len(), 0);
+        assert_eq!(limiter.buckets().failures.len(), 1);
+    }
+}
@@ -0,0 +1,222 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{ Arc, Mutex };
+
+use anyhow::{ Context, Result };
+use rcgen::{ generate_simple_self_signed, CertifiedKey };
+use rustls::client::danger::{ HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier };
+use rustls::crypto::{ ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider };
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{ CertificateDer, PrivateKeyDer, ServerName, UnixTime };
+use rustls::{ ClientConfig, DigitallySignedStruct, ServerConfig, SignatureScheme };
+use sha2::{ Digest, Sha256 };
+
+const CERT_FILE: &str = "server.crt";
+const KEY_FILE: &str = "server.key";
+
+pub struct TlsIdentity {
+    pub cert: CertificateDer<'static>,
+    pub key: PrivateKeyDer<'static>,
+    pub fingerprint: String,
+}
+
+impl TlsIdentity {
+    pub fn server_config(&self) -> Result<ServerConfig> {
+        let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .with_context(|| "Failed to select TLS protocol versions")?
+            .with_no_client_auth()
+            .with_single_cert(vec![self.cert.clone()], self.key.clone_key())
+            .with_context(|| "Failed to load server certificate")?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+}
+
+// Loads the station certificate from `dir`, generating a self-signed one on
+// the first run.
+pub fn load_or_generate(dir: &Path, host: &str) -> Result<TlsIdentity> {
+    let cert_path = dir.join(CERT_FILE);
+    let key_path = dir.join(KEY_FILE);
+
+    if !cert_path.exists() || !key_path.exists() {
+        let CertifiedKey { cert, key_pair } = generate_simple_self_signed(
+            vec![host.to_string(), "localhost".to_string()]
+        ).with_context(|| "Failed to generate self-signed certificate")?;
+
+        fs::write(&cert_path, cert.pem()).with_context(|| "Unable to write certificate")?;
+        // Owner only on unix, see `audit::store::load_key` for Windows
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(&key_path)
+            .and_then(|mut file| file.write_all(key_pair.serialize_pem().as_bytes()))
+            .with_context(|| "Unable to write key")?;
+    }
+
+    let cert = CertificateDer::from_pem_file(&cert_path).with_context(
+        || "Failed to read certificate"
+    )?;
+    let key = PrivateKeyDer::from_pem_file(&key_path).with_context(|| "Failed to read key")?;
+    let fingerprint = fingerprint(&cert);
+
+    Ok(TlsIdentity { cert, key, fingerprint })
+}
+
+// SHA-256 of the DER encoded certificate, as lowercase hex.
+pub fn fingerprint(cert: &CertificateDer<'_>) -> String {
+    Sha256::digest(cert.as_ref())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Verifies the station certificate against the fingerprint recorded at
+// pairing time. Without a recorded fingerprint the first certificate seen is
+// accepted (trust on first use) and kept so the caller can pin it.
+#[derive(Debug)]
+pub struct PinnedCertVerifier {
+    expected: Option<String>,
+    seen: Mutex<Option<String>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinnedCertVerifier {
+    pub fn new(expected: Option<&str>) -> Self {
+        PinnedCertVerifier {
+            expected: expected.map(|fingerprint| fingerprint.to_lowercase()),
+            seen: Mutex::new(None),
+            provider: Arc::new(ring::default_provider()),
+        }
+    }
+
+    pub fn seen_fingerprint(&self) -> Option<String> {
+        self.seen
+            .lock()
+            .ok()
+            .and_then(|seen| seen.clone())
+    }
+}
+
+pub fn pinned_http_client(
+    expected: Option<&str>
+) -> Result<(reqwest::Client, Arc<PinnedCertVerifier>)> {
+    let verifier = Arc::new(PinnedCertVerifier::new(expected));
+    let config = ClientConfig::builder_with_provider(verifier.provider.clone())
+        .with_safe_default_protocol_versions()
+        .with_context(|| "Failed to select TLS protocol versions")?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier.clone())
+        .with_no_client_auth();
+
+    let client = reqwest::Client
+        ::builder()
+        .use_preconfigured_tls(config)
+        .build()
+        .with_context(|| "Failed to build pinned HTTP client")?;
+
+    Ok((client, verifier))
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let presented = fingerprint(end_entity);
+        if let Some(expected) = &self.expected {
+            if &presented != expected {
+                return Err(
+                    rustls::Error::General("Certificate does not match the pinned fingerprint".into())
+                );
+            }
+        }
+
+        if let Ok(mut seen) = self.seen.lock() {
+            *seen = Some(presented);
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_certificate_is_generated_once_and_reused() {
+        let dir = temp_dir("cps_tls_reuse");
+        let first = load_or_generate(&dir, "127.0.0.1").unwrap();
+        let second = load_or_generate(&dir, "127.0.0.1").unwrap();
+        assert_eq!(first.fingerprint, second.fingerprint);
+        assert_eq!(first.fingerprint.len(), 64);
+        assert!(first.server_config().is_ok());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join(KEY_FILE)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_pinned_verifier_rejects_other_certificate() {
+        let pinned = load_or_generate(&temp_dir("cps_tls_pinned"), "127.0.0.1").unwrap();
+        let other = load_or_generate(&temp_dir("cps_tls_other"), "127.0.0.1").unwrap();
+        let verifier = PinnedCertVerifier::new(Some(&pinned.fingerprint));
+        let name = ServerName::try_from("127.0.0.1").unwrap();
+
+        let accepted = verifier.verify_server_cert(&pinned.cert, &[], &name, &[], UnixTime::now());
+        assert!(accepted.is_ok());
+        let rejected = verifier.verify_server_cert(&other.cert, &[], &name, &[], UnixTime::now());
+        assert!(rejected.is_err());
+    }
+
+    #[test]
+    fn test_unpinned_verifier_records_first_certificate() {
+        let identity = load_or_generate(&temp_dir("cps_tls_tofu"), "127.0.0.1").unwrap();
+        let verifier = PinnedCertVerifier::new(None);
+        let name = ServerName::try_from("127.0.0.1").unwrap();
+
+        let accepted = verifier.verify_server_cert(&identity.cert, &[], &name, &[], UnixTime::now());
+        assert!(accepted.is_ok());
+        assert_eq!(verifier.seen_fingerprint(), Some(identity.fingerprint));
+    }
+}
@@ -0,0 +1,177 @@
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use axum::extract::{ ConnectInfo, Query, State };
+use axum::http::{ header, StatusCode };
+use axum::response::{ IntoResponse, Response };
+use axum::Json;
+use tauri::{ Emitter, Manager };
+
+use super::models::{
+    RegisterRequest,
+    RegisterResponse,
+    AddTimeRequest,
+    AddTimeResponse,
+    SalesReportQuery,
+};
+use super::ApiDoc;
+use crate::constants;
+use crate::db::DatabaseState;
+use crate::http_server::handler::{ self, ServerState };
+use crate::http_server::peer::PairedPeer;
+use crate::http_server::ratelimit::Offender;
+use crate::pairing::PairingCode;
+use crate::payment::{ Payment, PaymentSource };
+use crate::promotion::Credit;
+use crate::reports::summary::ReportRow;
+use crate::reports::{ self, ReportFormat };
+use utoipa::OpenApi;
+
+#[utoipa::path(
+    post,
+    path = "/register",
+    request_body = RegisterRequest,
+    responses(
+        (status = 200, description = "Pairing result", body = RegisterResponse),
+        (status = 403, description = "Source is temporarily banned after repeated failed pairings"),
+        (status = 429, description = "Too many requests")
+    )
+)]
+pub async fn register_handler(
+    State(state): State<ServerState>,
+    ConnectInfo(source): ConnectInfo<SocketAddr>,
+    Json(payload): Json<RegisterRequest>
+) -> Json<RegisterResponse> {
+    Json(register(&state, source, payload))
+}
+
+// Checks the pairing code, shared by every API version.
+pub fn register(
+    state: &ServerState,
+    source: SocketAddr,
+    payload: RegisterRequest
+) -> RegisterResponse {
+    let rejected = |text: String| RegisterResponse {
+        status: false,
+        server_hwid: "".to_string(),
+        server_address: "".to_string(),
+        server_cert_fingerprint: "".to_string(),
+        text,
+    };
+
+    let pairing = state.app_handle.state::<PairingCode>();
+    match pairing.take(&payload.pair_id) {
+        Ok(true) => {}
+        Ok(false) => {
+            state.limiter.record_failure(Offender::Address(source.ip()), Instant::now());
+            return rejected("Invalid pair_id".to_string());
+        }
+        Err(e) => {
+            return rejected(format!("{:#}", e));
+        }
+    }
+
+    state.limiter.record_success(&Offender::Address(source.ip()));
+    let _ = state.app_handle.emit(constants::HANDLER_PAIRING_CODE, pairing.current());
+
+    let status = true;
+    let server_hwid = state.hwid.clone();
+    let server_address = state.address.clone();
+    let server_cert_fingerprint = state.cert_fingerprint.clone().unwrap_or_default();
+    let text = "Registration successful".to_string();
+
+    let register_response = RegisterResponse {
+        status,
+        server_hwid,
+        server_address,
+        server_cert_fingerprint,
+        text,
+    };
+
+    let _ = state.app_handle.emit("register_request", register_response.clone());
+
+    register_response
+}
+
+#[utoipa::path(
+    post,
+    path = "/addtime",
+    request_body = AddTimeRequest,
+    responses(
+        (status = 200, description = "Credits accepted", body = AddTimeResponse),
+        (status = 429, description = "Too many requests")
+    )
+)]
+pub async fn add_time_handler(
+    State(state): State<ServerState>,
+    ConnectInfo(source): ConnectInfo<SocketAddr>,
+    Json(payload): Json<AddTimeRequest>
+) -> Json<AddTimeResponse> {
+    // Version 1 clients count ₱1 coins
+    let payment = Payment {
+        amount_centavos: (payload.credits as u64) * 100,
+        ..Payment::cash(PaymentSource::Coin, 100)
+    };
+    let response = match handler::add_payment(&state, payment, source.ip()).await {
+        Ok(credit) => AddTimeResponse { status: true, text: added_text(&credit) },
+        Err(e) => AddTimeResponse { status: false, text: e.to_string() },
+    };
+
+    Json(response)
+}
+
+// The version 1 response has no field for the promotions, so they are told in
+// the text.
+fn added_text(credit: &Credit) -> String {
+    if credit.promotions.is_empty() {
+        return "Time added successfully".to_string();
+    }
+    let promotions: Vec<String> = credit.promotions
+        .iter()
+        .map(|promotion| format!("{} (+{}s)", promotion.name, promotion.bonus_seconds))
+        .collect();
+    format!("Time added successfully with {}", promotions.join(", "))
+}
+
+#[utoipa::path(
+    get,
+    path = "/reports/sales",
+    params(SalesReportQuery),
+    responses(
+        (
+            status = 200,
+            description = "Totals per station and period",
+            content((Vec<ReportRow> = "application/json"), (String = "text/csv"))
+        ),
+        (status = 401, description = "Not paired with this station"),
+        (status = 500, description = "The sales database could not be read")
+    )
+)]
+pub async fn sales_report_handler(
+    State(state): State<ServerState>,
+    _counter: PairedPeer,
+    Query(query): Query<SalesReportQuery>
+) -> Response {
+    let database = state.app_handle.state::<DatabaseState>();
+    let report = reports
+        ::sales_report(&database, query.granularity, query.from, query.to)
+        .and_then(|rows| reports::render(&rows, query.format));
+
+    let content_type = match query.format {
+        ReportFormat::Json => "application/json",
+        ReportFormat::Csv => "text/csv",
+    };
+    match report {
+        Ok(body) => ([(header::CONTENT_TYPE, content_type)], body).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    responses((status = 200, description = "OpenAPI document of this API version"))
+)]
+pub async fn openapi_handler() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
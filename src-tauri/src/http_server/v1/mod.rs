@@ -0,0 +1,72 @@
+//! Version 1 of the station HTTP API, served under [`PREFIX`].
+//!
+//! Request and response types of a published version are frozen: breaking
+//! changes go into a new `vN` module that is nested next to this one in
+//! `http_server::handler::router`, so older clients keep working.
+//!
+//! `/reports/sales` needs the credential handed out by the version 2
+//! `/register`.
+
+pub mod handler;
+pub mod models;
+
+use axum::Router;
+use axum::routing::{ get, post };
+use utoipa::OpenApi;
+
+use crate::http_server::handler::ServerState;
+
+pub const PREFIX: &str = "/api/v1";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Centralized Pisonet Station API", version = "1"),
+    servers((url = "/api/v1")),
+    paths(
+        handler::register_handler,
+        handler::add_time_handler,
+        handler::sales_report_handler,
+        handler::openapi_handler
+    ),
+    components(
+        schemas(
+            models::RegisterRequest,
+            models::RegisterResponse,
+            models::AddTimeRequest,
+            models::AddTimeResponse,
+            crate::reports::summary::ReportRow
+        )
+    )
+)]
+pub struct ApiDoc;
+
+pub fn router() -> Router<ServerState> {
+    Router::new()
+        .route("/register", post(handler::register_handler))
+        .route("/addtime", post(handler::add_time_handler))
+        .route("/reports/sales", get(handler::sales_report_handler))
+        .route("/openapi.json", get(handler::openapi_handler))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_documents_all_routes() {
+        let doc = ApiDoc::openapi();
+        for path in ["/register", "/addtime", "/reports/sales", "/openapi.json"] {
+            assert!(doc.paths.paths.contains_key(path), "{} should be documented", path);
+        }
+    }
+
+    #[test]
+    fn test_openapi_documents_request_field_names() {
+        let json = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let add_time = &json["components"]["schemas"]["AddTimeRequest"]["properties"];
+        assert!(add_time.get("credits").is_some());
+        let register = &json["components"]["schemas"]["RegisterRequest"]["properties"];
+        assert!(register.get("pair_id").is_some());
+        assert!(register.get("hwid").is_some());
+    }
+}
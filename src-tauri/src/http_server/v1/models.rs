@@ -0,0 +1,71 @@
+use serde::{ Deserialize, Serialize };
+use chrono::{ DateTime, Utc };
+use utoipa::{ IntoParams, ToSchema };
+
+use crate::reports::summary::Granularity;
+use crate::reports::ReportFormat;
+
+/// Pairing request sent by a client device to this station.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct RegisterRequest {
+    /// One-time pairing code shown in the station settings.
+    pub pair_id: String,
+    /// Address the client can be reached at.
+    #[schema(example = "192.168.1.50")]
+    pub address: String,
+    /// Hardware id of the client.
+    #[schema(example = "coin-acceptor-01")]
+    pub hwid: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct RegisterResponse {
+    /// `true` when the pairing was accepted.
+    pub status: bool,
+    /// Hardware id of this station.
+    pub server_hwid: String,
+    /// Address and port of this station.
+    pub server_address: String,
+    /// SHA-256 fingerprint of the station TLS certificate, empty when TLS is
+    /// disabled. Clients pin it and refuse any other certificate afterwards.
+    #[serde(default)]
+    pub server_cert_fingerprint: String,
+    /// Human readable result.
+    pub text: String,
+}
+
+/// Credits inserted on the client device.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct AddTimeRequest {
+    /// Number of coins inserted.
+    #[schema(example = 1)]
+    pub credits: u8,
+}
+
+#[derive(Serialize, Clone, ToSchema)]
+pub struct AddTimeResponse {
+    /// `true` when the credits were accepted.
+    pub status: bool,
+    /// Human readable result.
+    pub text: String,
+}
+
+/// Filter of the sales report, `from` is inclusive and `to` exclusive.
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SalesReportQuery {
+    /// `hour`, `day` (default) or `month`.
+    #[serde(default)]
+    #[param(value_type = Option<String>, example = "day")]
+    pub granularity: Granularity,
+    /// RFC 3339 timestamp.
+    #[param(value_type = Option<String>, example = "2026-10-01T00:00:00+08:00")]
+    pub from: Option<DateTime<Utc>>,
+    /// RFC 3339 timestamp.
+    #[param(value_type = Option<String>)]
+    pub to: Option<DateTime<Utc>>,
+    /// `json` (default) or `csv`.
+    #[serde(default)]
+    #[param(value_type = Option<String>, example = "csv")]
+    pub format: ReportFormat,
+}
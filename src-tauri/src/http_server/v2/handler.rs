@@ -0,0 +1,511 @@
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use axum::extract::{ ConnectInfo, State };
+use axum::http::StatusCode;
+use axum::response::{ IntoResponse, Response };
+use axum::Json;
+use chrono::Utc;
+use tauri::{ Emitter, Manager };
+use utoipa::OpenApi;
+
+use super::models::{
+    RegisterResponse,
+    AddTimeRequest,
+    AddTimeResponse,
+    AdjustTimeRequest,
+    AdjustTimeResponse,
+    EndSessionRequest,
+    EndSessionResponse,
+    MemberSignInRequest,
+    MemberSignInResponse,
+    MemberSignOutRequest,
+    MemberSignOutResponse,
+    RedeemVoucherRequest,
+    RedeemVoucherResponse,
+    StationMessageRequest,
+    StationMessageResponse,
+};
+use super::ApiDoc;
+use crate::constants;
+use crate::audit::event::AuditEvent;
+use crate::audit::store::AuditLog;
+use crate::db::DatabaseState;
+use crate::http_server::handler::{ self, ServerState };
+use crate::http_server::peer::{ self, PairedPeer };
+use crate::http_server::ratelimit::Offender;
+use crate::http_server::v1::{ self, models::RegisterRequest };
+use crate::member::{ self, MemberError };
+use crate::message::{ Inbox, StationMessage, MAX_MESSAGE_LENGTH };
+use crate::payment::PaymentError;
+use crate::station::{ self, StationStatus };
+use crate::voucher::{ self, VoucherError };
+use crate::window_manager;
+
+#[utoipa::path(
+    post,
+    path = "/register",
+    request_body = RegisterRequest,
+    responses(
+        (status = 200, description = "Pairing result", body = RegisterResponse),
+        (status = 403, description = "Source is temporarily banned after repeated failed pairings"),
+        (status = 429, description = "Too many requests")
+    )
+)]
+pub async fn register_handler(
+    State(state): State<ServerState>,
+    ConnectInfo(source): ConnectInfo<SocketAddr>,
+    Json(payload): Json<RegisterRequest>
+) -> Json<RegisterResponse> {
+    let hwid = payload.hwid.clone();
+    let address = payload.address.clone();
+    let registered = v1::handler::register(&state, source, payload);
+    let mut response = RegisterResponse {
+        status: registered.status,
+        server_hwid: registered.server_hwid,
+        server_address: registered.server_address,
+        server_cert_fingerprint: registered.server_cert_fingerprint,
+        credential: String::new(),
+        text: registered.text,
+    };
+    if !response.status {
+        return Json(response);
+    }
+
+    let saved = peer::new_credential().and_then(|credential| {
+        state.app_handle
+            .state::<DatabaseState>()
+            .peers()
+            .add(&hwid, &address, &credential, Utc::now())
+            .map(|()| credential)
+    });
+    match saved {
+        Ok(credential) => {
+            response.credential = credential;
+        }
+        Err(e) => {
+            response.status = false;
+            response.text = format!("Failed to save the pairing: {:#}", e);
+        }
+    }
+    Json(response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/addtime",
+    request_body = AddTimeRequest,
+    responses(
+        (status = 200, description = "Payment accepted", body = AddTimeResponse),
+        (
+            status = 422,
+            description = "Not a coin or bill, denomination not accepted or amount not valid",
+            body = AddTimeResponse
+        ),
+        (status = 429, description = "Too many requests"),
+        (status = 500, description = "Payment could not be credited", body = AddTimeResponse)
+    )
+)]
+pub async fn add_time_handler(
+    State(state): State<ServerState>,
+    ConnectInfo(source): ConnectInfo<SocketAddr>,
+    Json(payload): Json<AddTimeRequest>
+) -> (StatusCode, Json<AddTimeResponse>) {
+    match handler::add_payment(&state, payload.into(), source.ip()).await {
+        Ok(credit) => {
+            let response = AddTimeResponse {
+                status: true,
+                seconds: credit.seconds,
+                promotions: credit.promotions,
+                text: "Time added successfully".to_string(),
+            };
+            (StatusCode::OK, Json(response))
+        }
+        Err(e) => {
+            let status = match e {
+                PaymentError::NotCredited => StatusCode::INTERNAL_SERVER_ERROR,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            };
+            let response = AddTimeResponse {
+                status: false,
+                seconds: 0,
+                promotions: Vec::new(),
+                text: e.to_string(),
+            };
+            (status, Json(response))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/vouchers/redeem",
+    request_body = RedeemVoucherRequest,
+    responses(
+        (status = 200, description = "Voucher redeemed", body = RedeemVoucherResponse),
+        (status = 404, description = "Unknown code", body = RedeemVoucherResponse),
+        (status = 409, description = "Code was already used", body = RedeemVoucherResponse),
+        (status = 401, description = "Not paired with this station"),
+        (status = 410, description = "Code has expired", body = RedeemVoucherResponse),
+        (
+            status = 429,
+            description = "Too many requests, or too many wrong codes from the station",
+            body = RedeemVoucherResponse
+        )
+    )
+)]
+pub async fn redeem_voucher_handler(
+    State(state): State<ServerState>,
+    PairedPeer(peer): PairedPeer,
+    Json(payload): Json<RedeemVoucherRequest>
+) -> (StatusCode, Json<RedeemVoucherResponse>) {
+    let offender = Offender::Voucher { peer: peer.clone() };
+    if state.limiter.is_banned(&offender, Instant::now()) {
+        return refuse_voucher(&state, offender, VoucherError::TooManyAttempts);
+    }
+    let Some(code) = voucher::normalize(&payload.code) else {
+        return refuse_voucher(&state, offender, VoucherError::NotFound);
+    };
+
+    // The caller asked its own paired stations, this one only asks its others
+    // when told to pass the request on
+    let database = state.app_handle.state::<DatabaseState>();
+    let mut passed_on = false;
+    let mut result = voucher::redeem_here(&database, &code, &payload.station);
+    if result == Err(VoucherError::NotFound) && payload.pass_on {
+        passed_on = true;
+        result = voucher::ask_paired(&database, &code, &payload.station, Some(&peer), false).await;
+    }
+    let seconds = match result {
+        Ok(seconds) => seconds,
+        Err(e) => {
+            return refuse_voucher(&state, offender, e);
+        }
+    };
+
+    state.limiter.record_success(&offender);
+    // The station that had the voucher keeps it in its own audit log
+    if !passed_on {
+        state.app_handle.state::<AuditLog>().record(AuditEvent::VoucherRedeemed {
+            code: voucher::display(&code),
+            station: payload.station,
+            seconds,
+        });
+    }
+    let response = RedeemVoucherResponse {
+        status: true,
+        seconds,
+        error: None,
+        text: "Voucher redeemed".to_string(),
+    };
+    (StatusCode::OK, Json(response))
+}
+
+fn refuse_voucher(
+    state: &ServerState,
+    offender: Offender,
+    error: VoucherError
+) -> (StatusCode, Json<RedeemVoucherResponse>) {
+    let status = match error {
+        VoucherError::NotFound => StatusCode::NOT_FOUND,
+        VoucherError::AlreadyRedeemed { .. } => StatusCode::CONFLICT,
+        VoucherError::Expired { .. } => StatusCode::GONE,
+        VoucherError::TooManyAttempts => {
+            return (StatusCode::TOO_MANY_REQUESTS, Json(refused(error)));
+        }
+        VoucherError::Unavailable { .. } => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(refused(error)));
+        }
+    };
+    // Guessing codes counts towards a ban per station, so the customers of
+    // other stations can still redeem theirs
+    state.limiter.record_failure(offender, Instant::now());
+    (status, Json(refused(error)))
+}
+
+fn refused(error: VoucherError) -> RedeemVoucherResponse {
+    RedeemVoucherResponse {
+        status: false,
+        seconds: 0,
+        text: error.to_string(),
+        error: Some(error),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/members/signin",
+    request_body = MemberSignInRequest,
+    responses(
+        (status = 200, description = "Member signed in", body = MemberSignInResponse),
+        (
+            status = 401,
+            description = "Wrong username or PIN, or not paired with this station",
+            body = MemberSignInResponse
+        ),
+        (
+            status = 409,
+            description = "Member is signed in on another station",
+            body = MemberSignInResponse
+        ),
+        (
+            status = 429,
+            description = "Too many requests, or too many wrong PINs for the member",
+            body = MemberSignInResponse
+        )
+    )
+)]
+pub async fn member_sign_in_handler(
+    State(state): State<ServerState>,
+    PairedPeer(peer): PairedPeer,
+    Json(payload): Json<MemberSignInRequest>
+) -> (StatusCode, Json<MemberSignInResponse>) {
+    // Counted per station and username: everyone behind the station shares
+    // its address, and other members must still be able to sign in
+    let offender = Offender::Member { peer: peer.clone(), username: payload.username.clone() };
+    // Only accounts kept here, the caller asks the other stations itself
+    let database = state.app_handle.state::<DatabaseState>();
+    let result = if state.limiter.is_banned(&offender, Instant::now()) {
+        Err(MemberError::TooManyAttempts)
+    } else {
+        member::sign_in_here(
+            &database,
+            &payload.username,
+            &payload.pin,
+            &payload.station,
+            Some(&peer)
+        )
+    };
+    let (token, seconds) = match result {
+        Ok(signed_in) => signed_in,
+        Err(e) => {
+            let status = match e {
+                MemberError::NotFound | MemberError::WrongCredentials => StatusCode::UNAUTHORIZED,
+                MemberError::TooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
+                MemberError::SignedIn { .. } | MemberError::NotSignedIn => StatusCode::CONFLICT,
+                MemberError::Unavailable { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            if e == MemberError::WrongCredentials {
+                state.limiter.record_failure(offender, Instant::now());
+            }
+            let response = MemberSignInResponse {
+                status: false,
+                seconds: 0,
+                token: String::new(),
+                text: e.to_string(),
+                error: Some(e),
+            };
+            return (status, Json(response));
+        }
+    };
+
+    state.limiter.record_success(&offender);
+    state.app_handle.state::<AuditLog>().record(AuditEvent::MemberSignedIn {
+        username: payload.username,
+        station: payload.station,
+        seconds,
+    });
+    let response = MemberSignInResponse {
+        status: true,
+        seconds,
+        token,
+        error: None,
+        text: "Member signed in".to_string(),
+    };
+    (StatusCode::OK, Json(response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/members/signout",
+    request_body = MemberSignOutRequest,
+    responses(
+        (status = 200, description = "Member signed out", body = MemberSignOutResponse),
+        (
+            status = 409,
+            description = "Token is not the one of the current sign in",
+            body = MemberSignOutResponse
+        ),
+        (status = 401, description = "Not paired with this station"),
+        (status = 429, description = "Too many requests")
+    )
+)]
+pub async fn member_sign_out_handler(
+    State(state): State<ServerState>,
+    PairedPeer(peer): PairedPeer,
+    Json(payload): Json<MemberSignOutRequest>
+) -> (StatusCode, Json<MemberSignOutResponse>) {
+    // Only the station the member signed in on knows the token
+    let database = state.app_handle.state::<DatabaseState>();
+    let result = member::sign_out_here(
+        &database,
+        &payload.username,
+        &payload.token,
+        Some(&peer),
+        payload.seconds
+    );
+    let balance = match result {
+        Ok(balance) => balance,
+        Err(e) => {
+            let status = match e {
+                MemberError::Unavailable { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+                _ => StatusCode::CONFLICT,
+            };
+            let response = MemberSignOutResponse {
+                status: false,
+                balance_seconds: 0,
+                text: e.to_string(),
+                error: Some(e),
+            };
+            return (status, Json(response));
+        }
+    };
+
+    state.app_handle.state::<AuditLog>().record(AuditEvent::MemberSignedOut {
+        username: payload.username,
+        station: payload.station,
+        seconds: payload.seconds,
+    });
+    let response = MemberSignOutResponse {
+        status: true,
+        balance_seconds: balance,
+        error: None,
+        text: "Member signed out".to_string(),
+    };
+    (StatusCode::OK, Json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/station",
+    responses(
+        (status = 200, description = "What the station is doing", body = StationStatus),
+        (status = 401, description = "Not paired with this station"),
+        (status = 500, description = "The station settings could not be read")
+    )
+)]
+pub async fn station_status_handler(
+    State(state): State<ServerState>,
+    _counter: PairedPeer
+) -> Response {
+    match station::status(&state.app_handle) {
+        Ok(status) => Json(status).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/station/adjust",
+    request_body = AdjustTimeRequest,
+    responses(
+        (status = 200, description = "Time changed", body = AdjustTimeResponse),
+        (status = 401, description = "Not paired with this station"),
+        (status = 429, description = "Too many requests")
+    )
+)]
+pub async fn adjust_time_handler(
+    State(state): State<ServerState>,
+    PairedPeer(counter): PairedPeer,
+    Json(payload): Json<AdjustTimeRequest>
+) -> Json<AdjustTimeResponse> {
+    let remaining_seconds = station::adjust_time(
+        &state.app_handle,
+        payload.seconds,
+        payload.reason,
+        format!("counter {}", counter)
+    );
+    Json(AdjustTimeResponse {
+        status: true,
+        remaining_seconds,
+        text: "Time changed".to_string(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/station/end",
+    request_body = EndSessionRequest,
+    responses(
+        (status = 200, description = "Session ended", body = EndSessionResponse),
+        (
+            status = 500,
+            description = "The signed in member could not be signed out",
+            body = EndSessionResponse
+        ),
+        (status = 401, description = "Not paired with this station"),
+        (status = 429, description = "Too many requests")
+    )
+)]
+pub async fn end_session_handler(
+    State(state): State<ServerState>,
+    PairedPeer(counter): PairedPeer,
+    Json(payload): Json<EndSessionRequest>
+) -> (StatusCode, Json<EndSessionResponse>) {
+    let actor = format!("counter {}", counter);
+    match station::end_session(&state.app_handle, payload.reason, actor).await {
+        Ok(seconds) => {
+            let response = EndSessionResponse {
+                status: true,
+                seconds,
+                text: "Session ended".to_string(),
+            };
+            (StatusCode::OK, Json(response))
+        }
+        Err(e) => {
+            let response = EndSessionResponse { status: false, seconds: 0, text: e.to_string() };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/station/message",
+    request_body = StationMessageRequest,
+    responses(
+        (status = 200, description = "Message shown", body = StationMessageResponse),
+        (status = 401, description = "Not paired with this station"),
+        (status = 422, description = "Message is empty or too long", body = StationMessageResponse),
+        (status = 429, description = "Too many requests")
+    )
+)]
+pub async fn station_message_handler(
+    State(state): State<ServerState>,
+    _counter: PairedPeer,
+    Json(payload): Json<StationMessageRequest>
+) -> (StatusCode, Json<StationMessageResponse>) {
+    let text = payload.text.trim();
+    if text.is_empty() || text.chars().count() > MAX_MESSAGE_LENGTH {
+        let response = StationMessageResponse {
+            status: false,
+            text: format!("Messages have 1 to {} characters", MAX_MESSAGE_LENGTH),
+        };
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(response));
+    }
+
+    let message = StationMessage {
+        id: payload.id,
+        text: text.to_string(),
+        sent_at: Utc::now(),
+        bring_to_front: payload.bring_to_front,
+    };
+    // The counter may send again after a timeout, show it once
+    if state.app_handle.state::<Inbox>().receive(message.clone()) {
+        let _ = state.app_handle.emit(constants::HANDLER_MESSAGE, message.clone());
+        if message.bring_to_front {
+            window_manager::utility::request_attention(&state.app_handle);
+        }
+    }
+    let response = StationMessageResponse { status: true, text: "Message shown".to_string() };
+    (StatusCode::OK, Json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    responses((status = 200, description = "OpenAPI document of this API version"))
+)]
+pub async fn openapi_handler() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
@@ -0,0 +1,116 @@
+//! Version 2 of the station HTTP API, served under [`PREFIX`].
+//!
+//! `/register` also hands out a credential, which `/station/*`, used by the
+//! counter to manage the station, `/vouchers/redeem`, `/members/*` and
+//! `/reports/sales` require.
+//! `/addtime` takes an amount in centavos with its denomination and source
+//! instead of a coin count and answers with the time added and the promotions
+//! applied. `/vouchers/redeem`, `/members/*` and `/station/*` are new.
+//! The other routes are the same as in version 1.
+
+pub mod handler;
+pub mod models;
+
+use axum::Router;
+use axum::routing::{ get, post };
+use utoipa::OpenApi;
+
+use crate::http_server::handler::ServerState;
+use crate::http_server::v1;
+
+pub const PREFIX: &str = "/api/v2";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Centralized Pisonet Station API", version = "2"),
+    servers((url = "/api/v2")),
+    paths(
+        handler::register_handler,
+        handler::add_time_handler,
+        handler::redeem_voucher_handler,
+        handler::member_sign_in_handler,
+        handler::member_sign_out_handler,
+        handler::station_status_handler,
+        handler::adjust_time_handler,
+        handler::end_session_handler,
+        handler::station_message_handler,
+        v1::handler::sales_report_handler,
+        handler::openapi_handler
+    ),
+    components(
+        schemas(
+            v1::models::RegisterRequest,
+            models::RegisterResponse,
+            models::AddTimeRequest,
+            models::AddTimeResponse,
+            models::RedeemVoucherRequest,
+            models::RedeemVoucherResponse,
+            models::MemberSignInRequest,
+            models::MemberSignInResponse,
+            models::MemberSignOutRequest,
+            models::MemberSignOutResponse,
+            models::AdjustTimeRequest,
+            models::AdjustTimeResponse,
+            models::EndSessionRequest,
+            models::EndSessionResponse,
+            models::StationMessageRequest,
+            models::StationMessageResponse,
+            crate::payment::PaymentSource,
+            crate::promotion::AppliedPromotion,
+            crate::reports::summary::ReportRow,
+            crate::station::StationStatus
+        )
+    )
+)]
+pub struct ApiDoc;
+
+pub fn router() -> Router<ServerState> {
+    Router::new()
+        .route("/register", post(handler::register_handler))
+        .route("/addtime", post(handler::add_time_handler))
+        .route("/vouchers/redeem", post(handler::redeem_voucher_handler))
+        .route("/members/signin", post(handler::member_sign_in_handler))
+        .route("/members/signout", post(handler::member_sign_out_handler))
+        .route("/station", get(handler::station_status_handler))
+        .route("/station/adjust", post(handler::adjust_time_handler))
+        .route("/station/end", post(handler::end_session_handler))
+        .route("/station/message", post(handler::station_message_handler))
+        .route("/reports/sales", get(v1::handler::sales_report_handler))
+        .route("/openapi.json", get(handler::openapi_handler))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_documents_all_routes() {
+        let doc = ApiDoc::openapi();
+        let paths = [
+            "/register",
+            "/addtime",
+            "/vouchers/redeem",
+            "/members/signin",
+            "/members/signout",
+            "/station",
+            "/station/adjust",
+            "/station/end",
+            "/station/message",
+            "/reports/sales",
+            "/openapi.json",
+        ];
+        for path in paths {
+            assert!(doc.paths.paths.contains_key(path), "{} should be documented", path);
+        }
+    }
+
+    #[test]
+    fn test_openapi_documents_request_field_names() {
+        let json = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let add_time = &json["components"]["schemas"]["AddTimeRequest"]["properties"];
+        for field in ["amount_centavos", "denomination_centavos", "source"] {
+            assert!(add_time.get(field).is_some(), "{} should be documented", field);
+        }
+        assert!(add_time.get("credits").is_none());
+    }
+}
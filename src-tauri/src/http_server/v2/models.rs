@@ -0,0 +1,210 @@
+use serde::{ Deserialize, Serialize };
+use utoipa::ToSchema;
+
+use crate::member::MemberError;
+use crate::payment::{ Payment, PaymentSource };
+use crate::promotion::AppliedPromotion;
+use crate::voucher::VoucherError;
+
+/// Pairing result. A paired client sends `credential` back as
+/// `Authorization: Bearer <credential>` to the routes that need it.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct RegisterResponse {
+    /// `true` when the pairing was accepted.
+    pub status: bool,
+    /// Hardware id of this station.
+    pub server_hwid: String,
+    /// Address and port of this station.
+    pub server_address: String,
+    /// SHA-256 fingerprint of the station TLS certificate, empty when TLS is
+    /// disabled. Clients pin it and refuse any other certificate afterwards.
+    #[serde(default)]
+    pub server_cert_fingerprint: String,
+    /// Secret of this pairing, empty when refused.
+    #[serde(default)]
+    pub credential: String,
+    /// Human readable result.
+    pub text: String,
+}
+
+/// Money paid on the client device.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct AddTimeRequest {
+    /// Amount paid in centavos, e.g. `5000` for ₱50.
+    #[schema(example = 5000)]
+    pub amount_centavos: u64,
+    /// Face value of one coin or bill in centavos. Required for coins and
+    /// bills, which must be one of the accepted denominations.
+    #[serde(default)]
+    #[schema(example = 5000)]
+    pub denomination_centavos: Option<u64>,
+    /// Where the money came from, `coin` or `bill`. Cashier and promo time
+    /// is refused.
+    pub source: PaymentSource,
+}
+
+impl From<AddTimeRequest> for Payment {
+    fn from(request: AddTimeRequest) -> Self {
+        Payment {
+            amount_centavos: request.amount_centavos,
+            denomination_centavos: request.denomination_centavos,
+            source: request.source,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, ToSchema)]
+pub struct AddTimeResponse {
+    /// `true` when the payment was accepted.
+    pub status: bool,
+    /// Time added, including the bonus time of `promotions`.
+    pub seconds: u64,
+    /// Promotions that added bonus time to this payment.
+    pub promotions: Vec<AppliedPromotion>,
+    /// Human readable result.
+    pub text: String,
+}
+
+/// Voucher typed on the lock screen of a paired station.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct RedeemVoucherRequest {
+    /// Code as printed, dashes and case do not matter.
+    #[schema(example = "ABCD-EFGH-JKMN")]
+    pub code: String,
+    /// Station the time is added to.
+    #[schema(example = "station-01")]
+    pub station: String,
+    /// Also ask the stations paired with this one when the voucher is not
+    /// from here, e.g. for time saved on another station.
+    #[serde(default)]
+    pub pass_on: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct RedeemVoucherResponse {
+    /// `true` when the voucher was redeemed.
+    pub status: bool,
+    /// Time value of the voucher, `0` when it was refused.
+    pub seconds: u64,
+    /// Why it was refused, as `{ "kind": ... }`.
+    #[schema(value_type = Option<Object>)]
+    pub error: Option<VoucherError>,
+    /// Human readable result.
+    pub text: String,
+}
+
+/// Member signing in on the lock screen of a paired station.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct MemberSignInRequest {
+    #[schema(example = "juan")]
+    pub username: String,
+    #[schema(example = "1234")]
+    pub pin: String,
+    /// Station the balance is handed to for the session.
+    #[schema(example = "station-01")]
+    pub station: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct MemberSignInResponse {
+    /// `true` when the member is signed in.
+    pub status: bool,
+    /// Balance handed to the station, `0` when refused.
+    pub seconds: u64,
+    /// Session token needed to sign out, empty when refused.
+    pub token: String,
+    /// Why it was refused, as `{ "kind": ... }`.
+    #[schema(value_type = Option<Object>)]
+    pub error: Option<MemberError>,
+    /// Human readable result.
+    pub text: String,
+}
+
+/// Member signing out of a paired station, giving back the time left.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct MemberSignOutRequest {
+    #[schema(example = "juan")]
+    pub username: String,
+    /// Token returned when signing in.
+    pub token: String,
+    /// Time left on the station, added back to the balance.
+    #[schema(example = 1800)]
+    pub seconds: u64,
+    /// Station the member signs out of.
+    #[schema(example = "station-01")]
+    pub station: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct MemberSignOutResponse {
+    /// `true` when the member is signed out.
+    pub status: bool,
+    /// Balance after the time left was added back.
+    pub balance_seconds: u64,
+    /// Why it was refused, as `{ "kind": ... }`.
+    #[schema(value_type = Option<Object>)]
+    pub error: Option<MemberError>,
+    /// Human readable result.
+    pub text: String,
+}
+
+/// Time added or removed from the counter.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct AdjustTimeRequest {
+    /// Seconds to add, negative to remove.
+    #[schema(example = 900)]
+    pub seconds: i64,
+    /// Recorded in the audit log of the station.
+    #[schema(example = "Coin jammed")]
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct AdjustTimeResponse {
+    /// `true` when the time was changed.
+    pub status: bool,
+    /// Time left on the station after the change.
+    #[schema(example = 2700)]
+    pub remaining_seconds: u64,
+    /// Human readable result.
+    pub text: String,
+}
+
+/// Session ended from the counter. A signed in member keeps the time left.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct EndSessionRequest {
+    /// Recorded in the audit log of the station.
+    #[schema(example = "Closing time")]
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct EndSessionResponse {
+    /// `true` when the station is locked.
+    pub status: bool,
+    /// Time taken off the station, given back to a signed in member.
+    pub seconds: u64,
+    /// Human readable result.
+    pub text: String,
+}
+
+/// Text from the counter, shown on the station until acknowledged.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct StationMessageRequest {
+    /// Chosen by the counter, a message sent twice is shown once.
+    #[schema(example = "q8ZlT0c3mJ1v")]
+    pub id: String,
+    #[schema(example = "We are closing in 15 minutes.")]
+    pub text: String,
+    /// Also bring the station window to the front and flash it in the taskbar.
+    #[serde(default)]
+    pub bring_to_front: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct StationMessageResponse {
+    /// `true` when the message is shown on the station.
+    pub status: bool,
+    /// Human readable result.
+    pub text: String,
+}
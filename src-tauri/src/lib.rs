@@ -1,35 +1,94 @@
 use std::sync::atomic::{ AtomicU64, Ordering };
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{ Duration, Instant };
 
 use anyhow::Result;
-use http_server::handler::start_server;
-use settings::appconfigmodels::License;
+use audit::event::{ AuditEvent, ADMIN_ACTOR };
+use audit::store::{ AuditEntry, AuditLog, AuditQuery, AuditVerification };
+use chrono::{ DateTime, Local, Utc };
+use counter::{ CounterSales, StationOverview };
+use db::members::Member;
+use db::vouchers::{ NewVoucherBatch, VoucherBatch };
+use db::{ Database, DatabaseState };
+use http_server::handler::{
+    CreditReceiver,
+    CreditSender,
+    ServerController,
+    ServerOptions,
+    ServerStatus,
+};
+use http_server::metrics::Metrics;
+use http_server::v1::models::RegisterRequest;
+use licensing::activation::{
+    ActivationControl,
+    ActivationResult,
+    ActivationStep,
+    ACTIVATION_TIMEOUT,
+};
+use licensing::backend::LicenseBackendState;
+use licensing::fingerprint::DeviceFingerprint;
+use licensing::supabase::SupabaseBackend;
+use licensing::tier::{ tier_of, Feature, LicenseTier, LimitReached };
+use member::{ MemberError, MemberSlot };
+use message::{ Inbox, Outbox, SentMessage, StationMessage };
+use pairing::PairingCode;
+use payment::{ Payment, PaymentSource };
+use promotion::{ Credit, PromotionContext, SessionSpend };
+use reports::ledger::{ SalesEvent, SalesLedger };
+use reports::summary::{ Granularity, ReportRow };
+use reports::ReportFormat;
+use settings::appconfigmodels::{ Acceptor, License, Pricing, Promotion, RateLimit, Role };
 use settings::uuidmodel::UniqueId;
 use tauri::menu::{ Menu, MenuItem };
 use tauri::{ AppHandle, Emitter, Manager };
 use tauri::tray::TrayIconBuilder;
 use tokio::sync::mpsc;
 use tokio::time::sleep;
+use tracing::{ debug, error, info, trace, warn };
+use voucher::SavedTime;
 
+mod audit;
+mod acceptor;
+mod counter;
+mod db;
 mod http_server;
 mod window_manager;
 mod settings;
 mod licensing;
+mod reports;
 mod constants;
+mod logging;
+mod member;
+mod message;
+mod pairing;
+mod payment;
+mod promotion;
+mod station;
+mod voucher;
 
 type AppConfigState = std::sync::Mutex<settings::appconfigmodels::AppConfig>;
+type RemainingTime = Arc<AtomicU64>;
+type MemberSessionState = std::sync::Mutex<MemberSlot>;
+type Promotions = Vec<Promotion>;
+
+const MAX_VOUCHERS_PER_BATCH: u32 = 1000;
+// A station that stops mid session gives back at most this much more time
+// than it had left.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
 
 #[tauri::command]
 fn validate_password(
     password: &str,
     _state: tauri::State<AppConfigState>,
+    audit: tauri::State<AuditLog>,
     app_handle: tauri::AppHandle
 ) -> bool {
     if password == "123" {
+        audit.record(AuditEvent::AdminLogin);
         let _ = app_handle.emit(constants::HANDLER_SETTINGS_ROUTE, true);
         return true;
     }
+    audit.record(AuditEvent::AdminLoginFailed);
     false
 }
 
@@ -45,29 +104,914 @@ fn get_ui_config(
         .get_ui_config(device_name.as_str())
         .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
 
-    Ok(serde_json::to_value(ui_config).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?)
+    serde_json::to_value(ui_config).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))
+}
+
+#[tauri::command]
+async fn authorize(
+    serial_number: String,
+    email_address: String,
+    state: tauri::State<'_, AppConfigState>,
+    backend: tauri::State<'_, LicenseBackendState>,
+    activation: tauri::State<'_, ActivationControl>,
+    audit: tauri::State<'_, AuditLog>,
+    app_handle: tauri::AppHandle
+) -> Result<ActivationResult, tauri::ipc::InvokeError> {
+    info!(%serial_number, "Activating license");
+
+    let Some((_guard, cancelled)) = activation.begin() else {
+        return Ok(ActivationResult::InProgress);
+    };
+
+    let work = activate(&serial_number, &email_address, &state, backend.inner(), &app_handle);
+    let result = licensing::activation::run_with_deadline(work, cancelled, ACTIVATION_TIMEOUT).await;
+    info!(?result, "License activation finished");
+    if result == ActivationResult::Activated {
+        audit.record(AuditEvent::license_changed("activated", &serial_number));
+    }
+
+    Ok(result)
 }
 
 #[tauri::command]
-fn authorize(
+fn cancel_authorize(activation: tauri::State<ActivationControl>) -> bool {
+    activation.cancel()
+}
+
+async fn activate(
     serial_number: &str,
     email_address: &str,
-    _state: tauri::State<AppConfigState>
-) -> bool {
-    println!("Serial number: {}, email address: {}", serial_number, email_address);
+    state: &AppConfigState,
+    backend: &LicenseBackendState,
+    app_handle: &AppHandle
+) -> ActivationResult {
+    let progress = |step: ActivationStep| {
+        let _ = app_handle.emit(constants::HANDLER_ACTIVATION_PROGRESS, step);
+    };
+
+    progress(ActivationStep::ReadingHardware);
+    let fingerprint = tauri::async_runtime::spawn_blocking(DeviceFingerprint::this_device).await;
+    let device_id = match fingerprint {
+        Ok(Ok(fingerprint)) => fingerprint.encode(),
+        Ok(Err(e)) => {
+            return ActivationResult::Failed { message: e.to_string() };
+        }
+        Err(e) => {
+            return ActivationResult::Failed { message: e.to_string() };
+        }
+    };
+
+    progress(ActivationStep::ContactingServer);
+    let authorized = licensing::cloud_service::authorize(
+        backend.as_ref(),
+        serial_number,
+        email_address,
+        &device_id
+    ).await;
+    if let Err(e) = authorized {
+        return e.into();
+    }
+
+    // The signed token is what keeps the license valid offline
+    progress(ActivationStep::IssuingToken);
+    let token = match backend.issue_token(serial_number, &device_id).await {
+        Ok(token) => token,
+        Err(e) => {
+            return e.into();
+        }
+    };
+
+    //
+    // Persist the activated license and let the main window know
+    //
+    progress(ActivationStep::Saving);
+    let license = License {
+        authorized: true,
+        serial_number: serial_number.to_string(),
+        email_address: email_address.to_string(),
+        token,
+        last_validated_at: Some(chrono::Utc::now()),
+    };
+    if let Err(e) = save_license(state, license.clone()) {
+        return ActivationResult::Failed { message: e.to_string() };
+    }
+    let _ = app_handle.emit(constants::HANDLER_INITIALIZE_LICENSE, license.to_json());
+
+    ActivationResult::Activated
+}
+
+fn save_license(state: &AppConfigState, license: License) -> Result<()> {
+    let device = UniqueId::default()?;
+    let mut config = state.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    config.set_license(device.id.as_str(), license)?;
+    settings::appconfig::save(&config)
+}
+
+// Id of this station as shown on the UI, used for sales and sessions.
+fn station_id(state: &AppConfigState) -> Result<String> {
+    let device = UniqueId::default()?;
+    let config = state.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    Ok(config.get_ui_config(device.id.as_str())?.station_id)
+}
+
+#[tauri::command]
+async fn deactivate_license(
+    serial_number: String,
+    email_address: String,
+    state: tauri::State<'_, AppConfigState>,
+    backend: tauri::State<'_, LicenseBackendState>,
+    audit: tauri::State<'_, AuditLog>,
+    app_handle: tauri::AppHandle
+) -> Result<(), tauri::ipc::InvokeError> {
+    let device_id = DeviceFingerprint::this_device()
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?
+        .encode();
+
+    // Errors are `LicenseError`s and reach the UI as `{ kind, ... }`
+    licensing::cloud_service
+        ::deactivate(backend.as_ref(), &serial_number, &email_address, &device_id).await
+        .map_err(tauri::ipc::InvokeError::from)?;
+    audit.record(AuditEvent::license_changed("deactivated", &serial_number));
+
+    let license = License::default();
+    save_license(&state, license.clone()).map_err(|e|
+        tauri::ipc::InvokeError::from(e.to_string())
+    )?;
+    let _ = app_handle.emit(constants::HANDLER_INITIALIZE_LICENSE, license.to_json());
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn transfer_license(
+    serial_number: String,
+    email_address: String,
+    backend: tauri::State<'_, LicenseBackendState>,
+    audit: tauri::State<'_, AuditLog>
+) -> Result<(), tauri::ipc::InvokeError> {
+    let device_id = DeviceFingerprint::this_device()
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?
+        .encode();
+
+    licensing::cloud_service
+        ::transfer(backend.as_ref(), &serial_number, &email_address, &device_id).await
+        .map_err(tauri::ipc::InvokeError::from)?;
+    audit.record(AuditEvent::license_changed("transferred", &serial_number));
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn pair_station(
+    address: String,
+    pair_id: String,
+    state: tauri::State<'_, AppConfigState>,
+    audit: tauri::State<'_, AuditLog>,
+    database: tauri::State<'_, DatabaseState>,
+    app_handle: tauri::AppHandle
+) -> Result<(), tauri::ipc::InvokeError> {
+    let device = UniqueId::default().map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let own_address: String;
+    {
+        let config = state.lock().map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+        own_address = config
+            .get_ip_address(device.id.as_str())
+            .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    }
+
+    let request = RegisterRequest {
+        pair_id,
+        address: own_address,
+        hwid: device.id.clone(),
+    };
+    let client = http_server::client
+        ::pair_with_station(&address, &request).await
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+
+    let license = state
+        .lock()
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?
+        .get_license(device.id.as_str())
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let paired = database
+        .clients()
+        .all()
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    tier_of(&license)
+        .check_client_limit(&paired, &client.hwid)
+        .map_err(|limit| notify_limit(&app_handle, limit))?;
+
+    let details = format!("Paired station {} at {}", client.hwid, address);
+    database
+        .clients()
+        .upsert(&client)
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    audit.record(AuditEvent::ConfigChanged { section: "clients".to_string(), details });
+
+    Ok(())
+}
+
+#[tauri::command]
+fn get_server_status(server: tauri::State<Arc<ServerController>>) -> ServerStatus {
+    server.status()
+}
 
-    let async_result = tauri::async_runtime::block_on(async {
-        licensing::cloud_service::authorize(serial_number, email_address, "mockdeviceid").await
+#[tauri::command]
+async fn update_server_settings(
+    address: String,
+    port: u16,
+    tls: bool,
+    state: tauri::State<'_, AppConfigState>,
+    server: tauri::State<'_, Arc<ServerController>>,
+    audit: tauri::State<'_, AuditLog>,
+    app_handle: tauri::AppHandle
+) -> Result<ServerStatus, tauri::ipc::InvokeError> {
+    let device = UniqueId::default().map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let rate_limit: RateLimit;
+    let tier: LicenseTier;
+    {
+        let config = state.lock().map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+        rate_limit = config
+            .get_rate_limit(device.id.as_str())
+            .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+        tier = tier_of(
+            &config
+                .get_license(device.id.as_str())
+                .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?
+        );
+    }
+    if tls {
+        tier.check_feature(Feature::Tls).map_err(|limit| notify_limit(&app_handle, limit))?;
+    }
+
+    let options = ServerOptions {
+        ip_address: address.clone(),
+        port,
+        use_tls: tls,
+        rate_limit,
+        metrics: tier.allows(Feature::Metrics),
+    };
+    server
+        .restart(options).await
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))?;
+
+    // Only persist settings the server could actually bind to
+    let mut config = state.lock().map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    config
+        .set_server_network(device.id.as_str(), &address, port, tls)
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    settings::appconfig::save(&config).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    audit.record(AuditEvent::ConfigChanged {
+        section: "server".to_string(),
+        details: format!("{}:{} tls={}", address, port, tls),
     });
 
-    if let Ok(result) = async_result {
-        println!("Authorization result: {:?}", result);
-        return result;
-    } else {
-        println!("Authorization error encountered!");
+    Ok(server.status())
+}
+
+#[tauri::command]
+fn get_license_tier(
+    state: tauri::State<AppConfigState>
+) -> Result<LicenseTier, tauri::ipc::InvokeError> {
+    let device = UniqueId::default().map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let config = state.lock().map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let license = config
+        .get_license(device.id.as_str())
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    Ok(tier_of(&license))
+}
+
+// Adds (or with a negative value removes) time on this station by hand.
+// Returns the remaining time in seconds.
+#[tauri::command]
+fn adjust_time(seconds: i64, reason: String, app_handle: tauri::AppHandle) -> u64 {
+    station::adjust_time(&app_handle, seconds, reason, ADMIN_ACTOR.to_string())
+}
+
+// Paired stations with their time left, for the counter window.
+#[tauri::command]
+async fn list_stations(
+    role: tauri::State<'_, Role>,
+    database: tauri::State<'_, DatabaseState>,
+    outbox: tauri::State<'_, Outbox>
+) -> Result<Vec<StationOverview>, tauri::ipc::InvokeError> {
+    counter::ensure_counter(*role).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let stations = counter
+        ::overview(&database).await
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))?;
+    outbox.update(&stations);
+    Ok(stations)
+}
+
+// Adds (or with a negative value removes) time on a paired station from the
+// counter. Returns the time left on it.
+#[tauri::command]
+async fn adjust_station_time(
+    hwid: String,
+    seconds: i64,
+    reason: String,
+    role: tauri::State<'_, Role>,
+    database: tauri::State<'_, DatabaseState>,
+    audit: tauri::State<'_, AuditLog>
+) -> Result<u64, tauri::ipc::InvokeError> {
+    counter::ensure_counter(*role).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let station = counter
+        ::find_station(&database, &hwid)
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let remaining = http_server::client
+        ::adjust_station_time(&database, &station, seconds, &reason).await
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))?;
+    audit.record(AuditEvent::CounterAdjustment { station: hwid, seconds, reason });
+    Ok(remaining)
+}
+
+// Locks a paired station from the counter. Returns the time taken off.
+#[tauri::command]
+async fn end_station_session(
+    hwid: String,
+    reason: String,
+    role: tauri::State<'_, Role>,
+    database: tauri::State<'_, DatabaseState>,
+    audit: tauri::State<'_, AuditLog>
+) -> Result<u64, tauri::ipc::InvokeError> {
+    counter::ensure_counter(*role).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let station = counter
+        ::find_station(&database, &hwid)
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let seconds = http_server::client
+        ::end_station_session(&database, &station, &reason).await
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))?;
+    audit.record(AuditEvent::CounterAdjustment {
+        station: hwid,
+        seconds: 0i64.saturating_sub_unsigned(seconds),
+        reason,
+    });
+    Ok(seconds)
+}
+
+// Shows a message on one paired station, or on all of them when `hwid` is
+// missing. `bring_to_front` also brings the station window to the front.
+#[tauri::command]
+async fn send_station_message(
+    hwid: Option<String>,
+    text: String,
+    bring_to_front: bool,
+    role: tauri::State<'_, Role>,
+    app_handle: tauri::AppHandle
+) -> Result<SentMessage, tauri::ipc::InvokeError> {
+    counter::ensure_counter(*role).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let message = StationMessage
+        ::new(&text, bring_to_front)
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    send_message(&app_handle, hwid.as_deref(), message).await.map_err(|e|
+        tauri::ipc::InvokeError::from(format!("{:#}", e))
+    )
+}
+
+// Tells every paired station the cafe closes in 15 minutes.
+#[tauri::command]
+async fn announce_closing(
+    role: tauri::State<'_, Role>,
+    app_handle: tauri::AppHandle
+) -> Result<SentMessage, tauri::ipc::InvokeError> {
+    counter::ensure_counter(*role).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let message = StationMessage
+        ::new(message::CLOSING_MESSAGE, true)
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    send_message(&app_handle, None, message).await.map_err(|e|
+        tauri::ipc::InvokeError::from(format!("{:#}", e))
+    )
+}
+
+// Messages sent from the counter with whether each station acknowledged
+// them, as of the last `list_stations`.
+#[tauri::command]
+fn list_sent_messages(
+    role: tauri::State<Role>,
+    outbox: tauri::State<Outbox>
+) -> Result<Vec<SentMessage>, tauri::ipc::InvokeError> {
+    counter::ensure_counter(*role).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    Ok(outbox.all())
+}
+
+async fn send_message(
+    app_handle: &AppHandle,
+    hwid: Option<&str>,
+    message: StationMessage
+) -> Result<SentMessage> {
+    let database = app_handle.state::<DatabaseState>().inner().clone();
+    let stations = counter::recipients(&database, hwid)?;
+    let text = message.text.clone();
+    let sent = message::send(database, stations, message).await;
+    app_handle.state::<AuditLog>().record(AuditEvent::MessageSent {
+        to: hwid.unwrap_or("all").to_string(),
+        text,
+    });
+    app_handle.state::<Outbox>().add(sent.clone());
+    Ok(sent)
+}
+
+// Code a counter enters to pair with this station, replaced once used.
+#[tauri::command]
+fn get_pairing_code(pairing: tauri::State<PairingCode>) -> String {
+    pairing.current()
+}
+
+// Messages from the counter shown on this station, oldest first.
+#[tauri::command]
+fn get_messages(inbox: tauri::State<Inbox>) -> Vec<StationMessage> {
+    inbox.pending()
+}
+
+// The customer read the message, the counter sees it on its next refresh.
+#[tauri::command]
+fn acknowledge_message(id: String, inbox: tauri::State<Inbox>) -> bool {
+    inbox.acknowledge(&id)
+}
+
+// Sales of the counter and of the paired stations it could reach.
+#[tauri::command]
+async fn get_counter_sales(
+    granularity: Granularity,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    role: tauri::State<'_, Role>,
+    database: tauri::State<'_, DatabaseState>
+) -> Result<CounterSales, tauri::ipc::InvokeError> {
+    counter::ensure_counter(*role).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    counter
+        ::sales(&database, granularity, from, to).await
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))
+}
+
+#[tauri::command]
+fn query_audit_log(
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    event_type: Option<String>,
+    audit: tauri::State<AuditLog>
+) -> Result<Vec<AuditEntry>, tauri::ipc::InvokeError> {
+    audit
+        .query(&AuditQuery { from, to, event_type })
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))
+}
+
+#[tauri::command]
+fn verify_audit_log(
+    audit: tauri::State<AuditLog>
+) -> Result<AuditVerification, tauri::ipc::InvokeError> {
+    audit.verify().map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))
+}
+
+// Generates `count` codes worth `minutes` each, valid for `valid_days`.
+#[tauri::command]
+fn create_voucher_batch(
+    count: u32,
+    minutes: u64,
+    valid_days: u32,
+    label: String,
+    database: tauri::State<DatabaseState>,
+    audit: tauri::State<AuditLog>
+) -> Result<VoucherBatch, tauri::ipc::InvokeError> {
+    if !(1..=MAX_VOUCHERS_PER_BATCH).contains(&count) || minutes == 0 || valid_days == 0 {
+        return Err(
+            tauri::ipc::InvokeError::from(
+                format!(
+                    "A batch needs 1 to {} codes, and minutes and valid days above zero",
+                    MAX_VOUCHERS_PER_BATCH
+                )
+            )
+        );
     }
 
-    false
+    let now = Utc::now();
+    let seconds = minutes
+        .checked_mul(60)
+        .ok_or_else(|| tauri::ipc::InvokeError::from("Too many minutes for a voucher"))?;
+    let expires_at = now
+        .checked_add_signed(chrono::Duration::days(valid_days.into()))
+        .ok_or_else(|| tauri::ipc::InvokeError::from("Too many valid days for a voucher"))?;
+    let batch = NewVoucherBatch { label: &label, created_at: now, seconds, expires_at };
+    let batch_id = voucher
+        ::generate_codes(count as usize)
+        .and_then(|codes| database.vouchers().create_batch(&batch, &codes))
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))?;
+    audit.record(AuditEvent::VoucherBatchCreated {
+        batch_id,
+        codes: count.into(),
+        seconds: batch.seconds,
+    });
+
+    database
+        .vouchers()
+        .batches()
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))?
+        .into_iter()
+        .find(|batch| batch.id == batch_id)
+        .ok_or_else(|| tauri::ipc::InvokeError::from("Voucher batch was not saved"))
+}
+
+#[tauri::command]
+fn list_voucher_batches(
+    database: tauri::State<DatabaseState>
+) -> Result<Vec<VoucherBatch>, tauri::ipc::InvokeError> {
+    database
+        .vouchers()
+        .batches()
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))
+}
+
+// Codes of a batch as CSV, for printing the voucher cards.
+#[tauri::command]
+fn export_voucher_batch(
+    batch_id: i64,
+    database: tauri::State<DatabaseState>
+) -> Result<String, tauri::ipc::InvokeError> {
+    let vouchers = database.vouchers();
+    let batch = vouchers
+        .batches()
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))?
+        .into_iter()
+        .find(|batch| batch.id == batch_id)
+        .ok_or_else(|| tauri::ipc::InvokeError::from("Voucher batch not found"))?;
+    let codes = vouchers
+        .codes(batch_id)
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))?;
+    Ok(voucher::batch_to_csv(&batch, &codes))
+}
+
+// Redeems a voucher typed on the lock screen and adds its time. Errors are
+// `VoucherError`s and reach the UI as `{ kind, ... }`.
+#[tauri::command]
+async fn redeem_voucher(
+    code: String,
+    state: tauri::State<'_, AppConfigState>,
+    database: tauri::State<'_, DatabaseState>,
+    remaining_time: tauri::State<'_, RemainingTime>,
+    audit: tauri::State<'_, AuditLog>,
+    app_handle: tauri::AppHandle
+) -> Result<u64, tauri::ipc::InvokeError> {
+    let station = station_id(&state).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+
+    let seconds = voucher
+        ::redeem(&database, &code, &station).await
+        .map_err(tauri::ipc::InvokeError::from)?;
+    info!(seconds, "Voucher redeemed");
+    remaining_time.fetch_add(seconds, Ordering::SeqCst);
+    audit.record(AuditEvent::VoucherRedeemed {
+        code: voucher::display(&voucher::normalize(&code).unwrap_or(code)),
+        station,
+        seconds,
+    });
+    window_manager::utility::show_small_window(&app_handle);
+
+    Ok(seconds)
+}
+
+#[tauri::command]
+fn create_member(
+    username: String,
+    pin: String,
+    database: tauri::State<DatabaseState>,
+    audit: tauri::State<AuditLog>
+) -> Result<(), tauri::ipc::InvokeError> {
+    member
+        ::validate_username(&username)
+        .and_then(|_| member::validate_pin(&pin))
+        .and_then(|_| member::Credentials::new(&pin))
+        .and_then(|credentials| database.members().create(&username, &credentials, Utc::now()))
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))?;
+    audit.record(AuditEvent::ConfigChanged {
+        section: "members".to_string(),
+        details: format!("Created member {}", username),
+    });
+    Ok(())
+}
+
+#[tauri::command]
+fn list_members(
+    database: tauri::State<DatabaseState>
+) -> Result<Vec<Member>, tauri::ipc::InvokeError> {
+    database
+        .members()
+        .all()
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))
+}
+
+#[tauri::command]
+fn reset_member_pin(
+    username: String,
+    pin: String,
+    database: tauri::State<DatabaseState>,
+    audit: tauri::State<AuditLog>
+) -> Result<(), tauri::ipc::InvokeError> {
+    let updated = member
+        ::validate_pin(&pin)
+        .and_then(|_| member::Credentials::new(&pin))
+        .and_then(|credentials| database.members().set_credentials(&username, &credentials))
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))?;
+    if !updated {
+        return Err(tauri::ipc::InvokeError::from(MemberError::NotFound.to_string()));
+    }
+    audit.record(AuditEvent::ConfigChanged {
+        section: "members".to_string(),
+        details: format!("Reset the PIN of member {}", username),
+    });
+    Ok(())
+}
+
+// Balance bought at the counter, priced like any other payment. Returns the
+// new balance in seconds.
+#[tauri::command]
+fn top_up_member(
+    username: String,
+    amount_centavos: u64,
+    pricing: tauri::State<Pricing>,
+    promotions: tauri::State<Promotions>,
+    database: tauri::State<DatabaseState>,
+    audit: tauri::State<AuditLog>,
+    app_handle: tauri::AppHandle
+) -> Result<u64, tauri::ipc::InvokeError> {
+    let payment = Payment {
+        amount_centavos,
+        denomination_centavos: None,
+        source: PaymentSource::Cashier,
+    };
+    payment
+        ::validate(&pricing, &payment)
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let base_seconds = payment::seconds_for(&pricing, &payment);
+    // Not part of a session, only member and time of day rules can apply
+    let context = PromotionContext {
+        now: Local::now(),
+        session_spend_centavos: 0,
+        first_payment_today: false,
+        member: true,
+    };
+    let credit = Credit::new(
+        base_seconds,
+        promotion::apply(&promotions, &payment, base_seconds, &context)
+    );
+    let balance = database
+        .members()
+        .top_up(&username, credit.seconds)
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))?
+        .map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+
+    audit.record(AuditEvent::MemberToppedUp {
+        username,
+        amount_centavos,
+        seconds: credit.seconds,
+    });
+    record_credit(&app_handle, payment, credit);
+    Ok(balance)
+}
+
+// Signs a member in from the lock screen and adds their balance to this
+// station. Coins inserted while signed in add to the time given back when
+// signing out. Errors are `MemberError`s and reach the UI as `{ kind, ... }`.
+#[tauri::command]
+async fn sign_in_member(
+    username: String,
+    pin: String,
+    state: tauri::State<'_, AppConfigState>,
+    database: tauri::State<'_, DatabaseState>,
+    session: tauri::State<'_, MemberSessionState>,
+    remaining_time: tauri::State<'_, RemainingTime>,
+    app_handle: tauri::AppHandle
+) -> Result<u64, tauri::ipc::InvokeError> {
+    let station = station_id(&state).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    {
+        let mut slot = session.lock().map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+        if !matches!(*slot, MemberSlot::Empty) {
+            return Err(tauri::ipc::InvokeError::from(MemberError::SignedIn { station }));
+        }
+        *slot = MemberSlot::SigningIn;
+    }
+
+    let signed_in = member::sign_in(&database, &username, &pin, &station).await;
+    let mut slot = session.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (signed_in, seconds) = match signed_in {
+        Ok(signed_in) => signed_in,
+        Err(e) => {
+            *slot = MemberSlot::Empty;
+            return Err(tauri::ipc::InvokeError::from(e));
+        }
+    };
+    info!(seconds, "Member signed in");
+    *slot = MemberSlot::SignedIn(signed_in);
+    drop(slot);
+    remaining_time.fetch_add(seconds, Ordering::SeqCst);
+    app_handle.state::<AuditLog>().record(AuditEvent::MemberSignedIn {
+        username: username.clone(),
+        station,
+        seconds,
+    });
+    let _ = app_handle.emit(constants::HANDLER_MEMBER, Some(username));
+    // With an empty balance the station stays locked until coins are inserted
+    if seconds > 0 {
+        window_manager::utility::show_small_window(&app_handle);
+    }
+
+    Ok(seconds)
+}
+
+// Gives the time left back to the member's balance, which locks the
+// station. Returns the new balance in seconds.
+#[tauri::command]
+async fn sign_out_member(app_handle: tauri::AppHandle) -> Result<u64, tauri::ipc::InvokeError> {
+    station
+        ::sign_out_member(&app_handle).await
+        .map(|(_, balance)| balance)
+        .map_err(tauri::ipc::InvokeError::from)
+}
+
+#[tauri::command]
+fn get_signed_in_member(
+    session: tauri::State<MemberSessionState>
+) -> Result<Option<String>, tauri::ipc::InvokeError> {
+    let slot = session.lock().map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    Ok(slot.session().map(|session| session.username.clone()))
+}
+
+// Takes the time left off this station as a code to resume on any station.
+// Members save their time by signing out instead.
+#[tauri::command]
+fn save_time(
+    state: tauri::State<AppConfigState>,
+    database: tauri::State<DatabaseState>,
+    session: tauri::State<MemberSessionState>,
+    remaining_time: tauri::State<RemainingTime>,
+    ledger: tauri::State<SalesLedger>,
+    audit: tauri::State<AuditLog>,
+    app_handle: tauri::AppHandle
+) -> Result<SavedTime, tauri::ipc::InvokeError> {
+    let station = station_id(&state).map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?;
+    let signed_in = !matches!(
+        *session.lock().map_err(|e| tauri::ipc::InvokeError::from(e.to_string()))?,
+        MemberSlot::Empty
+    );
+    if signed_in {
+        return Err(tauri::ipc::InvokeError::from("Sign out to save the time to your account"));
+    }
+
+    // Zeroed before the code exists so the time is never in both places
+    let seconds = ledger.take_time(&remaining_time);
+    if seconds == 0 {
+        return Err(tauri::ipc::InvokeError::from("No time left to save"));
+    }
+    let saved = match voucher::save_time(&database, &station, seconds, Utc::now()) {
+        Ok(saved) => saved,
+        Err(e) => {
+            ledger.give_back(&remaining_time, seconds);
+            return Err(tauri::ipc::InvokeError::from(format!("{:#}", e)));
+        }
+    };
+    info!(seconds, "Time saved");
+    audit.record(AuditEvent::TimeSaved { code: saved.code.clone(), station, seconds });
+    let _ = app_handle.emit(constants::HANDLER_TIME_SAVED, saved.clone());
+
+    Ok(saved)
+}
+
+// The time ran out: nothing to give back, but the account must be released
+// so the member can sign in elsewhere.
+async fn release_member(app_handle: &AppHandle) {
+    let signed_in = app_handle
+        .state::<MemberSessionState>()
+        .lock()
+        .ok()
+        .and_then(|mut session| session.take());
+    let Some(signed_in) = signed_in else {
+        return;
+    };
+
+    let station = station_id(&app_handle.state::<AppConfigState>()).unwrap_or_default();
+    let database = app_handle.state::<DatabaseState>();
+    match member::sign_out(&database, &signed_in, 0, &station).await {
+        Ok(_) => {
+            app_handle.state::<AuditLog>().record(AuditEvent::MemberSignedOut {
+                username: signed_in.username,
+                station,
+                seconds: 0,
+            });
+        }
+        Err(e) => {
+            error!(username = signed_in.username, "Failed to sign out member: {}", e);
+        }
+    }
+    let _ = app_handle.emit(constants::HANDLER_MEMBER, None::<String>);
+}
+
+#[tauri::command]
+fn get_sales_report(
+    granularity: Granularity,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    database: tauri::State<DatabaseState>
+) -> Result<Vec<ReportRow>, tauri::ipc::InvokeError> {
+    reports
+        ::sales_report(&database, granularity, from, to)
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))
+}
+
+// Same report as `get_sales_report` rendered as CSV or JSON text, ready to be
+// saved by the UI.
+#[tauri::command]
+fn export_sales_report(
+    granularity: Granularity,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    format: ReportFormat,
+    database: tauri::State<DatabaseState>
+) -> Result<String, tauri::ipc::InvokeError> {
+    reports
+        ::sales_report(&database, granularity, from, to)
+        .and_then(|rows| reports::render(&rows, format))
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))
+}
+
+// Copies the recent logs into a single file for support and returns its path.
+#[tauri::command]
+fn export_logs() -> Result<String, tauri::ipc::InvokeError> {
+    logging
+        ::export(&logging::log_dir(), logging::EXPORT_MAX_BYTES)
+        .map(|path| path.display().to_string())
+        .map_err(|e| tauri::ipc::InvokeError::from(format!("{:#}", e)))
+}
+
+// What the promotion rules look at when `payment` is turned into time.
+fn promotion_context(app_handle: &AppHandle, payment: &Payment) -> PromotionContext {
+    let now = Local::now();
+    let first_payment_today = match app_handle.state::<SalesLedger>().last_payment_at() {
+        Ok(last) => {
+            last.is_none_or(|last| last.with_timezone(&Local).date_naive() < now.date_naive())
+        }
+        Err(e) => {
+            error!("Failed to read the last payment: {:#}", e);
+            false
+        }
+    };
+    PromotionContext {
+        now,
+        session_spend_centavos: app_handle.state::<SessionSpend>().add(payment),
+        first_payment_today,
+        member: app_handle
+            .state::<MemberSessionState>()
+            .lock()
+            .is_ok_and(|slot| slot.session().is_some()),
+    }
+}
+
+// Records a payment turned into time in the ledger, the audit log and the
+// metrics.
+fn record_credit(app_handle: &AppHandle, payment: Payment, credit: Credit) {
+    let audit = app_handle.state::<AuditLog>();
+    for applied in &credit.promotions {
+        audit.record(AuditEvent::PromotionApplied {
+            name: applied.name.clone(),
+            bonus_seconds: applied.bonus_seconds,
+        });
+    }
+    app_handle.state::<Arc<Metrics>>().record_payment(payment.amount_centavos, credit.seconds);
+    app_handle.state::<SalesLedger>().record(SalesEvent::Payment {
+        payment,
+        seconds: credit.seconds,
+        promotions: credit.promotions,
+    });
+}
+
+// A session the station never ended means it stopped mid session. Its time
+// left is saved as a code the counter can hand to the customer.
+fn recover_time(ledger: &SalesLedger, database: &Database, station: &str, audit: &AuditLog) {
+    let saved = ledger
+        .recover()
+        .and_then(|seconds| {
+            if seconds == 0 {
+                return Ok(None);
+            }
+            voucher::save_time(database, station, seconds, Utc::now()).map(Some)
+        });
+    match saved {
+        Ok(Some(saved)) => {
+            warn!(seconds = saved.seconds, "Recovered time of an unfinished session");
+            audit.record(AuditEvent::TimeRecovered {
+                code: saved.code,
+                station: station.to_string(),
+                seconds: saved.seconds,
+            });
+        }
+        Ok(None) => {}
+        Err(e) => {
+            error!("Failed to recover unfinished sessions: {:#}", e);
+        }
+    }
+}
+
+fn notify_limit(app_handle: &AppHandle, limit: LimitReached) -> tauri::ipc::InvokeError {
+    warn!(feature = ?limit.feature, "License limit reached: {}", limit);
+    let _ = app_handle.emit(constants::HANDLER_LICENSE_LIMIT, limit.clone());
+    tauri::ipc::InvokeError::from(limit.to_string())
 }
 
 fn create_system_tray(app: &AppHandle) -> Result<()> {
@@ -86,22 +1030,22 @@ fn create_system_tray(app: &AppHandle) -> Result<()> {
         .on_menu_event(|app, event| {
             match event.id.as_ref() {
                 "quit" => {
-                    println!("quit menu item was clicked");
+                    debug!("Quit menu item was clicked");
                     app.exit(0);
                 }
                 "show_small" => {
-                    println!("show small was clicked");
+                    debug!("Show small menu item was clicked");
                     window_manager::utility::show_small_window(app);
                     if let Some(small_window) = app.get_webview_window("small") {
                         small_window.show().unwrap();
                     }
                 }
                 "show_main" => {
-                    println!("show main was clicked");
+                    debug!("Show main menu item was clicked");
                     window_manager::utility::show_main_window(app);
                 }
                 _ => {
-                    println!("menu item {:?} not handled", event.id);
+                    warn!(id = ?event.id, "Menu item not handled");
                 }
             }
         })
@@ -115,10 +1059,19 @@ fn create_system_tray(app: &AppHandle) -> Result<()> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // Flushes the log file when the app exits
+    let _log_guard = match logging::init() {
+        Ok(guard) => Some(guard),
+        Err(e) => {
+            eprintln!("Failed to initialize logging: {:#}", e);
+            None
+        }
+    };
+
     let app_config = match settings::appconfig::initialize() {
         Ok(config) => config,
         Err(e) => {
-            eprintln!("Failed to initialize appconfig: {}", e);
+            error!("Failed to initialize appconfig: {:#}", e);
             return;
         }
     };
@@ -134,12 +1087,27 @@ pub fn run() {
             //
             // Create the System Tray
             //
-            let _ = create_system_tray(&app_handle)?;
+            create_system_tray(&app_handle)?;
 
-            let (tx, mut rx): (
-                mpsc::Sender<(u64, AppHandle)>,
-                mpsc::Receiver<(u64, AppHandle)>,
-            ) = mpsc::channel(32);
+            //
+            // Open the database and the audit log before anything can record
+            // to them
+            //
+            let config_dir = settings::appconfig::config_dir();
+            let database: DatabaseState = Arc::new(Database::open(&config_dir.join("cps.db"))?);
+            let audit_file = config_dir.join("audit.jsonl");
+            if let Err(e) = db::legacy::import_audit_log(&database, &audit_file) {
+                error!("Failed to import the audit log: {:#}", e);
+            }
+            let sales_file = config_dir.join("sales.jsonl");
+            if let Err(e) = db::legacy::import_sales_ledger(&database, &sales_file) {
+                error!("Failed to import the sales ledger: {:#}", e);
+            }
+            app.manage(database.clone());
+            let audit_key = audit::store::load_key(&config_dir.join("audit.key"))?;
+            app.manage(AuditLog::open(database.clone(), audit_key)?);
+
+            let (tx, mut rx): (CreditSender, CreditReceiver) = mpsc::channel(32);
 
             //
             // Get the application config
@@ -148,22 +1116,88 @@ pub fn run() {
             let device_name = device.id;
             let config = app.handle().state::<AppConfigState>();
 
-            let ip: String;
-            let port: u16;
+            let options: ServerOptions;
             let license: License;
+            let acceptors: Vec<Acceptor>;
+            let pricing: Pricing;
+            let promotions: Promotions;
+            let role: Role;
             {
-                let application_config = config.lock().unwrap();
-
-                // Get the ip and port from application config
-                ip = application_config.get_ip_address(device_name.as_str())?;
-                port = application_config.get_port(device_name.as_str())?.parse()?;
+                let mut application_config = config.lock().unwrap();
 
                 // Get the License information from application config
-                // and emit it to the main window
-                license = application_config.get_license(device_name.as_str())?;
-                println!("License: {:?}", license);
+                // and emit it to the main window. Only a valid signed token
+                // authorizes it, whatever the stored flag says.
+                let mut stored_license = application_config.get_license(device_name.as_str())?;
+                let device_id = DeviceFingerprint::this_device()
+                    .map(|fingerprint| fingerprint.encode())
+                    .unwrap_or_default();
+                stored_license.authorized = licensing::renewal::is_authorized(
+                    &stored_license,
+                    &device_id,
+                    chrono::Utc::now()
+                );
+                application_config.set_license(device_name.as_str(), stored_license.clone())?;
+                license = stored_license;
+
+                // Sales are recorded under the station id shown on the UI
+                let station = application_config.get_ui_config(device_name.as_str())?.station_id;
+                let ledger = SalesLedger::new(database.clone(), &station);
+                recover_time(&ledger, &database, &station, &app.state::<AuditLog>());
+                app.manage(ledger);
+
+                // Paired stations used to be kept in the config
+                let clients = application_config.take_clients(device_name.as_str())?;
+                if !clients.is_empty() {
+                    db::legacy::import_clients(&database, clients)?;
+                    settings::appconfig::save(&application_config)?;
+                }
+                info!(authorized = license.authorized, "License loaded");
+
+                // Get the server settings from application config, limited
+                // to what the license tier allows
+                let tier = tier_of(&license);
+                let use_tls = application_config.is_tls_enabled(device_name.as_str())?;
+                if use_tls && !tier.allows(Feature::Tls) {
+                    warn!("TLS is not available on this license, serving plain HTTP");
+                }
+                options = ServerOptions {
+                    ip_address: application_config.get_ip_address(device_name.as_str())?,
+                    port: application_config.get_port(device_name.as_str())?.parse()?,
+                    use_tls: use_tls && tier.allows(Feature::Tls),
+                    rate_limit: application_config.get_rate_limit(device_name.as_str())?,
+                    metrics: tier.allows(Feature::Metrics),
+                };
+                acceptors = application_config.get_acceptors(device_name.as_str())?;
+                pricing = application_config.get_pricing(device_name.as_str())?;
+                promotions = application_config.get_promotions(device_name.as_str())?;
+                role = application_config.get_role(device_name.as_str())?;
             }
 
+            //
+            // The counter manages the stations from its own window instead of
+            // showing the lock screen
+            //
+            app.manage(role);
+            if role == Role::Counter {
+                info!("Starting as the counter");
+                window_manager::utility::show_counter_window(app.handle())?;
+            }
+
+            //
+            // Threads to read the coin and bill acceptors wired to this PC
+            //
+            app.manage(pricing.clone());
+            app.manage(promotions.clone());
+            for config in acceptors.into_iter().filter(|config| config.enabled) {
+                acceptor::start(config, pricing.clone(), tx.clone(), app.handle().clone());
+            }
+
+            //
+            // Thread to renew the license token while online
+            //
+            tauri::async_runtime::spawn(licensing::renewal::run(app.handle().clone()));
+
             //
             // Thread to  send initialization to main window
             //
@@ -173,33 +1207,67 @@ pub fn run() {
                 let _ = emit_handle.emit(constants::HANDLER_INITIALIZE_LICENSE, license.to_json());
             });
 
+            let metrics = Arc::new(Metrics::default());
+            app.manage(metrics.clone());
+            app.manage(PairingCode::new()?);
+
             //
             // Thread to start the server
             //
+            let server = Arc::new(ServerController::new(app_handle, tx, metrics.clone()));
+            app.manage(server.clone());
             tauri::async_runtime::spawn(async move {
-                let _ = start_server(app_handle.clone(), tx, ip, port).await;
+                if let Err(e) = server.start(options).await {
+                    error!("Failed to start server: {:#}", e);
+                }
             });
 
-            let remaining_time = Arc::new(AtomicU64::new(0));
+            let remaining_time: RemainingTime = Arc::new(AtomicU64::new(0));
             let remaining_time_countdown = Arc::clone(&remaining_time);
+            app.manage(remaining_time.clone());
 
             //
             // Thread to update ui with the remaining time
             //
             let countdown_app_handle = app.handle().clone();
+            let countdown_metrics = metrics.clone();
             tauri::async_runtime::spawn(async move {
                 let mut last_value = remaining_time_countdown.load(Ordering::SeqCst);
+                let mut checkpoint_at = Instant::now();
                 loop {
                     let current = remaining_time_countdown.load(Ordering::SeqCst);
                     if current != last_value {
+                        if current > 0 && last_value == 0 {
+                            countdown_metrics.session_started();
+                            countdown_app_handle
+                                .state::<SalesLedger>()
+                                .record(SalesEvent::SessionStarted);
+                        }
+
                         if current == 0 && last_value > 0 {
+                            countdown_metrics.session_ended();
+                            countdown_app_handle.state::<SessionSpend>().reset();
+                            countdown_app_handle
+                                .state::<SalesLedger>()
+                                .record(SalesEvent::SessionEnded);
+                            countdown_app_handle
+                                .state::<AuditLog>()
+                                .record(AuditEvent::SessionEnded);
                             let _ = countdown_app_handle.emit(
                                 constants::HANDLER_TIMER_DONE,
                                 "Timer completed!"
                             );
                             window_manager::utility::show_main_window(&countdown_app_handle);
+                            release_member(&countdown_app_handle).await;
                         } else if current > 0 {
-                            println!("Remaining time: {}", current);
+                            // Time added is written at once, the countdown now
+                            // and then
+                            let added = current > last_value;
+                            if added || checkpoint_at.elapsed() >= CHECKPOINT_INTERVAL {
+                                countdown_app_handle.state::<SalesLedger>().checkpoint(current);
+                                checkpoint_at = Instant::now();
+                            }
+                            trace!(remaining = current, "Timer update");
                             let _ = countdown_app_handle.emit(
                                 constants::HANDLER_TIMER_UPDATE,
                                 current
@@ -208,9 +1276,12 @@ pub fn run() {
                         last_value = current;
                     }
 
-                    if current > 0 {
-                        remaining_time_countdown.fetch_sub(1, Ordering::SeqCst);
-                    }
+                    // Time taken meanwhile must not make it wrap around
+                    let _ = remaining_time_countdown.fetch_update(
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                        |remaining| remaining.checked_sub(1)
+                    );
 
                     sleep(Duration::from_secs(1)).await;
                 }
@@ -220,10 +1291,26 @@ pub fn run() {
             // Thread to receive timer events from the workers
             //
             tauri::async_runtime::spawn(async move {
-                while let Some((total_time, app_handle)) = rx.recv().await {
-                    println!("Received total time: {}", total_time);
-                    remaining_time.fetch_add(total_time * 5, Ordering::SeqCst);
-                    let _ = app_handle.emit(constants::HANDLER_ADDTIME, total_time);
+                while let Some((payment, app_handle, reply)) = rx.recv().await {
+                    info!(?payment, "Payment received");
+                    let base_seconds = payment::seconds_for(&pricing, &payment);
+                    let context = promotion_context(&app_handle, &payment);
+                    let credit = Credit::new(
+                        base_seconds,
+                        promotion::apply(&promotions, &payment, base_seconds, &context)
+                    );
+                    remaining_time.fetch_add(credit.seconds, Ordering::SeqCst);
+                    app_handle.state::<AuditLog>().record(AuditEvent::PaymentReceived {
+                        amount_centavos: payment.amount_centavos,
+                        denomination_centavos: payment.denomination_centavos,
+                        source: payment.source,
+                        seconds: credit.seconds,
+                    });
+                    record_credit(&app_handle, payment, credit.clone());
+                    let _ = app_handle.emit(constants::HANDLER_ADDTIME, payment.pesos());
+                    if let Some(reply) = reply {
+                        let _ = reply.send(credit);
+                    }
 
                     // Transition window to small when coin is inserted
                     window_manager::utility::show_small_window(&app_handle);
@@ -236,8 +1323,53 @@ pub fn run() {
             Ok(())
         })
         .manage(std::sync::Mutex::new(app_config))
+        .manage(Arc::new(SupabaseBackend::from_env()) as LicenseBackendState)
+        .manage(ActivationControl::default())
+        .manage(MemberSessionState::default())
+        .manage(SessionSpend::default())
+        .manage(Inbox::default())
+        .manage(Outbox::default())
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![authorize, validate_password, get_ui_config])
+        .invoke_handler(tauri::generate_handler![
+                authorize,
+                cancel_authorize,
+                validate_password,
+                get_ui_config,
+                pair_station,
+                get_pairing_code,
+                get_server_status,
+                update_server_settings,
+                deactivate_license,
+                transfer_license,
+                get_license_tier,
+                export_logs,
+                adjust_time,
+                query_audit_log,
+                verify_audit_log,
+                get_sales_report,
+                export_sales_report,
+                create_voucher_batch,
+                list_voucher_batches,
+                export_voucher_batch,
+                redeem_voucher,
+                create_member,
+                list_members,
+                reset_member_pin,
+                top_up_member,
+                sign_in_member,
+                sign_out_member,
+                get_signed_in_member,
+                save_time,
+                list_stations,
+                adjust_station_time,
+                end_station_session,
+                get_counter_sales,
+                send_station_message,
+                announce_closing,
+                list_sent_messages,
+                get_messages,
+                acknowledge_message
+            ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,150 @@
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::oneshot;
+
+use super::error::LicenseError;
+
+pub const ACTIVATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Result of the `authorize` command as seen by the UI.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ActivationResult {
+    Activated,
+    // Refused by the license server or the server could not be reached.
+    Rejected {
+        error: LicenseError,
+    },
+    // Local failure, e.g. the hardware could not be read.
+    Failed {
+        message: String,
+    },
+    TimedOut,
+    Cancelled,
+    // Another activation is still running.
+    InProgress,
+}
+
+impl From<LicenseError> for ActivationResult {
+    fn from(error: LicenseError) -> Self {
+        ActivationResult::Rejected { error }
+    }
+}
+
+// Reported through `HANDLER_ACTIVATION_PROGRESS` while an activation runs.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivationStep {
+    ReadingHardware,
+    ContactingServer,
+    IssuingToken,
+    Saving,
+}
+
+// Allows a single activation at a time and lets the UI cancel it.
+#[derive(Default)]
+pub struct ActivationControl {
+    cancel: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+pub struct ActivationGuard<'a> {
+    control: &'a ActivationControl,
+}
+
+impl Drop for ActivationGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut cancel) = self.control.cancel.lock() {
+            cancel.take();
+        }
+    }
+}
+
+impl ActivationControl {
+    // `None` while another activation is running. The activation ends when
+    // the returned guard is dropped.
+    pub fn begin(&self) -> Option<(ActivationGuard<'_>, oneshot::Receiver<()>)> {
+        let mut cancel = self.cancel.lock().ok()?;
+        if cancel.is_some() {
+            return None;
+        }
+
+        let (tx, rx) = oneshot::channel();
+        *cancel = Some(tx);
+        Some((ActivationGuard { control: self }, rx))
+    }
+
+    pub fn cancel(&self) -> bool {
+        match self.cancel.lock().ok().and_then(|mut cancel| cancel.take()) {
+            Some(tx) => tx.send(()).is_ok(),
+            None => false,
+        }
+    }
+}
+
+// Runs `work` until it finishes, `timeout` passes or the activation is
+// cancelled, whichever comes first.
+pub async fn run_with_deadline<F>(
+    work: F,
+    cancelled: oneshot::Receiver<()>,
+    timeout: Duration
+) -> ActivationResult
+    where F: Future<Output = ActivationResult>
+{
+    tokio::select! {
+        result = tokio::time::timeout(timeout, work) => {
+            result.unwrap_or(ActivationResult::TimedOut)
+        }
+        Ok(()) = cancelled => ActivationResult::Cancelled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_only_one_activation_at_a_time() {
+        let control = ActivationControl::default();
+        let (guard, _cancelled) = control.begin().unwrap();
+        assert!(control.begin().is_none());
+        drop(guard);
+        assert!(control.begin().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_activation_times_out() {
+        let control = ActivationControl::default();
+        let (_guard, cancelled) = control.begin().unwrap();
+        let result = run_with_deadline(
+            std::future::pending(),
+            cancelled,
+            Duration::from_millis(20)
+        ).await;
+        assert_eq!(result, ActivationResult::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn test_activation_can_be_cancelled() {
+        let control = ActivationControl::default();
+        let (_guard, cancelled) = control.begin().unwrap();
+        assert!(control.cancel());
+        let result = run_with_deadline(std::future::pending(), cancelled, ACTIVATION_TIMEOUT).await;
+        assert_eq!(result, ActivationResult::Cancelled);
+        assert!(!control.cancel());
+    }
+
+    #[tokio::test]
+    async fn test_finished_work_is_returned() {
+        let control = ActivationControl::default();
+        let (_guard, cancelled) = control.begin().unwrap();
+        let result = run_with_deadline(
+            async { LicenseError::OwnerMismatch.into() },
+            cancelled,
+            ACTIVATION_TIMEOUT
+        ).await;
+        assert_eq!(result, ActivationResult::Rejected { error: LicenseError::OwnerMismatch });
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{ DateTime, Utc };
+use serde::{ Deserialize, Serialize };
+
+use super::error::LicenseError;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SerialNumbersTable {
+    pub serial_number: String,
+    pub device_id: Option<String>,
+    pub active: bool,
+    #[serde(default)]
+    pub transferred_at: Option<DateTime<Utc>>,
+    // Set by us to shut a station off, e.g. when the cafe stopped paying.
+    #[serde(default)]
+    pub revoked: bool,
+    pub users: User,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct User {
+    pub email: String,
+}
+
+// Storage for serial numbers. The authorization rules in `cloud_service` only
+// talk to this trait so they can run against Supabase in the app and against
+// a local fake in tests.
+#[async_trait]
+pub trait LicenseBackend: Send + Sync {
+    // `None` when the serial number does not exist.
+    async fn fetch_serial_number(
+        &self,
+        serial_number: &str
+    ) -> Result<Option<SerialNumbersTable>, LicenseError>;
+
+    // Marks an inactive serial number as active on `device_id`. Returns `false`
+    // when the row was no longer inactive, i.e. another device claimed it first.
+    async fn claim_serial_number(
+        &self,
+        serial_number: &str,
+        device_id: &str
+    ) -> Result<bool, LicenseError>;
+
+    async fn update_status(
+        &self,
+        serial_number: &str,
+        device_id: &str,
+        active: bool
+    ) -> Result<(), LicenseError>;
+
+    async fn release_serial_number(&self, serial_number: &str) -> Result<(), LicenseError>;
+
+    async fn transfer_serial_number(
+        &self,
+        serial_number: &str,
+        device_id: &str,
+        at: DateTime<Utc>
+    ) -> Result<(), LicenseError>;
+
+    // Asks the license server for a signed token proving that `serial_number`
+    // is active on `device_id`. See `licensing::token`.
+    async fn issue_token(
+        &self,
+        serial_number: &str,
+        device_id: &str
+    ) -> Result<String, LicenseError>;
+}
+
+pub type LicenseBackendState = Arc<dyn LicenseBackend>;
@@ -1,224 +1,386 @@
-use anyhow::{ Context, Ok, Result };
-use dotenv::{ dotenv, var };
-use serde_json::json;
-use supabase_rs::SupabaseClient;
-
-use serde::{ Deserialize, Serialize };
-
-#[derive(Serialize, Deserialize, Debug)]
-struct SerialNumbersTable {
-    serial_number: String,
-    device_id: Option<String>,
-    active: bool,
-    users: User,
-}
-#[derive(Serialize, Deserialize, Debug)]
-struct User {
-    email: String,
-}
+use chrono::{ DateTime, Duration, Utc };
+use tracing::{ debug, info };
 
-pub async fn authorize(serial_number: &str, email_address: &str, device_id: &str) -> Result<bool> {
-    let supabase_client = connect_supabase().await?;
+use super::backend::{ LicenseBackend, SerialNumbersTable };
+use super::error::LicenseError;
+use super::fingerprint::device_matches;
 
-    let serial_number_table = fetch_serial_number_table(
-        supabase_client.clone(),
-        serial_number
-    ).await?;
-    let is_authorized = authorization(serial_number_table, device_id, email_address);
+// Minimum time between two self-service transfers of the same serial number
+// to a new PC.
+const TRANSFER_COOLDOWN_DAYS: i64 = 30;
 
-    // TODO: update the table
-    // if is_authorized {
-    //     update_status(supabase_client, serial_number, device_id, true).await?;
-    // }
+pub async fn authorize(
+    backend: &dyn LicenseBackend,
+    serial_number: &str,
+    email_address: &str,
+    device_id: &str
+) -> Result<(), LicenseError> {
+    let serial_number_table = fetch_serial_number_table(backend, serial_number).await?;
+    authorization(&serial_number_table, device_id, email_address)?;
 
-    Ok(is_authorized)
+    if !serial_number_table.active {
+        // Claim the serial number for this device. The claim only succeeds
+        // while the row is still inactive, so two PCs activating the same
+        // serial at once cannot both win.
+        if !backend.claim_serial_number(serial_number, device_id).await? {
+            debug!(%serial_number, "Serial number was claimed by another device first");
+            return Err(LicenseError::DeviceMismatch);
+        }
+        info!(%serial_number, "Serial number claimed for this device");
+    } else if serial_number_table.device_id.as_deref() != Some(device_id) {
+        // Same PC with slightly different hardware, record the current
+        // fingerprint so later changes are compared against it.
+        info!(%serial_number, "Recording the new fingerprint of this device");
+        backend.update_status(serial_number, device_id, true).await?;
+    }
+
+    Ok(())
 }
 
-async fn connect_supabase() -> Result<SupabaseClient> {
-    dotenv().ok();
+// Releases the serial number from this PC so it can be activated elsewhere.
+pub async fn deactivate(
+    backend: &dyn LicenseBackend,
+    serial_number: &str,
+    email_address: &str,
+    device_id: &str
+) -> Result<(), LicenseError> {
+    let serial_number_table = fetch_serial_number_table(backend, serial_number).await?;
+    deactivation_allowed(&serial_number_table, device_id, email_address)?;
+    info!(%serial_number, "Releasing serial number");
+    backend.release_serial_number(serial_number).await
+}
 
-    let url = var("SUPABASE_URL").with_context(|| "SUPABASE_URL not set")?;
-    let anon_key = var("SUPABASE_KEY").with_context(|| "SUPABASE_KEY not set")?;
-    let supabase_client = SupabaseClient::new(url, anon_key)?;
-    Ok(supabase_client)
+// Moves an active serial number to this PC when the old one can no longer
+// deactivate it (e.g. after a motherboard replacement).
+pub async fn transfer(
+    backend: &dyn LicenseBackend,
+    serial_number: &str,
+    email_address: &str,
+    device_id: &str
+) -> Result<(), LicenseError> {
+    let serial_number_table = fetch_serial_number_table(backend, serial_number).await?;
+    let now = Utc::now();
+    transfer_allowed(&serial_number_table, email_address, now)?;
+    info!(%serial_number, "Transferring serial number to this device");
+    backend.transfer_serial_number(serial_number, device_id, now).await
 }
 
-async fn fetch_serial_number_table(
-    supabase_client: SupabaseClient,
-    serial_number: &str
-) -> Result<SerialNumbersTable> {
-    let get_active_status = supabase_client
-        .select("serial_numbers")
-        .columns(["*", "users(email)"].to_vec())
-        .eq("serial_number", serial_number)
-        .execute().await;
-
-    if get_active_status.is_err() {
-        println!("Error fetching active status: {:?}", get_active_status);
-        return Err(anyhow::anyhow!("Failed to fetch active status"));
-    }
-
-    let mut serial_numbers: Option<SerialNumbersTable> = None;
-    if get_active_status.is_ok() {
-        for response in get_active_status.iter() {
-            println!("Active status: {:?}", response);
-            if response.is_empty() {
-                println!("No active status found for the given serial number");
-                return Err(anyhow::anyhow!("No active status found for the given serial number"));
-            }
-
-            serial_numbers = Some(
-                serde_json
-                    ::from_value(response[0].clone())
-                    .with_context(|| "Failed to deserialize response into SerialNumbersTable")?
-            );
-        }
+// Checks that the serial number is still active on this PC, so a station
+// notices when it was deactivated or revoked on the license server.
+pub async fn revalidate(
+    backend: &dyn LicenseBackend,
+    serial_number: &str,
+    email_address: &str,
+    device_id: &str
+) -> Result<(), LicenseError> {
+    let serial_number_table = fetch_serial_number_table(backend, serial_number).await?;
+    authorization(&serial_number_table, device_id, email_address)?;
+    if !serial_number_table.active {
+        return Err(LicenseError::Revoked);
     }
+    Ok(())
+}
 
-    let table = match serial_numbers {
-        Some(sn) => sn,
-        None => {
-            println!("No serial number data found");
-            return Err(anyhow::anyhow!("Serial number data could not be retrieved"));
-        }
-    };
-
-    Ok(table)
+async fn fetch_serial_number_table(
+    backend: &dyn LicenseBackend,
+    serial_number: &str
+) -> Result<SerialNumbersTable, LicenseError> {
+    backend.fetch_serial_number(serial_number).await?.ok_or(LicenseError::NotFound)
 }
 
-async fn update_status(
-    supabase_client: SupabaseClient,
-    serial_number: &str,
+// Whether `email_address` may use the serial number on `device_id`. An
+// inactive serial number can be claimed by any PC of its owner.
+fn authorization(
+    serial_number_table: &SerialNumbersTable,
     device_id: &str,
-    active: bool
-) -> Result<()> {
-    let update_result = supabase_client.update_with_column_name(
-        "serial_numbers",
-        "serial_number",
-        serial_number,
-        json!({
-           "active": active,
-           "device_id": device_id
-         })
-    ).await;
-
-    if update_result.is_err() {
-        println!("Error updating status: {:?}", update_result);
-        return Err(anyhow::anyhow!("Failed to update status"));
+    email_address: &str
+) -> Result<(), LicenseError> {
+    if email_address != serial_number_table.users.email {
+        return Err(LicenseError::OwnerMismatch);
+    }
+    if serial_number_table.revoked {
+        return Err(LicenseError::Revoked);
+    }
+
+    let current_device_id = serial_number_table.device_id.as_deref().unwrap_or("");
+    if serial_number_table.active && !device_matches(current_device_id, device_id) {
+        return Err(LicenseError::DeviceMismatch);
     }
 
     Ok(())
 }
 
-fn authorization(
-    serial_number_table: SerialNumbersTable,
+fn deactivation_allowed(
+    serial_number_table: &SerialNumbersTable,
     device_id: &str,
     email_address: &str
-) -> bool {
-    println!("EARL_DEBUG compare active and device_id");
-    let current_device_id = match serial_number_table.device_id {
-        Some(id) => id,
-        None => { "".to_string() }
-    };
-
+) -> Result<(), LicenseError> {
     if email_address != serial_number_table.users.email {
-        println!("Transaction failed. Email address is not the owner of the serial number");
-        return false;
+        return Err(LicenseError::OwnerMismatch);
     }
 
-    if serial_number_table.active && current_device_id != device_id {
-        println!("Serial number is already active and device ID does not match");
-        return false;
+    let current_device_id = serial_number_table.device_id.as_deref().unwrap_or("");
+    if !serial_number_table.active || !device_matches(current_device_id, device_id) {
+        return Err(LicenseError::DeviceMismatch);
     }
 
-    if serial_number_table.active && current_device_id == device_id {
-        println!("Serial number is already active and device ID matches");
-        return true;
-    }
-
-    println!("EARL_DEBUG active should be false");
-    if !serial_number_table.active {
-        println!("Serial number is not active and device ID does not match");
+    Ok(())
+}
 
-        // TODO: Update the serial number
-        return true;
+fn transfer_allowed(
+    serial_number_table: &SerialNumbersTable,
+    email_address: &str,
+    now: DateTime<Utc>
+) -> Result<(), LicenseError> {
+    if email_address != serial_number_table.users.email {
+        return Err(LicenseError::OwnerMismatch);
+    }
+    if serial_number_table.revoked {
+        return Err(LicenseError::Revoked);
     }
 
-    false
+    match serial_number_table.transferred_at {
+        Some(last_transfer) if now - last_transfer < Duration::days(TRANSFER_COOLDOWN_DAYS) => {
+            Err(LicenseError::TransferCooldown {
+                until: last_transfer + Duration::days(TRANSFER_COOLDOWN_DAYS),
+            })
+        }
+        _ => Ok(()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::licensing::backend::User;
+    use crate::licensing::fake_server;
+    use crate::licensing::memory::InMemoryBackend;
+    use crate::licensing::supabase::SupabaseBackend;
+
+    const TEST_EMAIL_ADDRESS: &str = fake_server::OWNER_EMAIL;
+
+    fn table(device_id: Option<&str>, active: bool) -> SerialNumbersTable {
+        SerialNumbersTable {
+            serial_number: "SERIAL-000".to_string(),
+            device_id: device_id.map(|id| id.to_string()),
+            active,
+            transferred_at: None,
+            revoked: false,
+            users: User { email: TEST_EMAIL_ADDRESS.to_string() },
+        }
+    }
 
-    const TEST_EMAIL_ADDRESS: &str = "mpguser004@gmail.com";
+    async fn backend() -> SupabaseBackend {
+        let url = fake_server::start().await;
+        SupabaseBackend::new(&url, fake_server::ANON_KEY)
+    }
+
+    #[test]
+    fn test_deactivation_requires_owner_and_same_device() {
+        let active = table(Some("device-a"), true);
+        assert!(deactivation_allowed(&active, "device-a", TEST_EMAIL_ADDRESS).is_ok());
+        assert_eq!(
+            deactivation_allowed(&active, "device-b", TEST_EMAIL_ADDRESS),
+            Err(LicenseError::DeviceMismatch)
+        );
+        assert_eq!(
+            deactivation_allowed(&active, "device-a", "invalid@emailaddress.com"),
+            Err(LicenseError::OwnerMismatch)
+        );
+        assert_eq!(
+            deactivation_allowed(&table(None, false), "device-a", TEST_EMAIL_ADDRESS),
+            Err(LicenseError::DeviceMismatch)
+        );
+    }
+
+    #[test]
+    fn test_transfer_is_limited_by_cooldown() {
+        let now = Utc::now();
+        let mut active = table(Some("device-a"), true);
+        assert!(transfer_allowed(&active, TEST_EMAIL_ADDRESS, now).is_ok());
+        assert_eq!(
+            transfer_allowed(&active, "invalid@emailaddress.com", now),
+            Err(LicenseError::OwnerMismatch)
+        );
+
+        active.transferred_at = Some(now - Duration::days(3));
+        assert_eq!(
+            transfer_allowed(&active, TEST_EMAIL_ADDRESS, now),
+            Err(LicenseError::TransferCooldown { until: now + Duration::days(27) })
+        );
+
+        active.transferred_at = Some(now - Duration::days(TRANSFER_COOLDOWN_DAYS + 1));
+        assert!(transfer_allowed(&active, TEST_EMAIL_ADDRESS, now).is_ok());
+    }
 
     #[tokio::test]
     async fn test_success_when_serial_number_is_found_and_active_is_false() {
-        let client = connect_supabase().await.unwrap();
-        let serial_number_table = fetch_serial_number_table(client, "SERIAL-123").await;
-        assert!(serial_number_table.is_ok(), "Serial number is found on the database");
+        let backend = backend().await;
+        let serial_number_table = backend.fetch_serial_number("SERIAL-123").await;
+        assert!(
+            matches!(serial_number_table, Ok(Some(_))),
+            "Serial number is found on the database"
+        );
         let auth_result = authorization(
-            serial_number_table.unwrap(),
+            &serial_number_table.unwrap().unwrap(),
             "mock_device_id",
             TEST_EMAIL_ADDRESS
         );
-        assert!(auth_result, "Authorization should be successful");
+        assert!(auth_result.is_ok(), "Authorization should be successful");
     }
 
     #[tokio::test]
     async fn test_success_when_serialnumber_is_found_active_is_true_deviceid_is_match() {
-        let client = connect_supabase().await.unwrap();
-        let serial_number_table = fetch_serial_number_table(client, "SERIAL-456").await;
-        assert!(serial_number_table.is_ok(), "Serial number should be found on the database");
+        let backend = backend().await;
+        let serial_number_table = backend.fetch_serial_number("SERIAL-456").await;
+        assert!(
+            matches!(serial_number_table, Ok(Some(_))),
+            "Serial number should be found on the database"
+        );
         let auth_result = authorization(
-            serial_number_table.unwrap(),
+            &serial_number_table.unwrap().unwrap(),
             "windowsmachine",
             TEST_EMAIL_ADDRESS
         );
-        assert!(auth_result, "Authorization should be successful");
+        assert!(auth_result.is_ok(), "Authorization should be successful");
     }
 
     #[tokio::test]
     async fn test_authorize_failure_when_active_is_true_and_deviceid_is_incorrect() {
-        let client = connect_supabase().await.unwrap();
-        let serial_number_table = fetch_serial_number_table(client, "SERIAL-456").await;
-        assert!(serial_number_table.is_ok(), "Serial number should be found on the database");
+        let backend = backend().await;
+        let serial_number_table = backend.fetch_serial_number("SERIAL-456").await;
+        assert!(
+            matches!(serial_number_table, Ok(Some(_))),
+            "Serial number should be found on the database"
+        );
         let auth_result = authorization(
-            serial_number_table.unwrap(),
+            &serial_number_table.unwrap().unwrap(),
             "incorrect_device_id",
             TEST_EMAIL_ADDRESS
         );
-        assert!(!auth_result, "Authorization should NOT be successful");
+        assert_eq!(
+            auth_result,
+            Err(LicenseError::DeviceMismatch),
+            "Authorization should NOT be successful"
+        );
     }
 
     #[tokio::test]
     async fn test_failure_when_serial_number_is_not_found() {
-        let client = connect_supabase().await.unwrap();
-        let result = fetch_serial_number_table(client, "INVALIDSERIAL-123").await;
-        assert!(result.is_err(), "Serial number should not be found on the database");
+        let backend = backend().await;
+        let result = fetch_serial_number_table(&backend, "INVALIDSERIAL-123").await;
+        assert_eq!(
+            result.unwrap_err(),
+            LicenseError::NotFound,
+            "Serial number should not be found on the database"
+        );
     }
 
     #[tokio::test]
     async fn test_failure_when_email_address_is_not_owner_of_serial() {
-        let client = connect_supabase().await.unwrap();
-        let serial_number_table = fetch_serial_number_table(client, "SERIAL-123").await;
-        assert!(serial_number_table.is_ok(), "Serial number should be found on the database");
+        let backend = backend().await;
+        let serial_number_table = backend.fetch_serial_number("SERIAL-123").await;
+        assert!(
+            matches!(serial_number_table, Ok(Some(_))),
+            "Serial number should be found on the database"
+        );
         let auth_result = authorization(
-            serial_number_table.unwrap(),
+            &serial_number_table.unwrap().unwrap(),
             "mockdevice_id",
             "invalid@emailaddress.com"
         );
-        assert!(!auth_result, "Authorization should NOT be successful");
+        assert_eq!(
+            auth_result,
+            Err(LicenseError::OwnerMismatch),
+            "Authorization should NOT be successful"
+        );
     }
 
     #[tokio::test]
     async fn test_success_update_status() {
-        let client = connect_supabase().await.unwrap();
-        let result = update_status(client.clone(), "SERIAL-789", "earl_device_id", true).await;
+        let backend = backend().await;
+        let result = backend.update_status("SERIAL-789", "earl_device_id", true).await;
         assert!(result.is_ok(), "Status should be updated successfully");
 
-        // Revert for testing purposes
-        let _result = update_status(client, "SERIAL-789", "earl_device_id", false).await;
+        let serial_number_table = backend.fetch_serial_number("SERIAL-789").await.unwrap().unwrap();
+        assert!(serial_number_table.active);
+        assert_eq!(serial_number_table.device_id.as_deref(), Some("earl_device_id"));
+    }
+
+    #[tokio::test]
+    async fn test_authorize_claims_serial_for_first_device_only() {
+        let backend = InMemoryBackend::default().with_serial("SERIAL-123", TEST_EMAIL_ADDRESS, None);
+        let activate = |device_id: &'static str| {
+            authorize(&backend, "SERIAL-123", TEST_EMAIL_ADDRESS, device_id)
+        };
+        assert!(activate("device-a").await.is_ok());
+        assert!(activate("device-a").await.is_ok());
+        assert_eq!(activate("device-b").await, Err(LicenseError::DeviceMismatch));
+    }
+
+    #[tokio::test]
+    async fn test_authorize_reports_why_activation_failed() {
+        let backend = InMemoryBackend::default().with_serial("SERIAL-123", TEST_EMAIL_ADDRESS, None);
+        assert_eq!(
+            authorize(&backend, "SERIAL-999", TEST_EMAIL_ADDRESS, "device-a").await,
+            Err(LicenseError::NotFound)
+        );
+        assert_eq!(
+            authorize(&backend, "SERIAL-123", "invalid@emailaddress.com", "device-a").await,
+            Err(LicenseError::OwnerMismatch)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deactivate_then_activate_on_another_device() {
+        let backend = InMemoryBackend::default().with_serial(
+            "SERIAL-456",
+            TEST_EMAIL_ADDRESS,
+            Some("device-a")
+        );
+        assert!(deactivate(&backend, "SERIAL-456", TEST_EMAIL_ADDRESS, "device-b").await.is_err());
+        assert!(deactivate(&backend, "SERIAL-456", TEST_EMAIL_ADDRESS, "device-a").await.is_ok());
+        assert!(authorize(&backend, "SERIAL-456", TEST_EMAIL_ADDRESS, "device-b").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_transfer_moves_serial_and_starts_cooldown() {
+        let backend = InMemoryBackend::default().with_serial(
+            "SERIAL-456",
+            TEST_EMAIL_ADDRESS,
+            Some("device-a")
+        );
+        assert!(transfer(&backend, "SERIAL-456", TEST_EMAIL_ADDRESS, "device-b").await.is_ok());
+        assert!(authorize(&backend, "SERIAL-456", TEST_EMAIL_ADDRESS, "device-b").await.is_ok());
+        assert!(matches!(
+            transfer(&backend, "SERIAL-456", TEST_EMAIL_ADDRESS, "device-c").await,
+            Err(LicenseError::TransferCooldown { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_revalidate_detects_deactivation_and_revocation() {
+        let backend = InMemoryBackend::default()
+            .with_serial("SERIAL-456", TEST_EMAIL_ADDRESS, Some("device-a"))
+            .with_serial("SERIAL-789", TEST_EMAIL_ADDRESS, Some("device-a"));
+        assert!(revalidate(&backend, "SERIAL-456", TEST_EMAIL_ADDRESS, "device-a").await.is_ok());
+
+        backend.release_serial_number("SERIAL-456").await.unwrap();
+        assert_eq!(
+            revalidate(&backend, "SERIAL-456", TEST_EMAIL_ADDRESS, "device-a").await,
+            Err(LicenseError::Revoked)
+        );
+
+        let backend = backend.revoke("SERIAL-789");
+        assert_eq!(
+            revalidate(&backend, "SERIAL-789", TEST_EMAIL_ADDRESS, "device-a").await,
+            Err(LicenseError::Revoked)
+        );
+        assert_eq!(
+            authorize(&backend, "SERIAL-789", TEST_EMAIL_ADDRESS, "device-a").await,
+            Err(LicenseError::Revoked)
+        );
+        assert!(LicenseError::Revoked.is_revocation());
+        assert!(!LicenseError::unavailable("offline").is_revocation());
     }
 }
@@ -0,0 +1,75 @@
+use std::fmt;
+
+use chrono::{ DateTime, Utc };
+use serde::Serialize;
+
+// Why a licensing operation failed. Serialized as `{ "kind": ..., ... }` so
+// the UI can show a specific message for each case.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LicenseError {
+    NotFound,
+    // The email address is not the owner of the serial number.
+    OwnerMismatch,
+    // The serial number is active on another PC.
+    DeviceMismatch,
+    // The serial number was revoked or deactivated on the license server.
+    Revoked,
+    TransferCooldown {
+        until: DateTime<Utc>,
+    },
+    BackendUnavailable {
+        message: String,
+    },
+    MalformedResponse {
+        message: String,
+    },
+}
+
+impl LicenseError {
+    pub fn unavailable(message: impl fmt::Display) -> Self {
+        LicenseError::BackendUnavailable { message: message.to_string() }
+    }
+
+    pub fn malformed(message: impl fmt::Display) -> Self {
+        LicenseError::MalformedResponse { message: message.to_string() }
+    }
+
+    // Whether the license server answered that this PC may no longer use the
+    // serial number, as opposed to not answering properly.
+    pub fn is_revocation(&self) -> bool {
+        matches!(
+            self,
+            LicenseError::NotFound |
+                LicenseError::OwnerMismatch |
+                LicenseError::DeviceMismatch |
+                LicenseError::Revoked
+        )
+    }
+}
+
+impl fmt::Display for LicenseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LicenseError::NotFound => write!(f, "Serial number not found"),
+            LicenseError::OwnerMismatch => {
+                write!(f, "Email address is not the owner of the serial number")
+            }
+            LicenseError::DeviceMismatch => {
+                write!(f, "Serial number is already active on another device")
+            }
+            LicenseError::Revoked => write!(f, "License has been revoked"),
+            LicenseError::TransferCooldown { until } => {
+                write!(f, "Serial number cannot be transferred again before {}", until)
+            }
+            LicenseError::BackendUnavailable { message } => {
+                write!(f, "License server is unavailable: {}", message)
+            }
+            LicenseError::MalformedResponse { message } => {
+                write!(f, "License server sent an unexpected response: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LicenseError {}
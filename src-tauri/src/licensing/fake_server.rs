@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
+
+use axum::extract::{ Query, State };
+use axum::http::{ HeaderMap, StatusCode };
+use axum::response::{ IntoResponse, Response };
+use axum::routing::{ get, post };
+use axum::{ Json, Router };
+use serde_json::{ json, Value };
+
+use super::backend::SerialNumbersTable;
+use super::token;
+
+pub const ANON_KEY: &str = "test-anon-key";
+pub const OWNER_EMAIL: &str = "mpguser004@gmail.com";
+
+type Rows = Arc<Mutex<Vec<Value>>>;
+
+// Minimal PostgREST stand-in for the `serial_numbers` table, enough for the
+// requests `SupabaseBackend` sends. Every call to `start` gets its own port
+// and its own copy of the seed rows so tests do not affect each other.
+pub async fn start() -> String {
+    let rows: Rows = Arc::new(Mutex::new(seed()));
+    let app = Router::new()
+        .route("/rest/v1/serial_numbers", get(select_handler).patch(update_handler))
+        .route("/rest/v1/rpc/issue_license_token", post(issue_token_handler))
+        .with_state(rows);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    format!("http://{}", address)
+}
+
+fn seed() -> Vec<Value> {
+    vec![
+        json!({
+            "serial_number": "SERIAL-123",
+            "device_id": null,
+            "active": false,
+            "users": { "email": OWNER_EMAIL }
+        }),
+        json!({
+            "serial_number": "SERIAL-456",
+            "device_id": "windowsmachine",
+            "active": true,
+            "users": { "email": OWNER_EMAIL }
+        }),
+        json!({
+            "serial_number": "SERIAL-789",
+            "device_id": null,
+            "active": false,
+            "users": { "email": OWNER_EMAIL }
+        })
+    ]
+}
+
+fn authorized(headers: &HeaderMap) -> bool {
+    headers.get("apikey").and_then(|value| value.to_str().ok()) == Some(ANON_KEY)
+}
+
+// Supports the `eq.` and `is.` operators, which are the only ones used.
+fn matches(row: &Value, filters: &HashMap<String, String>) -> bool {
+    filters
+        .iter()
+        .filter(|(column, _)| column.as_str() != "select")
+        .all(|(column, filter)| {
+            let value = &row[column.as_str()];
+            match filter.split_once('.') {
+                Some(("eq", expected)) => value.as_str() == Some(expected),
+                Some(("is", "true")) => value == &json!(true),
+                Some(("is", "false")) => value == &json!(false),
+                Some(("is", "null")) => value.is_null(),
+                _ => false,
+            }
+        })
+}
+
+async fn select_handler(
+    State(rows): State<Rows>,
+    headers: HeaderMap,
+    Query(filters): Query<HashMap<String, String>>
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let rows = rows.lock().unwrap();
+    let selected: Vec<Value> = rows
+        .iter()
+        .filter(|row| matches(row, &filters))
+        .cloned()
+        .collect();
+    Json(selected).into_response()
+}
+
+async fn update_handler(
+    State(rows): State<Rows>,
+    headers: HeaderMap,
+    Query(filters): Query<HashMap<String, String>>,
+    Json(changes): Json<Value>
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let mut rows = rows.lock().unwrap();
+    let mut updated = Vec::new();
+    for row in rows.iter_mut().filter(|row| matches(row, &filters)) {
+        if let (Some(row), Some(changes)) = (row.as_object_mut(), changes.as_object()) {
+            for (column, value) in changes {
+                row.insert(column.clone(), value.clone());
+            }
+        }
+        updated.push(row.clone());
+    }
+
+    let representation = headers
+        .get("Prefer")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("return=representation"));
+    if representation {
+        Json(updated).into_response()
+    } else {
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
+
+async fn issue_token_handler(
+    State(rows): State<Rows>,
+    headers: HeaderMap,
+    Json(request): Json<Value>
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let row = rows
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|row| row["serial_number"] == request["serial_number"])
+        .cloned();
+    let table: Option<SerialNumbersTable> = row.and_then(|row| serde_json::from_value(row).ok());
+    let device_id = request["device_id"].as_str().unwrap_or_default();
+
+    match table.map(|table| token::issue_for_tests(&table, device_id)) {
+        Some(Ok(token)) => Json(token).into_response(),
+        _ => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
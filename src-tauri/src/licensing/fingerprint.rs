@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use machineid_rs::{ Encryption, HWIDComponent, IdBuilder };
+
+use crate::settings::uuidmodel::HWID_KEY;
+
+const PREFIX: &str = "fp1:";
+const HASH_LENGTH: usize = 16;
+
+// Share of the match score (out of 100) that each hardware component
+// contributes. The system UUID and the boot disk identify a PC best, the CPU
+// vendor id is shared by every machine with the same processor family.
+const COMPONENTS: [(&str, u32); 5] = [
+    ("system", 35),
+    ("disk", 25),
+    ("mac", 20),
+    ("cpu", 10),
+    ("machine", 10),
+];
+
+// A license stays bound to a PC as long as components worth at least this
+// score are unchanged, so replacing a disk or a network card does not need a
+// license transfer.
+pub const MATCH_THRESHOLD: u32 = 60;
+
+static THIS_DEVICE: OnceLock<DeviceFingerprint> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DeviceFingerprint {
+    components: BTreeMap<String, String>,
+}
+
+impl DeviceFingerprint {
+    // Fingerprint of this PC. The hardware is read once, when the app starts,
+    // so commands asking for it afterwards do not block on it.
+    pub fn this_device() -> Result<&'static Self> {
+        if let Some(fingerprint) = THIS_DEVICE.get() {
+            return Ok(fingerprint);
+        }
+        let fingerprint = Self::collect()?;
+        Ok(THIS_DEVICE.get_or_init(|| fingerprint))
+    }
+
+    // Reads every component available on this machine. Components that
+    // cannot be read (e.g. no network card) are left out and simply do not
+    // contribute to the score.
+    fn collect() -> Result<Self> {
+        let mut components = BTreeMap::new();
+        for (name, _) in COMPONENTS {
+            let mut builder = IdBuilder::new(Encryption::SHA256);
+            builder.add_component(hwid_component(name));
+            if let Ok(hash) = builder.build(HWID_KEY) {
+                components.insert(name.to_string(), hash[..HASH_LENGTH].to_string());
+            }
+        }
+
+        if components.is_empty() {
+            return Err(anyhow::anyhow!("No hardware component could be read"));
+        }
+
+        Ok(DeviceFingerprint { components })
+    }
+
+    // Compact form stored as the `device_id` of an activated serial number,
+    // e.g. `fp1:cpu=...;disk=...;system=...`.
+    pub fn encode(&self) -> String {
+        let parts: Vec<String> = self.components
+            .iter()
+            .map(|(name, hash)| format!("{}={}", name, hash))
+            .collect();
+        format!("{}{}", PREFIX, parts.join(";"))
+    }
+
+    pub fn decode(encoded: &str) -> Option<Self> {
+        let body = encoded.strip_prefix(PREFIX)?;
+        let mut components = BTreeMap::new();
+        for part in body.split(';').filter(|part| !part.is_empty()) {
+            let (name, hash) = part.split_once('=')?;
+            components.insert(name.to_string(), hash.to_string());
+        }
+        Some(DeviceFingerprint { components })
+    }
+
+    pub fn score(&self, other: &DeviceFingerprint) -> u32 {
+        COMPONENTS.iter()
+            .filter(|(name, _)| {
+                match (self.components.get(*name), other.components.get(*name)) {
+                    (Some(mine), Some(theirs)) => mine == theirs,
+                    _ => false,
+                }
+            })
+            .map(|(_, weight)| weight)
+            .sum()
+    }
+}
+
+// Whether the device recorded on the serial number is the presented one.
+// Device ids recorded before fingerprints were introduced are compared as is.
+pub fn device_matches(recorded: &str, presented: &str) -> bool {
+    match (DeviceFingerprint::decode(recorded), DeviceFingerprint::decode(presented)) {
+        (Some(recorded), Some(presented)) => recorded.score(&presented) >= MATCH_THRESHOLD,
+        _ => recorded == presented,
+    }
+}
+
+fn hwid_component(name: &str) -> HWIDComponent {
+    match name {
+        "system" => HWIDComponent::SystemID,
+        "disk" => HWIDComponent::DriveSerial,
+        "mac" => HWIDComponent::MacAddress,
+        "cpu" => HWIDComponent::CPUID,
+        _ => HWIDComponent::MachineName,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint(system: &str, disk: &str, mac: &str) -> DeviceFingerprint {
+        let components = [
+            ("system", system),
+            ("disk", disk),
+            ("mac", mac),
+            ("cpu", "cpu-a"),
+            ("machine", "pc-01"),
+        ];
+        DeviceFingerprint {
+            components: components
+                .iter()
+                .map(|(name, hash)| (name.to_string(), hash.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_encode_and_decode_round_trip() {
+        let original = fingerprint("sys-a", "disk-a", "mac-a");
+        let decoded = DeviceFingerprint::decode(&original.encode()).unwrap();
+        assert_eq!(decoded, original);
+        assert!(DeviceFingerprint::decode("windowsmachine").is_none());
+    }
+
+    #[test]
+    fn test_minor_hardware_change_still_matches() {
+        let original = fingerprint("sys-a", "disk-a", "mac-a").encode();
+        let new_disk = fingerprint("sys-a", "disk-b", "mac-a").encode();
+        let new_nic = fingerprint("sys-a", "disk-a", "mac-b").encode();
+        assert!(device_matches(&original, &new_disk));
+        assert!(device_matches(&original, &new_nic));
+    }
+
+    #[test]
+    fn test_replaced_motherboard_does_not_match() {
+        let original = fingerprint("sys-a", "disk-a", "mac-a").encode();
+        let new_board = fingerprint("sys-b", "disk-a", "mac-b").encode();
+        assert!(!device_matches(&original, &new_board));
+    }
+
+    #[test]
+    fn test_legacy_device_ids_are_compared_exactly() {
+        assert!(device_matches("windowsmachine", "windowsmachine"));
+        assert!(!device_matches("windowsmachine", "incorrect_device_id"));
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{ DateTime, Utc };
+
+use super::backend::{ LicenseBackend, SerialNumbersTable, User };
+use super::error::LicenseError;
+use super::token;
+
+// Keeps serial numbers in memory for tests of the authorization flow.
+#[derive(Default)]
+pub struct InMemoryBackend {
+    rows: Mutex<HashMap<String, SerialNumbersTable>>,
+}
+
+impl InMemoryBackend {
+    pub fn with_serial(self, serial_number: &str, owner: &str, device_id: Option<&str>) -> Self {
+        let table = SerialNumbersTable {
+            serial_number: serial_number.to_string(),
+            device_id: device_id.map(|id| id.to_string()),
+            active: device_id.is_some(),
+            transferred_at: None,
+            revoked: false,
+            users: User { email: owner.to_string() },
+        };
+        self.rows.lock().unwrap().insert(serial_number.to_string(), table);
+        self
+    }
+
+    pub fn revoke(self, serial_number: &str) -> Self {
+        if let Some(table) = self.rows.lock().unwrap().get_mut(serial_number) {
+            table.revoked = true;
+        }
+        self
+    }
+
+    fn modify<F>(&self, serial_number: &str, change: F) -> Result<(), LicenseError>
+        where F: FnOnce(&mut SerialNumbersTable)
+    {
+        let mut rows = self.rows.lock().unwrap();
+        let table = rows
+            .get_mut(serial_number)
+            .ok_or(LicenseError::NotFound)?;
+        change(table);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LicenseBackend for InMemoryBackend {
+    async fn fetch_serial_number(
+        &self,
+        serial_number: &str
+    ) -> Result<Option<SerialNumbersTable>, LicenseError> {
+        Ok(self.rows.lock().unwrap().get(serial_number).cloned())
+    }
+
+    async fn claim_serial_number(
+        &self,
+        serial_number: &str,
+        device_id: &str
+    ) -> Result<bool, LicenseError> {
+        let mut claimed = false;
+        self.modify(serial_number, |table| {
+            if !table.active {
+                table.active = true;
+                table.device_id = Some(device_id.to_string());
+                claimed = true;
+            }
+        })?;
+        Ok(claimed)
+    }
+
+    async fn update_status(
+        &self,
+        serial_number: &str,
+        device_id: &str,
+        active: bool
+    ) -> Result<(), LicenseError> {
+        self.modify(serial_number, |table| {
+            table.active = active;
+            table.device_id = Some(device_id.to_string());
+        })
+    }
+
+    async fn release_serial_number(&self, serial_number: &str) -> Result<(), LicenseError> {
+        self.modify(serial_number, |table| {
+            table.active = false;
+            table.device_id = None;
+        })
+    }
+
+    async fn transfer_serial_number(
+        &self,
+        serial_number: &str,
+        device_id: &str,
+        at: DateTime<Utc>
+    ) -> Result<(), LicenseError> {
+        self.modify(serial_number, |table| {
+            table.active = true;
+            table.device_id = Some(device_id.to_string());
+            table.transferred_at = Some(at);
+        })
+    }
+
+    async fn issue_token(
+        &self,
+        serial_number: &str,
+        device_id: &str
+    ) -> Result<String, LicenseError> {
+        let table = self.fetch_serial_number(serial_number).await?.ok_or(LicenseError::NotFound)?;
+        token::issue_for_tests(&table, device_id).map_err(|_| LicenseError::DeviceMismatch)
+    }
+}
@@ -1 +1,14 @@
+pub mod activation;
+pub mod backend;
 pub mod cloud_service;
+pub mod error;
+pub mod fingerprint;
+pub mod renewal;
+pub mod supabase;
+pub mod tier;
+pub mod token;
+
+#[cfg(test)]
+mod fake_server;
+#[cfg(test)]
+mod memory;
@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{ DateTime, Utc };
+use tauri::{ AppHandle, Emitter, Manager };
+use tokio::time::sleep;
+use tracing::{ error, info, warn };
+
+use super::backend::LicenseBackendState;
+use super::cloud_service;
+use super::error::LicenseError;
+use super::fingerprint::DeviceFingerprint;
+use super::token::{ self, TokenStatus, LICENSE_PUBLIC_KEY };
+use crate::audit::event::AuditEvent;
+use crate::audit::store::AuditLog;
+use crate::constants;
+use crate::settings::appconfigmodels::{ License, LicensePolicy };
+use crate::settings::uuidmodel::UniqueId;
+use crate::AppConfigState;
+
+// Used when the policy cannot be read from the config.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Whether the stored license lets the app run, judged only from its signed
+// token so editing `authorized` in the config file has no effect.
+pub fn is_authorized(license: &License, device_id: &str, now: DateTime<Utc>) -> bool {
+    match token::check_license(license, LICENSE_PUBLIC_KEY, device_id, now) {
+        Ok(TokenStatus::Valid) => true,
+        Ok(TokenStatus::Grace) => {
+            warn!("License token expired, running on the offline grace period");
+            true
+        }
+        Ok(TokenStatus::Expired) => false,
+        Err(e) => {
+            warn!("License token rejected: {:#}", e);
+            false
+        }
+    }
+}
+
+// Asks the license server whether the serial number is still active on this
+// PC, renews the license token whenever the PC is online and re-checks the
+// stored one, so a revoked or expired license locks the app even if it is
+// never restarted.
+pub async fn run(app_handle: AppHandle) {
+    loop {
+        if let Err(e) = revalidate(&app_handle).await {
+            warn!("License revalidation failed: {:#}", e);
+        }
+        if let Err(e) = renew(&app_handle).await {
+            warn!("License renewal failed: {:#}", e);
+        }
+        if let Err(e) = refresh(&app_handle) {
+            warn!("License check failed: {:#}", e);
+        }
+
+        let interval = load(&app_handle)
+            .map(|(_, policy)| revalidate_interval(&policy))
+            .unwrap_or(CHECK_INTERVAL);
+        sleep(interval).await;
+    }
+}
+
+// Only an answer from the license server locks the station, being offline is
+// covered by the token's grace period.
+async fn revalidate(app_handle: &AppHandle) -> Result<()> {
+    let (license, _) = load(app_handle)?;
+    if license.serial_number.is_empty() {
+        return Ok(());
+    }
+
+    let device_id = DeviceFingerprint::this_device()?.encode();
+    let backend = app_handle.state::<LicenseBackendState>().inner().clone();
+    let result = cloud_service::revalidate(
+        backend.as_ref(),
+        &license.serial_number,
+        &license.email_address,
+        &device_id
+    ).await;
+
+    match result {
+        Ok(()) => store(app_handle, License { last_validated_at: Some(Utc::now()), ..license }),
+        Err(error) if error.is_revocation() => lock(app_handle, license, error),
+        Err(error) => Err(error.into()),
+    }
+}
+
+// Drops the token so the station stays locked across restarts. Renewal gets
+// a new one if the serial number is reinstated on the license server.
+fn lock(app_handle: &AppHandle, license: License, error: LicenseError) -> Result<()> {
+    if !license.authorized && license.token.is_empty() {
+        return Ok(());
+    }
+
+    error!(%error, "License revoked, locking the station");
+    app_handle
+        .state::<AuditLog>()
+        .record(AuditEvent::license_changed("revoked", &license.serial_number));
+    store(app_handle, License { authorized: false, token: String::new(), ..license })?;
+    let _ = app_handle.emit(constants::HANDLER_LICENSE_REVOKED, error);
+    Ok(())
+}
+
+async fn renew(app_handle: &AppHandle) -> Result<()> {
+    let (license, policy) = load(app_handle)?;
+    let device_id = DeviceFingerprint::this_device()?.encode();
+    if !needs_renewal(&license, LICENSE_PUBLIC_KEY, &policy, Utc::now()) {
+        return Ok(());
+    }
+
+    let backend = app_handle.state::<LicenseBackendState>().inner().clone();
+    let token = backend.issue_token(&license.serial_number, &device_id).await?;
+    let renewed = License { token, ..license };
+    if !is_authorized(&renewed, &device_id, Utc::now()) {
+        return Err(anyhow::anyhow!("License server issued an invalid token"));
+    }
+
+    info!("License token renewed");
+    store(app_handle, License { authorized: true, ..renewed })
+}
+
+fn refresh(app_handle: &AppHandle) -> Result<()> {
+    let (license, _) = load(app_handle)?;
+    let device_id = DeviceFingerprint::this_device()?.encode();
+    let authorized = is_authorized(&license, &device_id, Utc::now());
+    if authorized != license.authorized {
+        store(app_handle, License { authorized, ..license })?;
+    }
+    Ok(())
+}
+
+fn load(app_handle: &AppHandle) -> Result<(License, LicensePolicy)> {
+    let device = UniqueId::default()?;
+    let state = app_handle.state::<AppConfigState>();
+    let config = state.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    Ok((config.get_license(device.id.as_str())?, config.get_license_policy(device.id.as_str())?))
+}
+
+fn store(app_handle: &AppHandle, license: License) -> Result<()> {
+    crate::save_license(&app_handle.state::<AppConfigState>(), license.clone())?;
+    let _ = app_handle.emit(constants::HANDLER_INITIALIZE_LICENSE, license.to_json());
+    Ok(())
+}
+
+fn revalidate_interval(policy: &LicensePolicy) -> Duration {
+    Duration::from_secs(policy.revalidate_interval_minutes.max(1) * 60)
+}
+
+// A token is requested for an activated serial number when none is stored
+// yet (e.g. activated by an older version), when it no longer verifies, or
+// once the renewal interval has passed.
+fn needs_renewal(
+    license: &License,
+    public_key: &str,
+    policy: &LicensePolicy,
+    now: DateTime<Utc>
+) -> bool {
+    if license.serial_number.is_empty() {
+        return false;
+    }
+
+    match token::verify(&license.token, public_key) {
+        Ok(claims) => claims.renewal_due(now, policy),
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use token::{ testing, LicenseClaims };
+
+    #[test]
+    fn test_needs_renewal() {
+        let now = Utc::now();
+        let policy = LicensePolicy::default();
+        let claims = LicenseClaims {
+            serial_number: "SERIAL-123".to_string(),
+            device_id: "device-a".to_string(),
+            issued_at: now,
+            expires_at: now + Duration::days(30),
+            tier: Default::default(),
+            grace_until: None,
+        };
+        let license = License {
+            authorized: true,
+            serial_number: "SERIAL-123".to_string(),
+            email_address: "mpguser004@gmail.com".to_string(),
+            token: token::sign(&claims, &testing::key_pair()),
+            last_validated_at: None,
+        };
+        let public_key = testing::public_key();
+
+        assert!(!needs_renewal(&license, &public_key, &policy, now));
+        assert!(needs_renewal(&license, &public_key, &policy, now + Duration::days(2)));
+        assert!(needs_renewal(&License { token: String::new(), ..license }, &public_key, &policy, now));
+        assert!(!needs_renewal(&License::default(), &public_key, &policy, now));
+    }
+
+    #[test]
+    fn test_revalidate_interval_comes_from_policy() {
+        let policy = LicensePolicy { revalidate_interval_minutes: 15, ..LicensePolicy::default() };
+        assert_eq!(revalidate_interval(&policy), std::time::Duration::from_secs(15 * 60));
+
+        let policy = LicensePolicy { revalidate_interval_minutes: 0, ..LicensePolicy::default() };
+        assert_eq!(revalidate_interval(&policy), std::time::Duration::from_secs(60));
+    }
+}
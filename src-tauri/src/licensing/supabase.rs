@@ -0,0 +1,250 @@
+use async_trait::async_trait;
+use chrono::{ DateTime, Utc };
+use dotenv::{ dotenv, var };
+use serde_json::{ json, Value };
+use supabase_rs::SupabaseClient;
+
+use super::backend::{ LicenseBackend, SerialNumbersTable };
+use super::error::LicenseError;
+
+const TABLE: &str = "serial_numbers";
+
+// Serial numbers stored in the Supabase `serial_numbers` table. Credentials
+// are read once; a missing variable is only reported when the backend is used
+// so the app still starts without them.
+pub struct SupabaseBackend {
+    url: Option<String>,
+    anon_key: Option<String>,
+}
+
+impl SupabaseBackend {
+    pub fn new(url: &str, anon_key: &str) -> Self {
+        SupabaseBackend {
+            url: Some(url.trim_end_matches('/').to_string()),
+            anon_key: Some(anon_key.to_string()),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        dotenv().ok();
+
+        match (var("SUPABASE_URL"), var("SUPABASE_KEY")) {
+            (Ok(url), Ok(anon_key)) => SupabaseBackend::new(&url, &anon_key),
+            (url, anon_key) =>
+                SupabaseBackend {
+                    url: url.ok(),
+                    anon_key: anon_key.ok(),
+                },
+        }
+    }
+
+    fn credentials(&self) -> Result<(&str, &str), LicenseError> {
+        let url = self.url
+            .as_deref()
+            .ok_or_else(|| LicenseError::unavailable("SUPABASE_URL not set"))?;
+        let anon_key = self.anon_key
+            .as_deref()
+            .ok_or_else(|| LicenseError::unavailable("SUPABASE_KEY not set"))?;
+        Ok((url, anon_key))
+    }
+
+    fn connect(&self) -> Result<SupabaseClient, LicenseError> {
+        let (url, anon_key) = self.credentials()?;
+        SupabaseClient::new(url.to_string(), anon_key.to_string()).map_err(LicenseError::unavailable)
+    }
+
+    async fn update(&self, serial_number: &str, body: Value) -> Result<(), LicenseError> {
+        self.connect()?
+            .update_with_column_name(TABLE, "serial_number", serial_number, body).await
+            .map_err(LicenseError::unavailable)?;
+        Ok(())
+    }
+
+    // Sends a PostgREST request built by `request` and decodes its JSON body.
+    async fn send<T, F>(&self, request: F) -> Result<T, LicenseError>
+        where T: serde::de::DeserializeOwned, F: FnOnce(&str) -> reqwest::RequestBuilder
+    {
+        let (url, anon_key) = self.credentials()?;
+        let response = request(url)
+            .header("apikey", anon_key)
+            .bearer_auth(anon_key)
+            .send().await
+            .map_err(LicenseError::unavailable)?;
+
+        if !response.status().is_success() {
+            return Err(LicenseError::unavailable(response.status()));
+        }
+
+        response.json().await.map_err(LicenseError::malformed)
+    }
+}
+
+#[async_trait]
+impl LicenseBackend for SupabaseBackend {
+    async fn fetch_serial_number(
+        &self,
+        serial_number: &str
+    ) -> Result<Option<SerialNumbersTable>, LicenseError> {
+        let response = self
+            .connect()?
+            .select(TABLE)
+            .columns(["*", "users(email)"].to_vec())
+            .eq("serial_number", serial_number)
+            .execute().await
+            .map_err(LicenseError::unavailable)?;
+
+        match response.into_iter().next() {
+            Some(row) => serde_json::from_value(row).map(Some).map_err(LicenseError::malformed),
+            None => Ok(None),
+        }
+    }
+
+    async fn claim_serial_number(
+        &self,
+        serial_number: &str,
+        device_id: &str
+    ) -> Result<bool, LicenseError> {
+        // The `active=is.false` filter makes the update conditional, so two
+        // PCs activating the same serial at once cannot both win.
+        let claimed_rows: Vec<Value> = self.send(|url| {
+            reqwest::Client
+                ::new()
+                .patch(format!("{}/rest/v1/{}", url, TABLE))
+                .query(
+                    &[
+                        ("serial_number", format!("eq.{}", serial_number)),
+                        ("active", "is.false".to_string()),
+                    ]
+                )
+                .header("Prefer", "return=representation")
+                .json(&json!({
+                    "active": true,
+                    "device_id": device_id
+                }))
+        }).await?;
+        Ok(!claimed_rows.is_empty())
+    }
+
+    async fn update_status(
+        &self,
+        serial_number: &str,
+        device_id: &str,
+        active: bool
+    ) -> Result<(), LicenseError> {
+        self.update(
+            serial_number,
+            json!({
+                "active": active,
+                "device_id": device_id
+            })
+        ).await
+    }
+
+    async fn release_serial_number(&self, serial_number: &str) -> Result<(), LicenseError> {
+        self.update(
+            serial_number,
+            json!({
+                "active": false,
+                "device_id": null
+            })
+        ).await
+    }
+
+    async fn transfer_serial_number(
+        &self,
+        serial_number: &str,
+        device_id: &str,
+        at: DateTime<Utc>
+    ) -> Result<(), LicenseError> {
+        self.update(
+            serial_number,
+            json!({
+                "active": true,
+                "device_id": device_id,
+                "transferred_at": at
+            })
+        ).await
+    }
+
+    async fn issue_token(
+        &self,
+        serial_number: &str,
+        device_id: &str
+    ) -> Result<String, LicenseError> {
+        // Tokens are signed by the `issue_license_token` database function,
+        // which holds the private key and checks the serial is active on
+        // this device.
+        self.send(|url| {
+            reqwest::Client
+                ::new()
+                .post(format!("{}/rest/v1/rpc/issue_license_token", url))
+                .json(&json!({
+                    "serial_number": serial_number,
+                    "device_id": device_id
+                }))
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::licensing::{ fake_server, token };
+
+    #[tokio::test]
+    async fn test_missing_credentials_are_reported_on_use() {
+        let backend = SupabaseBackend { url: None, anon_key: None };
+        let error = backend.fetch_serial_number("SERIAL-123").await.unwrap_err();
+        assert_eq!(error, LicenseError::unavailable("SUPABASE_URL not set"));
+    }
+
+    #[tokio::test]
+    async fn test_claim_only_succeeds_while_inactive() {
+        let url = fake_server::start().await;
+        let backend = SupabaseBackend::new(&url, fake_server::ANON_KEY);
+        assert!(backend.claim_serial_number("SERIAL-123", "device-a").await.unwrap());
+        assert!(!backend.claim_serial_number("SERIAL-123", "device-b").await.unwrap());
+
+        let table = backend.fetch_serial_number("SERIAL-123").await.unwrap().unwrap();
+        assert!(table.active);
+        assert_eq!(table.device_id.as_deref(), Some("device-a"));
+    }
+
+    #[tokio::test]
+    async fn test_release_and_transfer_update_the_row() {
+        let url = fake_server::start().await;
+        let backend = SupabaseBackend::new(&url, fake_server::ANON_KEY);
+
+        backend.release_serial_number("SERIAL-456").await.unwrap();
+        let table = backend.fetch_serial_number("SERIAL-456").await.unwrap().unwrap();
+        assert!(!table.active);
+        assert!(table.device_id.is_none());
+
+        let now = Utc::now();
+        backend.transfer_serial_number("SERIAL-456", "device-b", now).await.unwrap();
+        let table = backend.fetch_serial_number("SERIAL-456").await.unwrap().unwrap();
+        assert!(table.active);
+        assert_eq!(table.device_id.as_deref(), Some("device-b"));
+        assert_eq!(table.transferred_at, Some(now));
+    }
+
+    #[tokio::test]
+    async fn test_issues_token_only_for_active_device() {
+        let url = fake_server::start().await;
+        let backend = SupabaseBackend::new(&url, fake_server::ANON_KEY);
+        let token = backend.issue_token("SERIAL-456", "windowsmachine").await.unwrap();
+        let claims = token::verify(&token, &token::testing::public_key()).unwrap();
+        assert_eq!(claims.serial_number, "SERIAL-456");
+        assert_eq!(claims.device_id, "windowsmachine");
+
+        assert!(backend.issue_token("SERIAL-456", "incorrect_device_id").await.is_err());
+        assert!(backend.issue_token("SERIAL-123", "windowsmachine").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_api_key() {
+        let url = fake_server::start().await;
+        let backend = SupabaseBackend::new(&url, "wrong-key");
+        assert!(backend.fetch_serial_number("SERIAL-123").await.is_err());
+    }
+}
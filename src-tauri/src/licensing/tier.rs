@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::{ DateTime, Utc };
+use serde::{ Deserialize, Serialize };
+
+use super::fingerprint::DeviceFingerprint;
+use super::token::{ self, TokenStatus, LICENSE_PUBLIC_KEY };
+use crate::settings::appconfigmodels::{ Client, License };
+
+// What a license was sold as. The tier is part of the signed token claims so
+// it cannot be changed by editing the config file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LicenseTier {
+    // No valid license token, nothing is allowed.
+    Unlicensed,
+    // Tokens issued before tiers were introduced are single station.
+    #[default]
+    SingleStation,
+    MultiStation {
+        max_clients: usize,
+    },
+    // Ends at `expires_at` without any offline grace period.
+    Trial {
+        expires_at: DateTime<Utc>,
+    },
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    Clients,
+    Tls,
+    Metrics,
+}
+
+// Sent to the UI through `HANDLER_LICENSE_LIMIT` when an action is refused
+// because of the license tier.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LimitReached {
+    pub feature: Feature,
+    pub tier: LicenseTier,
+    pub message: String,
+}
+
+impl fmt::Display for LimitReached {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl LicenseTier {
+    pub fn max_clients(&self) -> usize {
+        match self {
+            LicenseTier::Unlicensed => 0,
+            LicenseTier::SingleStation | LicenseTier::Trial { .. } => 1,
+            LicenseTier::MultiStation { max_clients } => *max_clients,
+        }
+    }
+
+    pub fn allows(&self, feature: Feature) -> bool {
+        match self {
+            LicenseTier::Unlicensed => false,
+            LicenseTier::Trial { .. } => feature == Feature::Clients,
+            _ => true,
+        }
+    }
+
+    pub fn check_feature(&self, feature: Feature) -> Result<(), LimitReached> {
+        if self.allows(feature) {
+            return Ok(());
+        }
+
+        Err(self.limit_reached(feature, match feature {
+            Feature::Clients => "Pairing stations requires a license".to_string(),
+            Feature::Tls => "Encrypted connections are not available on this license".to_string(),
+            Feature::Metrics => "Metrics are not available on this license".to_string(),
+        }))
+    }
+
+    // Re-pairing a station that is already known never counts against the
+    // limit.
+    pub fn check_client_limit(
+        &self,
+        paired: &HashMap<String, Client>,
+        hwid: &str
+    ) -> Result<(), LimitReached> {
+        if paired.contains_key(hwid) || paired.len() < self.max_clients() {
+            return Ok(());
+        }
+
+        Err(
+            self.limit_reached(
+                Feature::Clients,
+                format!("This license allows up to {} paired station(s)", self.max_clients())
+            )
+        )
+    }
+
+    fn limit_reached(&self, feature: Feature, message: String) -> LimitReached {
+        LimitReached {
+            feature,
+            tier: self.clone(),
+            message,
+        }
+    }
+}
+
+pub fn tier_of(license: &License) -> LicenseTier {
+    let device_id = DeviceFingerprint::this_device()
+        .map(|fingerprint| fingerprint.encode())
+        .unwrap_or_default();
+    tier_at(license, LICENSE_PUBLIC_KEY, &device_id, Utc::now())
+}
+
+// Only a token that still lets this device run grants its tier.
+fn tier_at(
+    license: &License,
+    public_key: &str,
+    device_id: &str,
+    now: DateTime<Utc>
+) -> LicenseTier {
+    match token::check_license(license, public_key, device_id, now) {
+        Ok(TokenStatus::Valid | TokenStatus::Grace) => {
+            token::verify(&license.token, public_key)
+                .map(|claims| claims.tier)
+                .unwrap_or(LicenseTier::Unlicensed)
+        }
+        Ok(TokenStatus::Expired) | Err(_) => LicenseTier::Unlicensed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    fn paired(count: usize) -> HashMap<String, Client> {
+        (0..count)
+            .map(|i| {
+                let hwid = format!("station-{}", i);
+                (hwid.clone(), Client { hwid, ..Client::default() })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_client_limit_per_tier() {
+        let multi = LicenseTier::MultiStation { max_clients: 3 };
+        assert!(multi.check_client_limit(&paired(2), "station-new").is_ok());
+        assert!(multi.check_client_limit(&paired(3), "station-new").is_err());
+        assert!(multi.check_client_limit(&paired(3), "station-1").is_ok());
+
+        let single = LicenseTier::SingleStation;
+        assert!(single.check_client_limit(&paired(0), "station-new").is_ok());
+        let limit = single.check_client_limit(&paired(1), "station-new").unwrap_err();
+        assert_eq!(limit.feature, Feature::Clients);
+
+        assert!(LicenseTier::Unlicensed.check_client_limit(&paired(0), "station-new").is_err());
+    }
+
+    #[test]
+    fn test_client_limit_counts_paired_stations() {
+        let database = Database::open_in_memory().unwrap();
+        let pair = |hwid: &str, address: &str| {
+            let paired = database.clients().all().unwrap();
+            LicenseTier::MultiStation { max_clients: 2 }.check_client_limit(&paired, hwid)?;
+            let client = Client {
+                hwid: hwid.to_string(),
+                address: address.to_string(),
+                ..Client::default()
+            };
+            database.clients().upsert(&client).unwrap();
+            Ok::<(), LimitReached>(())
+        };
+        assert!(pair("station-a", "http://192.168.1.11:3000").is_ok());
+        assert!(pair("station-b", "http://192.168.1.12:3000").is_ok());
+        // Moved to another address, still the same station
+        assert!(pair("station-a", "http://192.168.1.21:3000").is_ok());
+        assert!(pair("station-c", "http://192.168.1.13:3000").is_err());
+        assert_eq!(database.clients().all().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_trial_disables_server_features() {
+        let trial = LicenseTier::Trial { expires_at: Utc::now() };
+        assert!(trial.check_feature(Feature::Tls).is_err());
+        assert!(trial.check_feature(Feature::Metrics).is_err());
+        assert!(trial.check_feature(Feature::Clients).is_ok());
+        assert!(LicenseTier::SingleStation.check_feature(Feature::Tls).is_ok());
+    }
+
+    #[test]
+    fn test_tier_is_read_from_the_token_claims() {
+        let tier: LicenseTier = serde_json
+            ::from_str(r#"{ "kind": "multi_station", "max_clients": 5 }"#)
+            .unwrap();
+        assert_eq!(tier, LicenseTier::MultiStation { max_clients: 5 });
+        assert_eq!(tier_of(&License::default()), LicenseTier::Unlicensed);
+        assert_eq!(
+            serde_json::to_value(LicenseTier::Unlicensed).unwrap(),
+            serde_json::json!({ "kind": "unlicensed" })
+        );
+    }
+
+    #[test]
+    fn test_expired_or_foreign_token_is_unlicensed() {
+        let now = Utc::now();
+        let claims = token::LicenseClaims {
+            serial_number: "SERIAL-123".to_string(),
+            device_id: "device-a".to_string(),
+            issued_at: now,
+            expires_at: now + chrono::Duration::days(30),
+            tier: LicenseTier::MultiStation { max_clients: 5 },
+            grace_until: None,
+        };
+        let license = License {
+            serial_number: "SERIAL-123".to_string(),
+            token: token::sign(&claims, &token::testing::key_pair()),
+            ..License::default()
+        };
+        let public_key = token::testing::public_key();
+
+        assert_eq!(tier_at(&license, &public_key, "device-a", now), claims.tier);
+        assert_eq!(tier_at(&license, &public_key, "device-b", now), LicenseTier::Unlicensed);
+        let later = now + chrono::Duration::days(31);
+        assert_eq!(tier_at(&license, &public_key, "device-a", later), LicenseTier::Unlicensed);
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{ Context, Result };
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{ DateTime, Duration, Utc };
+use ring::signature::{ UnparsedPublicKey, ED25519 };
+use serde::{ Deserialize, Serialize };
+
+use super::fingerprint::device_matches;
+use super::tier::LicenseTier;
+use crate::settings::appconfigmodels::{ License, LicensePolicy };
+
+// Offline grace period given to the tokens issued in tests.
+#[cfg(test)]
+const GRACE_DAYS: i64 = 7;
+
+// Ed25519 public key of the license server (base64url). Tokens are signed
+// with the matching private key, which never ships with the app. Set at build
+// time, see build.rs.
+pub const LICENSE_PUBLIC_KEY: &str = env!("LICENSE_PUBLIC_KEY");
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LicenseClaims {
+    pub serial_number: String,
+    pub device_id: String,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default)]
+    pub tier: LicenseTier,
+    // End of the offline grace period. Tokens without one have none.
+    #[serde(default)]
+    pub grace_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenStatus {
+    Valid,
+    // Expired, but still inside the offline grace period.
+    Grace,
+    Expired,
+}
+
+impl LicenseClaims {
+    pub fn status(&self, now: DateTime<Utc>) -> TokenStatus {
+        if let LicenseTier::Trial { expires_at } = self.tier {
+            if now >= expires_at {
+                return TokenStatus::Expired;
+            }
+        }
+
+        if now < self.expires_at {
+            TokenStatus::Valid
+        } else if self.grace_until.is_some_and(|grace_until| now < grace_until) {
+            TokenStatus::Grace
+        } else {
+            TokenStatus::Expired
+        }
+    }
+
+    pub fn renewal_due(&self, now: DateTime<Utc>, policy: &LicensePolicy) -> bool {
+        now >= self.issued_at + Duration::hours(policy.renew_interval_hours as i64)
+    }
+}
+
+// Tokens have the form `base64url(claims json).base64url(signature)` where the
+// signature covers the first part as sent.
+pub fn verify(token: &str, public_key: &str) -> Result<LicenseClaims> {
+    let (payload, signature) = token
+        .split_once('.')
+        .with_context(|| "License token is malformed")?;
+    let public_key = URL_SAFE_NO_PAD
+        .decode(public_key)
+        .with_context(|| "License public key is malformed")?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .with_context(|| "License token signature is malformed")?;
+
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(payload.as_bytes(), &signature)
+        .map_err(|_| anyhow::anyhow!("License token signature is invalid"))?;
+
+    let claims = URL_SAFE_NO_PAD
+        .decode(payload)
+        .with_context(|| "License token payload is malformed")?;
+    serde_json::from_slice(&claims).with_context(|| "License token claims are malformed")
+}
+
+// Checks the stored license token offline. The `authorized` flag stored next
+// to it is never trusted on its own.
+pub fn check_license(
+    license: &License,
+    public_key: &str,
+    device_id: &str,
+    now: DateTime<Utc>
+) -> Result<TokenStatus> {
+    let claims = verify(&license.token, public_key)?;
+
+    if claims.serial_number != license.serial_number {
+        return Err(anyhow::anyhow!("License token was issued for another serial number"));
+    }
+    if !device_matches(&claims.device_id, device_id) {
+        return Err(anyhow::anyhow!("License token was issued for another device"));
+    }
+
+    Ok(claims.status(now))
+}
+
+#[cfg(test)]
+pub fn sign(claims: &LicenseClaims, key_pair: &ring::signature::Ed25519KeyPair) -> String {
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).unwrap());
+    let signature = key_pair.sign(payload.as_bytes());
+    format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature.as_ref()))
+}
+
+// Mirrors what the license server does when asked for a token, signed with
+// the test key.
+#[cfg(test)]
+pub fn issue_for_tests(
+    table: &super::backend::SerialNumbersTable,
+    device_id: &str
+) -> Result<String> {
+    if table.revoked || !table.active || table.device_id.as_deref() != Some(device_id) {
+        return Err(anyhow::anyhow!("Serial number is not active on this device"));
+    }
+
+    let now = Utc::now();
+    let claims = LicenseClaims {
+        serial_number: table.serial_number.clone(),
+        device_id: device_id.to_string(),
+        issued_at: now,
+        expires_at: now + Duration::days(30),
+        tier: LicenseTier::default(),
+        grace_until: Some(now + Duration::days(30 + GRACE_DAYS)),
+    };
+    Ok(sign(&claims, &testing::key_pair()))
+}
+
+#[cfg(test)]
+pub mod testing {
+    use base64::Engine;
+    use ring::signature::{ Ed25519KeyPair, KeyPair };
+
+    pub fn key_pair() -> Ed25519KeyPair {
+        Ed25519KeyPair::from_seed_unchecked(&[7u8; 32]).unwrap()
+    }
+
+    pub fn public_key() -> String {
+        super::URL_SAFE_NO_PAD.encode(key_pair().public_key().as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(now: DateTime<Utc>) -> LicenseClaims {
+        LicenseClaims {
+            serial_number: "SERIAL-123".to_string(),
+            device_id: "device-a".to_string(),
+            issued_at: now,
+            expires_at: now + Duration::days(30),
+            tier: LicenseTier::default(),
+            grace_until: Some(now + Duration::days(30 + GRACE_DAYS)),
+        }
+    }
+
+    fn license(token: String) -> License {
+        License {
+            authorized: true,
+            serial_number: "SERIAL-123".to_string(),
+            email_address: "mpguser004@gmail.com".to_string(),
+            token,
+            last_validated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_verify_round_trip() {
+        let claims = claims(Utc::now());
+        let token = sign(&claims, &testing::key_pair());
+        assert_eq!(verify(&token, &testing::public_key()).unwrap(), claims);
+        assert!(verify(&token, LICENSE_PUBLIC_KEY).is_err());
+    }
+
+    #[test]
+    fn test_tampered_token_is_rejected() {
+        let token = sign(&claims(Utc::now()), &testing::key_pair());
+        let (_, signature) = token.split_once('.').unwrap();
+
+        let mut forged = claims(Utc::now());
+        forged.expires_at += Duration::days(3650);
+        let forged_payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap());
+        let forged_token = format!("{}.{}", forged_payload, signature);
+        assert!(verify(&forged_token, &testing::public_key()).is_err());
+        assert!(verify("not-a-token", &testing::public_key()).is_err());
+    }
+
+    #[test]
+    fn test_status_honours_grace_period() {
+        let now = Utc::now();
+        let claims = claims(now);
+        let policy = LicensePolicy::default();
+        assert_eq!(claims.status(now), TokenStatus::Valid);
+        assert_eq!(claims.status(now + Duration::days(31)), TokenStatus::Grace);
+        assert_eq!(claims.status(now + Duration::days(38)), TokenStatus::Expired);
+
+        let no_grace = LicenseClaims { grace_until: None, ..claims.clone() };
+        assert_eq!(no_grace.status(now + Duration::days(31)), TokenStatus::Expired);
+
+        let trial = LicenseClaims {
+            tier: LicenseTier::Trial { expires_at: now + Duration::days(10) },
+            ..claims.clone()
+        };
+        assert_eq!(trial.status(now), TokenStatus::Valid);
+        assert_eq!(trial.status(now + Duration::days(10)), TokenStatus::Expired);
+
+        assert!(!claims.renewal_due(now + Duration::hours(23), &policy));
+        assert!(claims.renewal_due(now + Duration::hours(24), &policy));
+    }
+
+    #[test]
+    fn test_check_license_binds_serial_and_device() {
+        let now = Utc::now();
+        let token = sign(&claims(now), &testing::key_pair());
+        let public_key = testing::public_key();
+
+        let valid = license(token.clone());
+        assert_eq!(
+            check_license(&valid, &public_key, "device-a", now).unwrap(),
+            TokenStatus::Valid
+        );
+        assert!(check_license(&valid, &public_key, "device-b", now).is_err());
+
+        let mut other_serial = license(token);
+        other_serial.serial_number = "SERIAL-456".to_string();
+        assert!(check_license(&other_serial, &public_key, "device-a", now).is_err());
+
+        assert!(check_license(&license(String::new()), &public_key, "device-a", now).is_err());
+    }
+}
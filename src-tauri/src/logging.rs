@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::{ Path, PathBuf };
+
+use anyhow::{ Context, Result };
+use chrono::Utc;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{ RollingFileAppender, Rotation };
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{ fmt, EnvFilter };
+
+use crate::settings::appconfig;
+
+const LOG_FILE_PREFIX: &str = "cps";
+const LOG_FILE_SUFFIX: &str = "log";
+const MAX_LOG_FILES: usize = 14;
+// Used unless `RUST_LOG` is set, e.g. `RUST_LOG=centralized_pisonet_app_lib::licensing=trace`.
+const DEFAULT_FILTER: &str = "info,centralized_pisonet_app_lib=debug";
+// Keeps an export small enough to attach to a support ticket.
+pub const EXPORT_MAX_BYTES: usize = 2 * 1024 * 1024;
+
+pub fn log_dir() -> PathBuf {
+    appconfig::config_dir().join("logs")
+}
+
+// Writes `tracing` events to a daily rolling file beside the config, and to
+// stdout in debug builds since release builds on Windows have no console.
+// The returned guard flushes the file when dropped, keep it for the lifetime
+// of the app.
+pub fn init() -> Result<WorkerGuard> {
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(log_dir())
+        .with_context(|| "Failed to create the log file")?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        EnvFilter::new(DEFAULT_FILTER)
+    });
+    tracing_subscriber
+        ::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(writer).with_ansi(false))
+        .with(cfg!(debug_assertions).then(fmt::layer))
+        .try_init()
+        .with_context(|| "Failed to install the logger")?;
+
+    Ok(guard)
+}
+
+// Copies the most recent log lines, at most `max_bytes`, into a single file in
+// `dir` and returns its path.
+pub fn export(dir: &Path, max_bytes: usize) -> Result<PathBuf> {
+    let contents = recent_logs(dir, max_bytes)?;
+    let timestamp = Utc::now().format("%Y%m%d-%H%M%S");
+    let path = dir.join(format!("{}-export-{}.txt", LOG_FILE_PREFIX, timestamp));
+    fs::write(&path, contents).with_context(|| "Failed to write the log export")?;
+    Ok(path)
+}
+
+fn recent_logs(dir: &Path, max_bytes: usize) -> Result<String> {
+    let mut files: Vec<PathBuf> = fs
+        ::read_dir(dir)
+        .with_context(|| "Failed to read the log directory")?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_log_file(path))
+        .collect();
+    // The file names end with the date, so this is oldest first.
+    files.sort();
+
+    let mut chunks = Vec::new();
+    let mut remaining = max_bytes;
+    for path in files.iter().rev() {
+        if remaining == 0 {
+            break;
+        }
+
+        let contents = fs::read(path).with_context(|| "Failed to read a log file")?;
+        let mut start = contents.len().saturating_sub(remaining);
+        if start > 0 {
+            // Do not start in the middle of a line.
+            start = contents[start..]
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .map_or(contents.len(), |newline| start + newline + 1);
+        }
+        remaining = remaining.saturating_sub(contents.len() - start);
+        chunks.push(String::from_utf8_lossy(&contents[start..]).into_owned());
+    }
+
+    chunks.reverse();
+    Ok(chunks.concat())
+}
+
+fn is_log_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            name.starts_with(&format!("{}.", LOG_FILE_PREFIX)) &&
+                name.ends_with(&format!(".{}", LOG_FILE_SUFFIX))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_dir_with(files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cps-logs-{}", unique_suffix()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    fn unique_suffix() -> u128 {
+        std::time::SystemTime
+            ::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    }
+
+    #[test]
+    fn test_recent_logs_keeps_newest_whole_lines() {
+        let dir = log_dir_with(
+            &[
+                ("cps.2026-10-17.log", "old line\n"),
+                ("cps.2026-10-18.log", "first line\nsecond line\n"),
+                ("cps.2026-10-19.log", "third line\n"),
+                ("notes.txt", "not a log\n"),
+            ]
+        );
+
+        let all = recent_logs(&dir, EXPORT_MAX_BYTES).unwrap();
+        assert_eq!(all, "old line\nfirst line\nsecond line\nthird line\n");
+
+        let recent = recent_logs(&dir, "second line\nthird line\n".len() + 3).unwrap();
+        assert_eq!(recent, "second line\nthird line\n");
+
+        let exported = export(&dir, EXPORT_MAX_BYTES).unwrap();
+        assert_eq!(fs::read_to_string(&exported).unwrap(), all);
+        assert!(!is_log_file(&exported));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
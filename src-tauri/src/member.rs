@@ -0,0 +1,327 @@
+use std::fmt;
+use std::num::NonZeroU32;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::pbkdf2::{ self, PBKDF2_HMAC_SHA256 };
+use ring::rand::{ SecureRandom, SystemRandom };
+use serde::{ Deserialize, Serialize };
+use tracing::warn;
+
+use crate::db::Database;
+use crate::http_server;
+use crate::settings::appconfigmodels::Client;
+
+const PIN_ITERATIONS: u32 = 100_000;
+const PIN_LENGTH: std::ops::RangeInclusive<usize> = 4..=8;
+const USERNAME_LENGTH: std::ops::RangeInclusive<usize> = 3..=32;
+
+// Why a member could not sign in or out. Serialized as `{ "kind": ..., ... }`
+// for the lock screen and between stations.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MemberError {
+    NotFound,
+    // Unknown username or wrong PIN, never told apart so the answer does not
+    // tell whether an account exists.
+    WrongCredentials,
+    // Too many wrong PINs for this username from this station.
+    TooManyAttempts,
+    // Signed in on another station, which holds the balance.
+    SignedIn {
+        station: String,
+    },
+    // The session token is not the one of the current sign in.
+    NotSignedIn,
+    // Neither this station nor a paired one could be asked.
+    Unavailable {
+        message: String,
+    },
+}
+
+impl MemberError {
+    pub fn unavailable(message: impl fmt::Display) -> Self {
+        MemberError::Unavailable { message: message.to_string() }
+    }
+}
+
+impl fmt::Display for MemberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemberError::NotFound => write!(f, "Member not found"),
+            MemberError::WrongCredentials => write!(f, "Wrong username or PIN"),
+            MemberError::TooManyAttempts => {
+                write!(f, "Too many wrong PINs, please try again later")
+            }
+            MemberError::SignedIn { station } => {
+                write!(f, "Already signed in on {}", station)
+            }
+            MemberError::NotSignedIn => write!(f, "Member is not signed in"),
+            MemberError::Unavailable { message } => {
+                write!(f, "Member account could not be reached: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MemberError {}
+
+// Salted PBKDF2 hash of a PIN, never the PIN itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credentials {
+    pub salt: Vec<u8>,
+    pub hash: Vec<u8>,
+}
+
+impl Credentials {
+    pub fn new(pin: &str) -> anyhow::Result<Self> {
+        let mut salt = vec![0u8; 16];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .map_err(|_| anyhow::anyhow!("Failed to generate PIN salt"))?;
+        let mut hash = vec![0u8; ring::digest::SHA256_OUTPUT_LEN];
+        pbkdf2::derive(PBKDF2_HMAC_SHA256, iterations(), &salt, pin.as_bytes(), &mut hash);
+        Ok(Credentials { salt, hash })
+    }
+
+    pub fn verify(&self, pin: &str) -> bool {
+        pbkdf2
+            ::verify(PBKDF2_HMAC_SHA256, iterations(), &self.salt, pin.as_bytes(), &self.hash)
+            .is_ok()
+    }
+}
+
+fn iterations() -> NonZeroU32 {
+    NonZeroU32::new(PIN_ITERATIONS).unwrap()
+}
+
+// Letters, digits, `.`, `_` and `-`, so names survive being typed on any
+// station.
+pub fn validate_username(username: &str) -> anyhow::Result<()> {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-');
+    if !USERNAME_LENGTH.contains(&username.len()) || !username.chars().all(allowed) {
+        return Err(
+            anyhow::anyhow!(
+                "Usernames have {} to {} letters, digits, dots, dashes or underscores",
+                USERNAME_LENGTH.start(),
+                USERNAME_LENGTH.end()
+            )
+        );
+    }
+    Ok(())
+}
+
+pub fn validate_pin(pin: &str) -> anyhow::Result<()> {
+    if !PIN_LENGTH.contains(&pin.len()) || !pin.chars().all(|c| c.is_ascii_digit()) {
+        return Err(
+            anyhow::anyhow!("PINs have {} to {} digits", PIN_LENGTH.start(), PIN_LENGTH.end())
+        );
+    }
+    Ok(())
+}
+
+// A member signed in on this station. `home` is the paired station keeping
+// the account, `None` when it is kept here.
+#[derive(Debug, Clone)]
+pub struct MemberSession {
+    pub username: String,
+    pub token: String,
+    pub home: Option<Client>,
+}
+
+// Member using this station. `SigningIn` holds the place while the account
+// is checked out, so a second sign in started meanwhile is refused.
+#[derive(Debug, Clone, Default)]
+pub enum MemberSlot {
+    #[default]
+    Empty,
+    SigningIn,
+    SignedIn(MemberSession),
+}
+
+impl MemberSlot {
+    pub fn session(&self) -> Option<&MemberSession> {
+        match self {
+            MemberSlot::SignedIn(session) => Some(session),
+            _ => None,
+        }
+    }
+
+    // Takes the signed in session, leaving a sign in under way alone.
+    pub fn take(&mut self) -> Option<MemberSession> {
+        match std::mem::take(self) {
+            MemberSlot::SignedIn(session) => Some(session),
+            other => {
+                *self = other;
+                None
+            }
+        }
+    }
+}
+
+// Signs in on the station keeping the account and returns the balance
+// handed to this station for the session.
+pub async fn sign_in(
+    database: &Database,
+    username: &str,
+    pin: &str,
+    station: &str
+) -> Result<(MemberSession, u64), MemberError> {
+    match sign_in_here(database, username, pin, station, None) {
+        Err(MemberError::WrongCredentials) => {}
+        result => {
+            return result.map(|(token, seconds)| {
+                (MemberSession { username: username.to_string(), token, home: None }, seconds)
+            });
+        }
+    }
+
+    let stations = database.clients().all().map_err(MemberError::unavailable)?;
+    let mut unavailable = None;
+    for paired in stations.values() {
+        match http_server::client::sign_in_member(database, paired, username, pin, station).await {
+            Err(MemberError::WrongCredentials) => {}
+            Err(MemberError::Unavailable { message }) => {
+                warn!(station = paired.address, "Failed to ask for member: {}", message);
+                unavailable = Some(MemberError::Unavailable { message });
+            }
+            result => {
+                return result.map(|(token, seconds)| {
+                    let session = MemberSession {
+                        username: username.to_string(),
+                        token,
+                        home: Some(paired.clone()),
+                    };
+                    (session, seconds)
+                });
+            }
+        }
+    }
+    Err(unavailable.unwrap_or(MemberError::WrongCredentials))
+}
+
+// Checks the PIN of an account kept on this station and hands its balance to
+// `station`, which is this one or reached through the paired `peer`. Returns
+// the session token and the balance.
+pub fn sign_in_here(
+    database: &Database,
+    username: &str,
+    pin: &str,
+    station: &str,
+    peer: Option<&str>
+) -> Result<(String, u64), MemberError> {
+    let members = database.members();
+    let Some(credentials) = members.credentials(username).map_err(MemberError::unavailable)? else {
+        // As slow as a wrong PIN, so the time taken does not tell either
+        let unknown = Credentials {
+            salt: vec![0u8; 16],
+            hash: vec![0u8; ring::digest::SHA256_OUTPUT_LEN],
+        };
+        let _ = unknown.verify(pin);
+        return Err(MemberError::WrongCredentials);
+    };
+    if !credentials.verify(pin) {
+        return Err(MemberError::WrongCredentials);
+    }
+
+    let token = new_token().map_err(MemberError::unavailable)?;
+    let seconds = match members.check_out(username, station, peer, &token) {
+        Ok(Err(MemberError::NotFound)) => Err(MemberError::WrongCredentials),
+        Ok(result) => result,
+        Err(e) => Err(MemberError::unavailable(e)),
+    }?;
+    Ok((token, seconds))
+}
+
+// Gives the time left back to the account. Returns the new balance.
+pub async fn sign_out(
+    database: &Database,
+    session: &MemberSession,
+    seconds: u64,
+    station: &str
+) -> Result<u64, MemberError> {
+    match &session.home {
+        None => sign_out_here(database, &session.username, &session.token, None, seconds),
+        Some(home) => {
+            http_server::client::sign_out_member(database, home, session, seconds, station).await
+        }
+    }
+}
+
+// Gives the time left back to an account kept on this station, signed in here
+// or through the paired `peer`. Returns the new balance.
+pub fn sign_out_here(
+    database: &Database,
+    username: &str,
+    token: &str,
+    peer: Option<&str>,
+    seconds: u64
+) -> Result<u64, MemberError> {
+    database.members().check_in(username, peer, token, seconds).map_err(MemberError::unavailable)?
+}
+
+fn new_token() -> anyhow::Result<String> {
+    let mut token = [0u8; 24];
+    SystemRandom::new()
+        .fill(&mut token)
+        .map_err(|_| anyhow::anyhow!("Failed to generate session token"))?;
+    Ok(URL_SAFE_NO_PAD.encode(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_credentials_verify_only_the_same_pin() {
+        let credentials = Credentials::new("1234").unwrap();
+        assert!(credentials.verify("1234"));
+        assert!(!credentials.verify("4321"));
+        assert_ne!(credentials.salt, Credentials::new("1234").unwrap().salt);
+
+        assert!(validate_pin("123").is_err());
+        assert!(validate_pin("12a4").is_err());
+        assert!(validate_username("juan.dela-cruz").is_ok());
+        assert!(validate_username("juan dela cruz").is_err());
+    }
+
+    #[test]
+    fn test_slot_is_held_while_signing_in() {
+        let mut slot = MemberSlot::SigningIn;
+        assert!(slot.take().is_none());
+        assert!(matches!(slot, MemberSlot::SigningIn));
+        assert!(slot.session().is_none());
+
+        let session = MemberSession {
+            username: "juan".to_string(),
+            token: "token".to_string(),
+            home: None,
+        };
+        slot = MemberSlot::SignedIn(session);
+        assert_eq!(slot.take().map(|session| session.username), Some("juan".to_string()));
+        assert!(matches!(slot, MemberSlot::Empty));
+    }
+
+    #[tokio::test]
+    async fn test_member_signs_in_and_out_on_the_keeping_station() {
+        let database = Database::open_in_memory().unwrap();
+        let credentials = Credentials::new("1234").unwrap();
+        database.members().create("juan", &credentials, Utc::now()).unwrap();
+        database.members().top_up("juan", 3600).unwrap().unwrap();
+
+        assert_eq!(
+            sign_in(&database, "juan", "0000", "station-01").await.unwrap_err(),
+            MemberError::WrongCredentials
+        );
+        let (session, seconds) = sign_in(&database, "juan", "1234", "station-01").await.unwrap();
+        assert!(session.home.is_none());
+        assert_eq!(seconds, 3600);
+
+        assert_eq!(sign_out(&database, &session, 1500, "station-01").await, Ok(1500));
+        assert_eq!(
+            sign_in(&database, "pedro", "1234", "station-01").await.unwrap_err(),
+            MemberError::WrongCredentials
+        );
+    }
+}
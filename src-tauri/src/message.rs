@@ -0,0 +1,293 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{ DateTime, Utc };
+use ring::rand::{ SecureRandom, SystemRandom };
+use serde::{ Deserialize, Serialize };
+use tokio::task::JoinSet;
+use tracing::error;
+
+use crate::counter::StationOverview;
+use crate::db::DatabaseState;
+use crate::http_server::client;
+use crate::settings::appconfigmodels::Client;
+
+pub const MAX_MESSAGE_LENGTH: usize = 500;
+pub const CLOSING_MESSAGE: &str =
+    "We are closing in 15 minutes. Please save your work and get ready to log off.";
+// Older sent messages are dropped from the counter window.
+const SENT_MESSAGES_KEPT: usize = 50;
+// The oldest message is dropped from the station when more arrive.
+pub const MAX_PENDING_MESSAGES: usize = 20;
+
+// Text sent from the counter, shown on the station until the customer
+// acknowledges it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StationMessage {
+    // Chosen by the counter, the same on every station it was sent to.
+    pub id: String,
+    pub text: String,
+    pub sent_at: DateTime<Utc>,
+    // Also bring the station window to the front and flash it in the taskbar.
+    pub bring_to_front: bool,
+}
+
+impl StationMessage {
+    pub fn new(text: &str, bring_to_front: bool) -> anyhow::Result<Self> {
+        let text = text.trim();
+        if text.is_empty() || text.chars().count() > MAX_MESSAGE_LENGTH {
+            return Err(
+                anyhow::anyhow!("Messages have 1 to {} characters", MAX_MESSAGE_LENGTH)
+            );
+        }
+        let mut id = [0u8; 9];
+        SystemRandom::new()
+            .fill(&mut id)
+            .map_err(|_| anyhow::anyhow!("Failed to generate message id"))?;
+        Ok(StationMessage {
+            id: URL_SAFE_NO_PAD.encode(id),
+            text: text.to_string(),
+            sent_at: Utc::now(),
+            bring_to_front,
+        })
+    }
+}
+
+// Messages this station shows until they are acknowledged.
+#[derive(Debug, Default)]
+pub struct Inbox(Mutex<Messages>);
+
+#[derive(Debug, Default)]
+struct Messages {
+    pending: Vec<StationMessage>,
+    // Ids reported to the counter, as many as it keeps sent messages.
+    acknowledged: VecDeque<String>,
+}
+
+impl Inbox {
+    // Returns `false` when the message was already received.
+    pub fn receive(&self, message: StationMessage) -> bool {
+        let mut messages = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let received =
+            messages.pending.iter().any(|received| received.id == message.id) ||
+            messages.acknowledged.contains(&message.id);
+        if received {
+            return false;
+        }
+        if messages.pending.len() >= MAX_PENDING_MESSAGES {
+            messages.pending.remove(0);
+        }
+        messages.pending.push(message);
+        true
+    }
+
+    pub fn acknowledge(&self, id: &str) -> bool {
+        let mut messages = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let before = messages.pending.len();
+        messages.pending.retain(|message| message.id != id);
+        if messages.pending.len() == before {
+            return false;
+        }
+        if messages.acknowledged.len() >= SENT_MESSAGES_KEPT {
+            messages.acknowledged.pop_front();
+        }
+        messages.acknowledged.push_back(id.to_string());
+        true
+    }
+
+    pub fn pending(&self) -> Vec<StationMessage> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).pending.clone()
+    }
+
+    pub fn acknowledged(&self) -> Vec<String> {
+        let messages = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        messages.acknowledged.iter().cloned().collect()
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum Delivery {
+    // Shown on the station, not acknowledged yet.
+    Delivered,
+    Acknowledged,
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Recipient {
+    pub hwid: String,
+    pub address: String,
+    pub delivery: Delivery,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SentMessage {
+    #[serde(flatten)]
+    pub message: StationMessage,
+    pub recipients: Vec<Recipient>,
+}
+
+// Messages sent from the counter, newest first. Acknowledgements are picked
+// up while listing the stations.
+#[derive(Debug, Default)]
+pub struct Outbox(Mutex<Vec<SentMessage>>);
+
+impl Outbox {
+    pub fn add(&self, sent: SentMessage) {
+        let mut messages = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        messages.insert(0, sent);
+        messages.truncate(SENT_MESSAGES_KEPT);
+    }
+
+    pub fn all(&self) -> Vec<SentMessage> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    // A delivered message is acknowledged once the station says so. Messages
+    // the station dropped or forgot on a restart stay delivered.
+    pub fn update(&self, stations: &[StationOverview]) {
+        let mut messages = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for sent in messages.iter_mut() {
+            for recipient in &mut sent.recipients {
+                let acknowledged = stations
+                    .iter()
+                    .find(|station| station.hwid == recipient.hwid && station.error.is_none())
+                    .is_some_and(|station| {
+                        station.acknowledged_messages.contains(&sent.message.id)
+                    });
+                if recipient.delivery == Delivery::Delivered && acknowledged {
+                    recipient.delivery = Delivery::Acknowledged;
+                }
+            }
+        }
+    }
+}
+
+// Sends `message` to every station of `stations` at once.
+pub async fn send(
+    database: DatabaseState,
+    stations: Vec<Client>,
+    message: StationMessage
+) -> SentMessage {
+    let mut requests = JoinSet::new();
+    for station in stations {
+        let database = database.clone();
+        let message = message.clone();
+        requests.spawn(async move {
+            let delivery = match client::send_station_message(&database, &station, &message).await {
+                Ok(()) => Delivery::Delivered,
+                Err(e) => Delivery::Failed { error: format!("{:#}", e) },
+            };
+            Recipient { hwid: station.hwid, address: station.address, delivery }
+        });
+    }
+
+    let mut recipients = Vec::new();
+    while let Some(recipient) = requests.join_next().await {
+        match recipient {
+            Ok(recipient) => recipients.push(recipient),
+            Err(e) => error!("Failed to send a station message: {}", e),
+        }
+    }
+    recipients.sort_by(|a, b| a.address.cmp(&b.address));
+    SentMessage { message, recipients }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overview(hwid: &str, acknowledged_messages: Vec<String>) -> StationOverview {
+        StationOverview {
+            hwid: hwid.to_string(),
+            address: format!("https://{}:3000", hwid),
+            station: Some(hwid.to_string()),
+            remaining_seconds: 0,
+            member: None,
+            pending_messages: Vec::new(),
+            acknowledged_messages,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_inbox_keeps_messages_until_acknowledged() {
+        let inbox = Inbox::default();
+        let message = StationMessage::new(CLOSING_MESSAGE, true).unwrap();
+        assert!(inbox.receive(message.clone()));
+        assert!(!inbox.receive(message.clone()));
+        assert_eq!(inbox.pending(), vec![message.clone()]);
+
+        assert!(inbox.acknowledge(&message.id));
+        assert!(!inbox.acknowledge(&message.id));
+        assert!(inbox.pending().is_empty());
+        assert_eq!(inbox.acknowledged(), vec![message.id.clone()]);
+        // Sent again after a timeout
+        assert!(!inbox.receive(message.clone()));
+
+        assert!(StationMessage::new("  ", false).is_err());
+        assert!(StationMessage::new(&"a".repeat(MAX_MESSAGE_LENGTH + 1), false).is_err());
+    }
+
+    #[test]
+    fn test_inbox_drops_the_oldest_message_when_full() {
+        let inbox = Inbox::default();
+        let messages: Vec<StationMessage> = (0..=MAX_PENDING_MESSAGES)
+            .map(|i| StationMessage::new(&format!("Message {}", i), false).unwrap())
+            .collect();
+        for message in &messages {
+            assert!(inbox.receive(message.clone()));
+        }
+        assert_eq!(inbox.pending(), messages[1..].to_vec());
+    }
+
+    #[test]
+    fn test_outbox_marks_messages_acknowledged_by_the_station() {
+        let message = StationMessage::new("Your time is almost up", false).unwrap();
+        let recipient = |hwid: &str, delivery: Delivery| Recipient {
+            hwid: hwid.to_string(),
+            address: format!("https://{}:3000", hwid),
+            delivery,
+        };
+        let outbox = Outbox::default();
+        outbox.add(SentMessage {
+            message: message.clone(),
+            recipients: vec![
+                recipient("station-01", Delivery::Delivered),
+                recipient("station-02", Delivery::Delivered),
+                recipient("station-03", Delivery::Failed { error: "offline".to_string() }),
+            ],
+        });
+
+        let mut offline = overview("station-02", vec![message.id.clone()]);
+        offline.error = Some("offline".to_string());
+        outbox.update(
+            &[
+                overview("station-01", vec![message.id.clone()]),
+                offline,
+                overview("station-03", vec![message.id.clone()]),
+            ]
+        );
+        let deliveries: Vec<Delivery> = outbox.all()[0].recipients
+            .iter()
+            .map(|recipient| recipient.delivery.clone())
+            .collect();
+        assert_eq!(
+            deliveries,
+            vec![
+                Delivery::Acknowledged,
+                Delivery::Delivered,
+                Delivery::Failed { error: "offline".to_string() }
+            ]
+        );
+
+        // Restarted, the message is neither pending nor acknowledged
+        outbox.update(&[overview("station-02", Vec::new())]);
+        assert_eq!(outbox.all()[0].recipients[1].delivery, Delivery::Delivered);
+    }
+}
@@ -0,0 +1,67 @@
+use std::sync::Mutex;
+
+use ring::rand::{ SecureRandom, SystemRandom };
+
+const CODE_DIGITS: u32 = 8;
+
+// Code a counter sends to `/register` to pair with this station, shown in the
+// station settings. It is replaced as soon as it is used, so a code read off
+// the screen pairs one client only.
+#[derive(Debug)]
+pub struct PairingCode(Mutex<String>);
+
+impl PairingCode {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(PairingCode(Mutex::new(generate()?)))
+    }
+
+    pub fn current(&self) -> String {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    // Returns `false` when `code` is not the current code.
+    pub fn take(&self, code: &str) -> anyhow::Result<bool> {
+        let mut current = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let matches =
+            current.len() == code.len() &&
+            current
+                .bytes()
+                .zip(code.bytes())
+                .fold(0, |difference, (a, b)| difference | (a ^ b)) == 0;
+        if !matches {
+            return Ok(false);
+        }
+        *current = generate()?;
+        Ok(true)
+    }
+}
+
+fn generate() -> anyhow::Result<String> {
+    let mut random = [0u8; 8];
+    SystemRandom::new()
+        .fill(&mut random)
+        .map_err(|_| anyhow::anyhow!("Failed to generate pairing code"))?;
+    let code = u64::from_le_bytes(random) % (10u64).pow(CODE_DIGITS);
+    Ok(format!("{:0width$}", code, width = CODE_DIGITS as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_pairs_once() {
+        let pairing = PairingCode::new().unwrap();
+        let code = pairing.current();
+        assert_eq!(code.len(), CODE_DIGITS as usize);
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+
+        assert!(!pairing.take("pair-id-123").unwrap());
+        assert!(!pairing.take("").unwrap());
+        assert_eq!(pairing.current(), code);
+
+        assert!(pairing.take(&code).unwrap());
+        assert!(!pairing.take(&code).unwrap());
+        assert_ne!(pairing.current(), code);
+    }
+}
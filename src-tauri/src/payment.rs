@@ -0,0 +1,221 @@
+use std::fmt;
+
+use serde::{ Deserialize, Serialize };
+use utoipa::ToSchema;
+
+use crate::settings::appconfigmodels::Pricing;
+
+// Where the money for station time came from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentSource {
+    Coin,
+    Bill,
+    Cashier,
+    // Time given away by a promotion, not revenue.
+    Promo,
+}
+
+impl PaymentSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentSource::Coin => "coin",
+            PaymentSource::Bill => "bill",
+            PaymentSource::Cashier => "cashier",
+            PaymentSource::Promo => "promo",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [PaymentSource::Coin, PaymentSource::Bill, PaymentSource::Cashier, PaymentSource::Promo]
+            .into_iter()
+            .find(|source| source.as_str() == value)
+    }
+
+    // Coins and bills come out of an acceptor, so only the denominations it
+    // is set up for are possible.
+    fn is_cash(&self) -> bool {
+        matches!(self, PaymentSource::Coin | PaymentSource::Bill)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Payment {
+    pub amount_centavos: u64,
+    // Face value of one coin or bill, `None` for cashier and promo amounts.
+    pub denomination_centavos: Option<u64>,
+    pub source: PaymentSource,
+}
+
+impl Payment {
+    pub fn cash(source: PaymentSource, denomination_centavos: u64) -> Self {
+        Payment {
+            amount_centavos: denomination_centavos,
+            denomination_centavos: Some(denomination_centavos),
+            source,
+        }
+    }
+
+    pub fn pesos(&self) -> f64 {
+        (self.amount_centavos as f64) / 100.0
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PaymentError {
+    ZeroAmount,
+    // Cashier and promo time is only given from this PC or the counter.
+    SourceNotAllowed {
+        source: PaymentSource,
+    },
+    MissingDenomination,
+    DenominationNotAccepted {
+        source: PaymentSource,
+        denomination_centavos: u64,
+    },
+    // The amount is not a whole number of coins or bills.
+    PartialDenomination {
+        amount_centavos: u64,
+        denomination_centavos: u64,
+    },
+    // Accepted but never turned into time, e.g. while the station shuts down.
+    NotCredited,
+}
+
+impl fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaymentError::ZeroAmount => write!(f, "Amount must be more than zero"),
+            PaymentError::SourceNotAllowed { source } => {
+                write!(f, "{} payments are not accepted from paired clients", source.as_str())
+            }
+            PaymentError::MissingDenomination => {
+                write!(f, "Coins and bills need a denomination")
+            }
+            PaymentError::DenominationNotAccepted { source, denomination_centavos } => {
+                write!(
+                    f,
+                    "{} of {} centavos is not accepted",
+                    source.as_str(),
+                    denomination_centavos
+                )
+            }
+            PaymentError::PartialDenomination { amount_centavos, denomination_centavos } => {
+                write!(
+                    f,
+                    "{} centavos is not a multiple of the {} centavo denomination",
+                    amount_centavos,
+                    denomination_centavos
+                )
+            }
+            PaymentError::NotCredited => write!(f, "Payment could not be credited"),
+        }
+    }
+}
+
+impl std::error::Error for PaymentError {}
+
+// Checks a coin or bill against the accepted denominations. Cashier and promo
+// amounts can be anything above zero.
+pub fn validate(pricing: &Pricing, payment: &Payment) -> Result<(), PaymentError> {
+    if payment.amount_centavos == 0 {
+        return Err(PaymentError::ZeroAmount);
+    }
+    if !payment.source.is_cash() {
+        return Ok(());
+    }
+
+    let denomination_centavos = payment.denomination_centavos.ok_or(
+        PaymentError::MissingDenomination
+    )?;
+    let accepted = pricing.accepted_denominations
+        .iter()
+        .any(|accepted| {
+            accepted.source == payment.source && accepted.centavos == denomination_centavos
+        });
+    if !accepted {
+        return Err(PaymentError::DenominationNotAccepted {
+            source: payment.source,
+            denomination_centavos,
+        });
+    }
+    if !payment.amount_centavos.is_multiple_of(denomination_centavos) {
+        return Err(PaymentError::PartialDenomination {
+            amount_centavos: payment.amount_centavos,
+            denomination_centavos,
+        });
+    }
+    Ok(())
+}
+
+// Checks a payment sent over HTTP, where anyone on the network could claim to
+// be the cashier. Only coins and bills are taken.
+pub fn validate_remote(pricing: &Pricing, payment: &Payment) -> Result<(), PaymentError> {
+    if !payment.source.is_cash() {
+        return Err(PaymentError::SourceNotAllowed { source: payment.source });
+    }
+    validate(pricing, payment)
+}
+
+// Station time bought by the payment, rounded down to whole seconds.
+pub fn seconds_for(pricing: &Pricing, payment: &Payment) -> u64 {
+    payment.amount_centavos.saturating_mul(pricing.seconds_per_peso) / 100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_against_accepted_denominations() {
+        let pricing = Pricing::default();
+        assert_eq!(validate(&pricing, &Payment::cash(PaymentSource::Bill, 5000)), Ok(()));
+        assert_eq!(
+            validate(&pricing, &Payment::cash(PaymentSource::Bill, 500)),
+            Err(PaymentError::DenominationNotAccepted {
+                source: PaymentSource::Bill,
+                denomination_centavos: 500,
+            })
+        );
+
+        // Three ₱5 coins at once
+        let coins = Payment { amount_centavos: 1500, ..Payment::cash(PaymentSource::Coin, 500) };
+        assert_eq!(validate(&pricing, &coins), Ok(()));
+        let partial = Payment { amount_centavos: 1250, ..coins };
+        assert!(
+            matches!(validate(&pricing, &partial), Err(PaymentError::PartialDenomination { .. }))
+        );
+
+        let cashier = Payment {
+            amount_centavos: 3750,
+            denomination_centavos: None,
+            source: PaymentSource::Cashier,
+        };
+        assert_eq!(validate(&pricing, &cashier), Ok(()));
+        assert_eq!(
+            validate(&pricing, &Payment { source: PaymentSource::Coin, ..cashier }),
+            Err(PaymentError::MissingDenomination)
+        );
+        assert_eq!(
+            validate(&pricing, &Payment { amount_centavos: 0, ..cashier }),
+            Err(PaymentError::ZeroAmount)
+        );
+
+        assert_eq!(
+            validate_remote(&pricing, &cashier),
+            Err(PaymentError::SourceNotAllowed { source: PaymentSource::Cashier })
+        );
+        assert_eq!(validate_remote(&pricing, &coins), Ok(()));
+    }
+
+    #[test]
+    fn test_seconds_for_amount() {
+        let pricing = Pricing::default();
+        assert_eq!(seconds_for(&pricing, &Payment::cash(PaymentSource::Bill, 2000)), 100);
+        let quarter = Payment { amount_centavos: 25, ..Payment::cash(PaymentSource::Coin, 25) };
+        assert_eq!(seconds_for(&pricing, &quarter), 1);
+        assert_eq!(PaymentSource::parse("promo"), Some(PaymentSource::Promo));
+        assert_eq!(PaymentSource::parse("gcash"), None);
+    }
+}
@@ -0,0 +1,195 @@
+use std::sync::atomic::{ AtomicU64, Ordering };
+
+use chrono::{ DateTime, Datelike, Local };
+use serde::{ Deserialize, Serialize };
+use utoipa::ToSchema;
+
+use crate::payment::{ Payment, PaymentSource };
+use crate::settings::appconfigmodels::{ Promotion, PromotionRule };
+
+// Bonus time a promotion gave on one payment.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AppliedPromotion {
+    /// Name of the promotion as set up on the station.
+    #[schema(example = "Morning happy hour")]
+    pub name: String,
+    #[schema(example = 60)]
+    pub bonus_seconds: u64,
+}
+
+// Time a payment turned into, with the promotions that added to it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Credit {
+    // Including the bonus time.
+    pub seconds: u64,
+    pub promotions: Vec<AppliedPromotion>,
+}
+
+impl Credit {
+    pub fn new(base_seconds: u64, promotions: Vec<AppliedPromotion>) -> Self {
+        let bonus: u64 = promotions
+            .iter()
+            .map(|promotion| promotion.bonus_seconds)
+            .sum();
+        Credit { seconds: base_seconds.saturating_add(bonus), promotions }
+    }
+}
+
+// What the rules look at besides the payment itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromotionContext {
+    // Local time, as the weekday and hours of a promotion are.
+    pub now: DateTime<Local>,
+    // Paid earlier in the running session.
+    pub session_spend_centavos: u64,
+    pub first_payment_today: bool,
+    pub member: bool,
+}
+
+// Money paid during the running session, for `PromotionRule::SessionSpend`.
+#[derive(Debug, Default)]
+pub struct SessionSpend(AtomicU64);
+
+impl SessionSpend {
+    // Returns what was paid before this payment. Free promo time does not
+    // count as spending.
+    pub fn add(&self, payment: &Payment) -> u64 {
+        if payment.source == PaymentSource::Promo {
+            return self.0.load(Ordering::SeqCst);
+        }
+        self.0.fetch_add(payment.amount_centavos, Ordering::SeqCst)
+    }
+
+    pub fn reset(&self) {
+        self.0.store(0, Ordering::SeqCst);
+    }
+}
+
+// Promotions the payment earns on top of `base_seconds`, in the order they
+// are set up. Free time from promos never earns more.
+pub fn apply(
+    promotions: &[Promotion],
+    payment: &Payment,
+    base_seconds: u64,
+    context: &PromotionContext
+) -> Vec<AppliedPromotion> {
+    if payment.source == PaymentSource::Promo {
+        return Vec::new();
+    }
+
+    promotions
+        .iter()
+        .filter(|promotion| promotion.enabled)
+        .filter_map(|promotion| {
+            let bonus_seconds = bonus_for(&promotion.rule, payment, base_seconds, context);
+            (bonus_seconds > 0).then(|| AppliedPromotion {
+                name: promotion.name.clone(),
+                bonus_seconds,
+            })
+        })
+        .collect()
+}
+
+fn bonus_for(
+    rule: &PromotionRule,
+    payment: &Payment,
+    base_seconds: u64,
+    context: &PromotionContext
+) -> u64 {
+    let percent_of = |percent: u64| base_seconds.saturating_mul(percent) / 100;
+    match rule {
+        // Only the payment that reaches the threshold gets the bonus
+        PromotionRule::SessionSpend { spend_centavos, bonus_seconds } => {
+            let before = context.session_spend_centavos;
+            let after = before.saturating_add(payment.amount_centavos);
+            if before < *spend_centavos && after >= *spend_centavos { *bonus_seconds } else { 0 }
+        }
+        PromotionRule::FirstPaymentOfDay { bonus_seconds } => {
+            if context.first_payment_today { *bonus_seconds } else { 0 }
+        }
+        PromotionRule::TimeOfDay { weekdays, from, to, bonus_percent } => {
+            let today = weekdays.is_empty() || weekdays.contains(&context.now.weekday());
+            let time = context.now.time();
+            if today && *from <= time && time < *to { percent_of(*bonus_percent) } else { 0 }
+        }
+        PromotionRule::Member { bonus_percent } => {
+            if context.member { percent_of(*bonus_percent) } else { 0 }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{ NaiveTime, TimeZone, Weekday };
+
+    fn promotion(name: &str, rule: PromotionRule) -> Promotion {
+        Promotion { name: name.to_string(), enabled: true, rule }
+    }
+
+    fn context(now: DateTime<Local>) -> PromotionContext {
+        PromotionContext {
+            now,
+            session_spend_centavos: 0,
+            first_payment_today: false,
+            member: false,
+        }
+    }
+
+    #[test]
+    fn test_spend_bonus_is_given_once_when_reaching_the_threshold() {
+        let promotions = [
+            promotion("₱20 spend", PromotionRule::SessionSpend {
+                spend_centavos: 2000,
+                bonus_seconds: 300,
+            }),
+            promotion("Early bird", PromotionRule::FirstPaymentOfDay { bonus_seconds: 60 }),
+        ];
+        let coin = Payment::cash(PaymentSource::Coin, 1000);
+        let now = Local::now();
+
+        assert!(apply(&promotions, &coin, 50, &context(now)).is_empty());
+        let reaching = PromotionContext { session_spend_centavos: 1000, ..context(now) };
+        assert_eq!(
+            apply(&promotions, &coin, 50, &reaching),
+            vec![AppliedPromotion { name: "₱20 spend".to_string(), bonus_seconds: 300 }]
+        );
+        let past = PromotionContext { session_spend_centavos: 2000, ..context(now) };
+        assert!(apply(&promotions, &coin, 50, &past).is_empty());
+
+        let first = PromotionContext { first_payment_today: true, ..context(now) };
+        assert_eq!(Credit::new(50, apply(&promotions, &coin, 50, &first)).seconds, 110);
+        let promo = Payment { source: PaymentSource::Promo, ..coin };
+        assert!(apply(&promotions, &promo, 50, &first).is_empty());
+    }
+
+    #[test]
+    fn test_weekday_morning_and_member_bonuses() {
+        let weekdays = vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+        let promotions = [
+            promotion("Weekday mornings", PromotionRule::TimeOfDay {
+                weekdays,
+                from: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                to: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+                bonus_percent: 20,
+            }),
+            promotion("Members", PromotionRule::Member { bonus_percent: 10 }),
+        ];
+        let bill = Payment::cash(PaymentSource::Bill, 5000);
+        // 2026-10-19 is a Monday
+        let monday_morning = Local.with_ymd_and_hms(2026, 10, 19, 9, 30, 0).unwrap();
+        let monday_noon = Local.with_ymd_and_hms(2026, 10, 19, 12, 0, 0).unwrap();
+        let sunday_morning = Local.with_ymd_and_hms(2026, 10, 18, 9, 30, 0).unwrap();
+
+        let applied = apply(&promotions, &bill, 250, &context(monday_morning));
+        assert_eq!(applied[0].bonus_seconds, 50);
+        assert!(apply(&promotions, &bill, 250, &context(monday_noon)).is_empty());
+        assert!(apply(&promotions, &bill, 250, &context(sunday_morning)).is_empty());
+
+        let member = PromotionContext { member: true, ..context(sunday_morning) };
+        assert_eq!(
+            apply(&promotions, &bill, 250, &member),
+            vec![AppliedPromotion { name: "Members".to_string(), bonus_seconds: 25 }]
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::sync::Mutex;
+
+use anyhow::Result;
+use chrono::{ DateTime, Utc };
+
+use crate::db::transactions::NewTransaction;
+use crate::db::{ Database, DatabaseState };
+use crate::payment::Payment;
+use crate::promotion::AppliedPromotion;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SalesEvent {
+    Payment {
+        payment: Payment,
+        // Including the bonus time of `promotions`.
+        seconds: u64,
+        promotions: Vec<AppliedPromotion>,
+    },
+    SessionStarted,
+    SessionEnded,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SalesRecord {
+    pub timestamp: DateTime<Utc>,
+    pub station: String,
+    pub event: SalesEvent,
+}
+
+// Records the payments and sessions of this station in the database so
+// reports can be rebuilt for any period.
+pub struct SalesLedger {
+    database: DatabaseState,
+    station: String,
+    // Id of the running session, closed by `SalesEvent::SessionEnded`.
+    session: Mutex<Option<i64>>,
+}
+
+impl SalesLedger {
+    pub fn new(database: DatabaseState, station: &str) -> Self {
+        SalesLedger {
+            database,
+            station: station.to_string(),
+            session: Mutex::new(None),
+        }
+    }
+
+    // Like the audit log, a failed write must not stop the station.
+    pub fn record(&self, event: SalesEvent) {
+        if let Err(e) = self.append(event, Utc::now()) {
+            tracing::error!("Failed to write sales record: {:#}", e);
+        }
+    }
+
+    // Time left on the running session, given back by `recover` if the
+    // station stops before the session ends.
+    pub fn checkpoint(&self, remaining_seconds: u64) {
+        let result = self.session
+            .lock()
+            .map_err(|e| anyhow::anyhow!(e.to_string()))
+            .and_then(|session| match *session {
+                Some(id) => self.database.sessions().checkpoint(id, remaining_seconds),
+                None => Ok(()),
+            });
+        if let Err(e) = result {
+            tracing::error!("Failed to write session checkpoint: {:#}", e);
+        }
+    }
+
+    // Takes all the time left off the station, zeroing the checkpoint with it
+    // so `recover` cannot give the time back a second time.
+    pub fn take_time(&self, remaining_time: &AtomicU64) -> u64 {
+        let seconds = remaining_time.swap(0, Ordering::SeqCst);
+        self.checkpoint(0);
+        seconds
+    }
+
+    // Adds (or with a negative value removes) time, writing the checkpoint
+    // with it so a removal is not undone by `recover`. Returns the time left.
+    pub fn adjust_time(&self, remaining_time: &AtomicU64, seconds: i64) -> u64 {
+        let change = seconds.unsigned_abs();
+        let adjust = |current: u64| {
+            if seconds >= 0 {
+                current.saturating_add(change)
+            } else {
+                current.saturating_sub(change)
+            }
+        };
+        let previous = remaining_time
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| Some(adjust(current)))
+            .unwrap_or_default();
+        let remaining_seconds = adjust(previous);
+        self.checkpoint(remaining_seconds);
+        remaining_seconds
+    }
+
+    // Undoes `take_time` when the time could not be kept elsewhere.
+    pub fn give_back(&self, remaining_time: &AtomicU64, seconds: u64) {
+        let remaining_seconds = remaining_time.fetch_add(seconds, Ordering::SeqCst) + seconds;
+        self.checkpoint(remaining_seconds);
+    }
+
+    pub fn last_payment_at(&self) -> Result<Option<DateTime<Utc>>> {
+        self.database.transactions().last_created_at(&self.station)
+    }
+
+    // Ends the sessions this station left running when it last stopped and
+    // returns the time they had left.
+    pub fn recover(&self) -> Result<u64> {
+        self.database.sessions().end_unfinished(&self.station, Utc::now())
+    }
+
+    fn append(&self, event: SalesEvent, timestamp: DateTime<Utc>) -> Result<()> {
+        let mut session = self.session.lock().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        match event {
+            SalesEvent::Payment { payment, seconds, promotions } => {
+                self.database.transactions().insert(&NewTransaction {
+                    station: &self.station,
+                    created_at: timestamp,
+                    payment,
+                    seconds,
+                    promotions: &promotions,
+                })?;
+            }
+            SalesEvent::SessionStarted => {
+                *session = Some(self.database.sessions().start(&self.station, timestamp)?);
+            }
+            SalesEvent::SessionEnded => {
+                if let Some(id) = session.take() {
+                    self.database.sessions().end(id, timestamp)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Payments and session starts of every station between `from` (inclusive) and
+// `to` (exclusive), in time order.
+pub fn records(
+    database: &Database,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>
+) -> Result<Vec<SalesRecord>> {
+    let sessions = database
+        .sessions()
+        .started_between(from, to)?
+        .into_iter()
+        .map(|session| SalesRecord {
+            timestamp: session.started_at,
+            station: session.station,
+            event: SalesEvent::SessionStarted,
+        });
+    let payments = database
+        .transactions()
+        .created_between(from, to)?
+        .into_iter()
+        .map(|transaction| SalesRecord {
+            timestamp: transaction.created_at,
+            station: transaction.station,
+            event: SalesEvent::Payment {
+                payment: transaction.payment,
+                seconds: transaction.seconds,
+                promotions: transaction.promotions,
+            },
+        });
+
+    let mut records: Vec<SalesRecord> = sessions.chain(payments).collect();
+    records.sort_by_key(|record| record.timestamp);
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payment::PaymentSource;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_records_are_read_back_in_order() {
+        let database = Arc::new(Database::open_in_memory().unwrap());
+        let ledger = SalesLedger::new(database.clone(), "station-01");
+        let now = Utc::now();
+        ledger.append(SalesEvent::SessionStarted, now).unwrap();
+        let early_bird = AppliedPromotion { name: "Early bird".to_string(), bonus_seconds: 60 };
+        let payment = SalesEvent::Payment {
+            payment: Payment::cash(PaymentSource::Coin, 100),
+            seconds: 65,
+            promotions: vec![early_bird],
+        };
+        ledger.append(payment.clone(), now).unwrap();
+        ledger.append(SalesEvent::SessionEnded, now).unwrap();
+
+        let records = records(&database, None, None).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].event, SalesEvent::SessionStarted);
+        assert_eq!(records[1].station, "station-01");
+        assert_eq!(records[1].event, payment);
+
+        let sessions = database.sessions().started_between(None, None).unwrap();
+        assert_eq!(sessions[0].ended_at, Some(now));
+    }
+
+    #[test]
+    fn test_saved_time_is_not_recovered() {
+        let database = Arc::new(Database::open_in_memory().unwrap());
+        let ledger = SalesLedger::new(database.clone(), "station-01");
+        ledger.append(SalesEvent::SessionStarted, Utc::now()).unwrap();
+        let remaining_time = AtomicU64::new(600);
+        ledger.checkpoint(600);
+
+        let seconds = ledger.take_time(&remaining_time);
+        crate::voucher::save_time(&database, "station-01", seconds, Utc::now()).unwrap();
+
+        // The station stops before the session ends
+        let restarted = SalesLedger::new(database, "station-01");
+        assert_eq!(restarted.recover().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_time_removed_by_hand_is_not_recovered() {
+        let database = Arc::new(Database::open_in_memory().unwrap());
+        let ledger = SalesLedger::new(database.clone(), "station-01");
+        ledger.append(SalesEvent::SessionStarted, Utc::now()).unwrap();
+        let remaining_time = AtomicU64::new(600);
+        ledger.checkpoint(600);
+
+        assert_eq!(ledger.adjust_time(&remaining_time, -500), 100);
+        assert_eq!(ledger.adjust_time(&remaining_time, -500), 0);
+
+        let restarted = SalesLedger::new(database, "station-01");
+        assert_eq!(restarted.recover().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_time_given_back_is_recovered() {
+        let database = Arc::new(Database::open_in_memory().unwrap());
+        let ledger = SalesLedger::new(database.clone(), "station-01");
+        ledger.append(SalesEvent::SessionStarted, Utc::now()).unwrap();
+        let remaining_time = AtomicU64::new(600);
+        ledger.checkpoint(600);
+
+        let seconds = ledger.take_time(&remaining_time);
+        ledger.give_back(&remaining_time, seconds);
+
+        let restarted = SalesLedger::new(database, "station-01");
+        assert_eq!(restarted.recover().unwrap(), 600);
+    }
+}
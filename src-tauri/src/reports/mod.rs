@@ -0,0 +1,35 @@
+pub mod ledger;
+pub mod summary;
+
+use anyhow::Result;
+use chrono::{ DateTime, Local, Utc };
+use serde::{ Deserialize, Serialize };
+
+use crate::db::Database;
+use summary::{ Granularity, ReportRow };
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+// Sales report of this station in the PC's local time, shared by the Tauri
+// commands and the HTTP endpoint.
+pub fn sales_report(
+    database: &Database,
+    granularity: Granularity,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>
+) -> Result<Vec<ReportRow>> {
+    Ok(summary::aggregate(&ledger::records(database, from, to)?, granularity, from, to, &Local))
+}
+
+pub fn render(rows: &[ReportRow], format: ReportFormat) -> Result<String> {
+    match format {
+        ReportFormat::Json => Ok(serde_json::to_string_pretty(rows)?),
+        ReportFormat::Csv => Ok(summary::to_csv(rows)),
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::{ DateTime, TimeZone, Utc };
+use serde::{ Deserialize, Serialize };
+use utoipa::ToSchema;
+
+use super::ledger::{ SalesEvent, SalesRecord };
+use crate::payment::PaymentSource;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    Hour,
+    #[default]
+    Day,
+    Month,
+}
+
+impl Granularity {
+    fn period<Tz: TimeZone>(&self, timestamp: &DateTime<Utc>, timezone: &Tz) -> String
+        where Tz::Offset: std::fmt::Display
+    {
+        let local = timestamp.with_timezone(timezone);
+        let format = match self {
+            Granularity::Hour => "%Y-%m-%d %H:00",
+            Granularity::Day => "%Y-%m-%d",
+            Granularity::Month => "%Y-%m",
+        };
+        local.format(format).to_string()
+    }
+}
+
+// Totals of one station over one period.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default, ToSchema)]
+pub struct ReportRow {
+    pub station: String,
+    // Start of the period in the station's local time, e.g. `2026-10-19` for
+    // a daily report.
+    pub period: String,
+    pub payments: u64,
+    // Coins, bills and cashier payments.
+    pub revenue_centavos: u64,
+    // Time given away by promotions, priced like a payment.
+    pub promo_centavos: u64,
+    pub seconds_sold: u64,
+    pub sessions: u64,
+}
+
+// Groups the records between `from` (inclusive) and `to` (exclusive) per
+// station and period. Periods follow `timezone` so a daily report matches the
+// owner's business day.
+pub fn aggregate<Tz: TimeZone>(
+    records: &[SalesRecord],
+    granularity: Granularity,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    timezone: &Tz
+) -> Vec<ReportRow>
+    where Tz::Offset: std::fmt::Display
+{
+    let mut rows: BTreeMap<(String, String), ReportRow> = BTreeMap::new();
+    let in_range = |record: &&SalesRecord| {
+        from.is_none_or(|from| record.timestamp >= from) &&
+            to.is_none_or(|to| record.timestamp < to)
+    };
+
+    for record in records.iter().filter(in_range) {
+        let period = granularity.period(&record.timestamp, timezone);
+        let row = rows.entry((record.station.clone(), period.clone())).or_insert_with(|| {
+            ReportRow { station: record.station.clone(), period, ..ReportRow::default() }
+        });
+        match &record.event {
+            SalesEvent::Payment { payment, seconds, .. } => {
+                row.payments += 1;
+                if payment.source == PaymentSource::Promo {
+                    row.promo_centavos += payment.amount_centavos;
+                } else {
+                    row.revenue_centavos += payment.amount_centavos;
+                }
+                row.seconds_sold += seconds;
+            }
+            SalesEvent::SessionStarted => {
+                row.sessions += 1;
+            }
+            SalesEvent::SessionEnded => {}
+        }
+    }
+
+    rows.into_values().collect()
+}
+
+pub fn to_csv(rows: &[ReportRow]) -> String {
+    let mut csv = String::from(
+        "station,period,payments,revenue_centavos,promo_centavos,seconds_sold,sessions\n"
+    );
+    for row in rows {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{}",
+            csv_field(&row.station),
+            csv_field(&row.period),
+            row.payments,
+            row.revenue_centavos,
+            row.promo_centavos,
+            row.seconds_sold,
+            row.sessions
+        );
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payment::Payment;
+    use chrono::FixedOffset;
+
+    fn record(station: &str, timestamp: &str, event: SalesEvent) -> SalesRecord {
+        SalesRecord {
+            timestamp: timestamp.parse().unwrap(),
+            station: station.to_string(),
+            event,
+        }
+    }
+
+    fn coin(pesos: u64) -> SalesEvent {
+        SalesEvent::Payment {
+            payment: Payment {
+                amount_centavos: pesos * 100,
+                ..Payment::cash(PaymentSource::Coin, 100)
+            },
+            seconds: pesos * 5,
+            promotions: Vec::new(),
+        }
+    }
+
+    fn promo(pesos: u64) -> SalesEvent {
+        SalesEvent::Payment {
+            payment: Payment {
+                amount_centavos: pesos * 100,
+                denomination_centavos: None,
+                source: PaymentSource::Promo,
+            },
+            seconds: pesos * 5,
+            promotions: Vec::new(),
+        }
+    }
+
+    fn records() -> Vec<SalesRecord> {
+        vec![
+            record("station-01", "2026-10-18T15:30:00Z", SalesEvent::SessionStarted),
+            record("station-01", "2026-10-18T15:30:00Z", coin(5)),
+            record("station-01", "2026-10-18T17:10:00Z", coin(1)),
+            record("station-02", "2026-10-18T17:20:00Z", SalesEvent::SessionStarted),
+            record("station-02", "2026-10-18T17:20:00Z", coin(10)),
+            record("station-02", "2026-10-18T17:20:00Z", promo(2))
+        ]
+    }
+
+    #[test]
+    fn test_aggregate_per_station_and_day() {
+        let rows = aggregate(&records(), Granularity::Day, None, None, &Utc);
+        assert_eq!(rows, vec![
+            ReportRow {
+                station: "station-01".to_string(),
+                period: "2026-10-18".to_string(),
+                payments: 2,
+                revenue_centavos: 600,
+                promo_centavos: 0,
+                seconds_sold: 30,
+                sessions: 1,
+            },
+            ReportRow {
+                station: "station-02".to_string(),
+                period: "2026-10-18".to_string(),
+                payments: 2,
+                revenue_centavos: 1000,
+                promo_centavos: 200,
+                seconds_sold: 60,
+                sessions: 1,
+            }
+        ]);
+    }
+
+    #[test]
+    fn test_periods_follow_the_local_day_and_range() {
+        // 17:10 UTC is already the next day in Manila
+        let manila = FixedOffset::east_opt(8 * 3600).unwrap();
+        let rows = aggregate(&records(), Granularity::Day, None, None, &manila);
+        let periods: Vec<&str> = rows
+            .iter()
+            .map(|row| row.period.as_str())
+            .collect();
+        assert_eq!(periods, vec!["2026-10-18", "2026-10-19", "2026-10-19"]);
+
+        let from = "2026-10-18T17:00:00Z".parse().ok();
+        let rows = aggregate(&records(), Granularity::Hour, from, None, &Utc);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].period, "2026-10-18 17:00");
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let rows = aggregate(&records(), Granularity::Month, None, None, &Utc);
+        let csv = to_csv(&rows);
+        assert_eq!(
+            csv,
+            "station,period,payments,revenue_centavos,promo_centavos,seconds_sold,sessions\n\
+             station-01,2026-10,2,600,0,30,1\n\
+             station-02,2026-10,2,1000,200,60,1\n"
+        );
+        assert_eq!(csv_field("Cafe, \"Main\""), "\"Cafe, \"\"Main\"\"\"");
+    }
+}
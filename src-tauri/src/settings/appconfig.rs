@@ -4,14 +4,29 @@ use anyhow::{ Context, Result };
 use crate::settings::appconfigmodels::AppConfig;
 use super::uuidmodel::UniqueId;
 
+const CONFIG_PATH: &str = "C:\\ProgramData\\CPS\\appconfig.json";
+
 pub fn initialize() -> Result<AppConfig> {
-    let config_path = Path::new("C:\\ProgramData\\CPS\\appconfig.json");
+    let config_path = Path::new(CONFIG_PATH);
 
     create_folder_if_not_exists(config_path.to_path_buf())?;
     let app_config = load_app_config(config_path.to_path_buf())?;
     Ok(app_config)
 }
 
+// Directory holding the config file, also used for the files that live
+// beside it (certificates, logs, ...).
+pub fn config_dir() -> PathBuf {
+    Path::new(CONFIG_PATH)
+        .parent()
+        .map(|dir| dir.to_path_buf())
+        .unwrap_or_default()
+}
+
+pub fn save(app_config: &AppConfig) -> Result<()> {
+    save_app_config(Path::new(CONFIG_PATH).to_path_buf(), app_config)
+}
+
 fn create_folder_if_not_exists(config_path: PathBuf) -> Result<()> {
     let parent_dir = config_path.parent().with_context(|| "Failed to fetch parent directory")?;
 
@@ -29,22 +44,27 @@ fn load_app_config(config_path: PathBuf) -> Result<AppConfig> {
         let generated = UniqueId::default()?;
         app_config_root.add_device(generated.id);
 
-        let json = serde_json
-            ::to_string_pretty(&app_config_root)
-            .with_context(|| "Failed to serialize config file")?;
-        fs::write(config_path, json).with_context(|| "Unable to write file")?;
+        save_app_config(config_path, &app_config_root)?;
     } else {
         let json = fs::read_to_string(&config_path).with_context(|| "Failed to read file")?;
         app_config_root = serde_json
             ::from_str(&json)
             .with_context(|| "Failed to deserialize config file")?;
 
-        println!("Config file already exists at {:?}", config_path);
+        tracing::debug!(path = ?config_path, "Loaded existing config file");
     }
 
     Ok(app_config_root)
 }
 
+fn save_app_config(config_path: PathBuf, app_config: &AppConfig) -> Result<()> {
+    let json = serde_json
+        ::to_string_pretty(app_config)
+        .with_context(|| "Failed to serialize config file")?;
+    fs::write(config_path, json).with_context(|| "Unable to write file")?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
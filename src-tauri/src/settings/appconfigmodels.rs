@@ -1,8 +1,11 @@
 use std::collections::HashMap;
 
 use anyhow::Context;
+use chrono::{ DateTime, NaiveTime, Utc, Weekday };
 use serde::{ Deserialize, Serialize };
 
+use crate::payment::PaymentSource;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppConfig {
     pub version: String,
@@ -17,16 +20,46 @@ pub struct DeviceConfig {
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct InnerConfig {
+    #[serde(default)]
+    pub role: Role,
+    // Only read to move paired stations from older configs into the database.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub client: HashMap<String, Client>,
     pub server: Server,
     pub license: License,
+    #[serde(default)]
+    pub license_policy: LicensePolicy,
     pub ui: UserInterface,
+    // Coin and bill acceptors wired to this PC.
+    #[serde(default)]
+    pub acceptors: Vec<Acceptor>,
+    #[serde(default)]
+    pub pricing: Pricing,
+    #[serde(default)]
+    pub promotions: Vec<Promotion>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+// What this PC is in the cafe. Every PC serves the station API, only the
+// counter manages the stations paired with it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    #[default]
+    Station,
+    // The attendant's PC, which opens the counter window instead of the lock
+    // screen.
+    Counter,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct Client {
     pub address: String,
     pub hwid: String,
+    #[serde(default)]
+    pub cert_fingerprint: String,
+    // Handed out by the station when pairing, see `http_server::peer`.
+    #[serde(default)]
+    pub credential: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,6 +69,20 @@ pub struct Server {
     pub hwid: String,
     pub password: String,
     pub configpath: String,
+    #[serde(default)]
+    pub tls: bool,
+    #[serde(default)]
+    pub rate_limit: RateLimit,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct RateLimit {
+    pub requests_per_minute_per_ip: u32,
+    pub requests_per_minute_per_client: u32,
+    pub max_body_bytes: usize,
+    pub failures_before_ban: u32,
+    pub ban_minutes: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -43,6 +90,122 @@ pub struct License {
     pub authorized: bool,
     pub serial_number: String,
     pub email_address: String,
+    // Signed license token issued by the license server, see
+    // `licensing::token`.
+    #[serde(default)]
+    pub token: String,
+    // Last time the license server confirmed the serial number is still
+    // active on this PC.
+    #[serde(default)]
+    pub last_validated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct LicensePolicy {
+    pub renew_interval_hours: u64,
+    // How often the license server is asked whether the serial number was
+    // deactivated or revoked.
+    pub revalidate_interval_minutes: u64,
+}
+
+// Coin or bill acceptor wired to a serial port of this PC, e.g. a CH-926
+// behind a USB-serial adapter.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Acceptor {
+    pub enabled: bool,
+    // `COM3` on Windows, `/dev/ttyUSB0` on Linux.
+    pub port: String,
+    pub baud_rate: u32,
+    pub mode: AcceptorMode,
+    // Whether it takes coins or bills.
+    pub source: PaymentSource,
+    // A coin or bill is complete once no pulse arrived for this long.
+    pub pulse_gap_ms: u64,
+    pub denominations: Vec<Denomination>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AcceptorMode {
+    // Every byte on the line is one pulse, a coin or bill sends as many
+    // pulses as it is set up for.
+    #[default]
+    Pulse,
+    // Every byte is the code of one coin or bill.
+    Serial,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Denomination {
+    // Pulse count or serial code the acceptor sends for the coin or bill.
+    pub signal: u8,
+    pub centavos: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Pricing {
+    // Station time bought with one peso.
+    pub seconds_per_peso: u64,
+    // Coins and bills this station takes, anything else is refused.
+    pub accepted_denominations: Vec<AcceptedDenomination>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AcceptedDenomination {
+    pub source: PaymentSource,
+    pub centavos: u64,
+}
+
+// Bonus time on top of the pricing, see `promotion::apply`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Promotion {
+    // Shown to the customer and recorded with the payment.
+    pub name: String,
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub rule: PromotionRule,
+}
+
+impl Promotion {
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        // Hours past midnight are set up as two promotions
+        if let PromotionRule::TimeOfDay { from, to, .. } = self.rule {
+            if to <= from {
+                anyhow::bail!("Promotion \"{}\" must end after it starts", self.name);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum PromotionRule {
+    // Bonus once the money paid in one session reaches `spend_centavos`.
+    SessionSpend {
+        spend_centavos: u64,
+        bonus_seconds: u64,
+    },
+    // Bonus on the first payment of the day on this station.
+    FirstPaymentOfDay {
+        bonus_seconds: u64,
+    },
+    // More time per peso from `from` until `to` on `weekdays`, every day when
+    // empty. Weekdays are written `Mon`, `Tue`, ...
+    TimeOfDay {
+        #[serde(default)]
+        weekdays: Vec<Weekday>,
+        from: NaiveTime,
+        to: NaiveTime,
+        bonus_percent: u64,
+    },
+    // More time per peso while a member is signed in, and on member top-ups.
+    Member {
+        bonus_percent: u64,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -78,6 +241,20 @@ impl Default for Server {
             hwid: "".to_string(),
             password: "".to_string(),
             configpath: "".to_string(),
+            tls: false,
+            rate_limit: RateLimit::default(),
+        }
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            requests_per_minute_per_ip: 120,
+            requests_per_minute_per_client: 60,
+            max_body_bytes: 16 * 1024,
+            failures_before_ban: 5,
+            ban_minutes: 15,
         }
     }
 }
@@ -88,6 +265,50 @@ impl Default for License {
             authorized: false,
             serial_number: "".to_string(),
             email_address: "".to_string(),
+            token: "".to_string(),
+            last_validated_at: None,
+        }
+    }
+}
+
+impl Default for Acceptor {
+    fn default() -> Self {
+        Acceptor {
+            enabled: false,
+            port: "".to_string(),
+            baud_rate: 9600,
+            mode: AcceptorMode::Pulse,
+            source: PaymentSource::Coin,
+            pulse_gap_ms: 100,
+            // ₱1, ₱5, ₱10 and ₱20 coins send one pulse per peso
+            denominations: [1, 5, 10, 20]
+                .into_iter()
+                .map(|pesos| Denomination { signal: pesos as u8, centavos: pesos * 100 })
+                .collect(),
+        }
+    }
+}
+
+impl Default for Pricing {
+    fn default() -> Self {
+        let coins = [100, 500, 1000, 2000].map(|centavos| (PaymentSource::Coin, centavos));
+        let bills = [2000, 5000, 10000].map(|centavos| (PaymentSource::Bill, centavos));
+        Pricing {
+            seconds_per_peso: 5,
+            accepted_denominations: coins
+                .into_iter()
+                .chain(bills)
+                .map(|(source, centavos)| AcceptedDenomination { source, centavos })
+                .collect(),
+        }
+    }
+}
+
+impl Default for LicensePolicy {
+    fn default() -> Self {
+        LicensePolicy {
+            renew_interval_hours: 24,
+            revalidate_interval_minutes: 60,
         }
     }
 }
@@ -98,6 +319,7 @@ impl License {
             "authorized": self.authorized,
             "serialNumber": self.serial_number,
             "emailAddress": self.email_address,
+            "lastValidatedAt": self.last_validated_at,
         })
     }
 }
@@ -118,6 +340,13 @@ impl AppConfig {
         self
     }
 
+    pub fn get_role(&self, device_name: &str) -> Result<Role, anyhow::Error> {
+        self.devices
+            .get(device_name)
+            .map(|device| device.config.role)
+            .with_context(|| "Device configuration is not found!")
+    }
+
     pub fn get_ip_address(&self, device_name: &str) -> Result<String, anyhow::Error> {
         self.devices
             .get(device_name)
@@ -132,6 +361,48 @@ impl AppConfig {
             .with_context(|| "Port is configured")
     }
 
+    pub fn get_rate_limit(&self, device_name: &str) -> Result<RateLimit, anyhow::Error> {
+        self.devices
+            .get(device_name)
+            .map(|device| device.config.server.rate_limit.clone())
+            .with_context(|| "Server is not configured")
+    }
+
+    pub fn is_tls_enabled(&self, device_name: &str) -> Result<bool, anyhow::Error> {
+        self.devices
+            .get(device_name)
+            .map(|device| device.config.server.tls)
+            .with_context(|| "Server is not configured")
+    }
+
+    pub fn set_server_network(
+        &mut self,
+        device_name: &str,
+        address: &str,
+        port: u16,
+        tls: bool
+    ) -> Result<(), anyhow::Error> {
+        if let Some(device) = self.devices.get_mut(device_name) {
+            device.config.server.address = address.to_string();
+            device.config.server.port = port.to_string();
+            device.config.server.tls = tls;
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("Device configuration is not found!"))
+        }
+    }
+
+    // Removes the paired stations that older versions kept in the config.
+    pub fn take_clients(
+        &mut self,
+        device_name: &str
+    ) -> Result<HashMap<String, Client>, anyhow::Error> {
+        self.devices
+            .get_mut(device_name)
+            .map(|device| std::mem::take(&mut device.config.client))
+            .with_context(|| "Device configuration is not found!")
+    }
+
     pub fn get_license(&self, device_name: &str) -> Result<License, anyhow::Error> {
         self.devices
             .get(device_name)
@@ -152,6 +423,46 @@ impl AppConfig {
         }
     }
 
+    pub fn get_license_policy(&self, device_name: &str) -> Result<LicensePolicy, anyhow::Error> {
+        self.devices
+            .get(device_name)
+            .map(|device| device.config.license_policy.clone())
+            .with_context(|| "License is not configured")
+    }
+
+    pub fn get_acceptors(&self, device_name: &str) -> Result<Vec<Acceptor>, anyhow::Error> {
+        self.devices
+            .get(device_name)
+            .map(|device| device.config.acceptors.clone())
+            .with_context(|| "Device configuration is not found!")
+    }
+
+    pub fn get_pricing(&self, device_name: &str) -> Result<Pricing, anyhow::Error> {
+        self.devices
+            .get(device_name)
+            .map(|device| device.config.pricing.clone())
+            .with_context(|| "Device configuration is not found!")
+    }
+
+    pub fn get_promotions(&self, device_name: &str) -> Result<Vec<Promotion>, anyhow::Error> {
+        let promotions = self.devices
+            .get(device_name)
+            .map(|device| device.config.promotions.clone())
+            .with_context(|| "Device configuration is not found!")?;
+        // One bad rule in the file should not stop the station from starting
+        Ok(
+            promotions
+                .into_iter()
+                .filter(|promotion| {
+                    promotion
+                        .validate()
+                        .map_err(|e| tracing::warn!("Skipping promotion: {:#}", e))
+                        .is_ok()
+                })
+                .collect()
+        )
+    }
+
     pub fn get_ui_config(
         &self,
         device_name: &str
@@ -247,5 +558,120 @@ mod tests {
         assert_eq!(retrieved_ui_config.cafe_name, ui_config.cafe_name);
     }
 
+    #[test]
+    fn test_tls_is_disabled_when_missing_from_config() {
+        let json = r#"{
+            "version": "1.0",
+            "device1": { "config": {
+                "client": {},
+                "server": { "address": "127.0.0.1", "port": "3000", "hwid": "", "password": "", "configpath": "" },
+                "license": { "authorized": false, "serial_number": "", "email_address": "" },
+                "ui": { "cafe_name": "", "station_id": "", "insert_coin_text": "", "autoshutdown_text": "",
+                        "smwindow_position": "", "background_img": "", "countdown_timer": 100 }
+            } }
+        }"#;
+        let app_config: AppConfig = serde_json::from_str(json).unwrap();
+        assert!(!app_config.is_tls_enabled("device1").unwrap());
+        assert!(app_config.get_acceptors("device1").unwrap().is_empty());
+        assert_eq!(app_config.get_pricing("device1").unwrap(), Pricing::default());
+        assert!(app_config.get_promotions("device1").unwrap().is_empty());
+        assert_eq!(app_config.get_role("device1").unwrap(), Role::Station);
+    }
+
+    #[test]
+    fn test_promotions_from_config() {
+        let json = r#"[
+            { "name": "Weekday mornings", "enabled": true, "rule": "time_of_day",
+              "weekdays": ["Mon", "Fri"], "from": "08:00:00", "to": "12:00:00",
+              "bonus_percent": 20 },
+            { "name": "Early bird", "enabled": false, "rule": "first_payment_of_day",
+              "bonus_seconds": 300 }
+        ]"#;
+        let promotions: Vec<Promotion> = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            promotions[0].rule,
+            PromotionRule::TimeOfDay {
+                weekdays: vec![Weekday::Mon, Weekday::Fri],
+                from: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                to: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+                bonus_percent: 20,
+            }
+        );
+        assert!(!promotions[1].enabled);
+    }
+
+    #[test]
+    fn test_time_of_day_promotion_must_end_after_it_starts() {
+        let mut app_config = AppConfig::default();
+        app_config.add_device("device1".to_string());
+        let promotion = |from: u32, to: u32| Promotion {
+            name: "Happy hour".to_string(),
+            enabled: true,
+            rule: PromotionRule::TimeOfDay {
+                weekdays: Vec::new(),
+                from: NaiveTime::from_hms_opt(from, 0, 0).unwrap(),
+                to: NaiveTime::from_hms_opt(to, 0, 0).unwrap(),
+                bonus_percent: 20,
+            },
+        };
+        let promotions = &mut app_config.devices.get_mut("device1").unwrap().config.promotions;
+        promotions.push(promotion(8, 12));
+        assert_eq!(app_config.get_promotions("device1").unwrap().len(), 1);
+
+        // Refused when saved, skipped when loaded
+        for (from, to) in [(22, 2), (8, 8)] {
+            assert!(promotion(from, to).validate().is_err());
+            let promotions = &mut app_config.devices.get_mut("device1").unwrap().config.promotions;
+            promotions.push(promotion(from, to));
+        }
+        assert_eq!(app_config.get_promotions("device1").unwrap(), vec![promotion(8, 12)]);
+    }
+
+    #[test]
+    fn test_set_server_network() {
+        let mut app_config = AppConfig::default();
+        app_config.add_device("device1".to_string());
+        app_config.set_server_network("device1", "0.0.0.0", 8443, true).unwrap();
+        assert_eq!(app_config.get_ip_address("device1").unwrap(), "0.0.0.0");
+        assert_eq!(app_config.get_port("device1").unwrap(), "8443");
+        assert!(app_config.is_tls_enabled("device1").unwrap());
+        assert!(app_config.set_server_network("device2", "0.0.0.0", 8443, true).is_err());
+    }
+
+    #[test]
+    fn test_take_clients_empties_the_config() {
+        let mut app_config = AppConfig::default();
+        app_config.add_device("device1".to_string());
+        let client = Client {
+            address: "https://192.168.1.10:3000".to_string(),
+            hwid: "station-hwid".to_string(),
+            cert_fingerprint: "aa".to_string(),
+            credential: String::new(),
+        };
+        app_config.devices
+            .get_mut("device1")
+            .unwrap()
+            .config.client.insert(client.hwid.clone(), client);
+
+        let clients = app_config.take_clients("device1").unwrap();
+        assert_eq!(clients["station-hwid"].cert_fingerprint, "aa");
+        assert!(app_config.take_clients("device1").unwrap().is_empty());
+
+        let json = serde_json::to_value(&app_config).unwrap();
+        assert!(json["device1"]["config"].get("client").is_none());
+    }
+
+    #[test]
+    fn test_license_token_and_policy_default_when_missing_from_config() {
+        let license: License = serde_json::from_str(
+            r#"{ "authorized": true, "serial_number": "SERIAL-123", "email_address": "" }"#
+        ).unwrap();
+        assert!(license.token.is_empty());
+        assert!(license.last_validated_at.is_none());
+
+        let mut app_config = AppConfig::default();
+        app_config.add_device("device1".to_string());
+        assert_eq!(app_config.get_license_policy("device1").unwrap(), LicensePolicy::default());
+    }
 
 }
@@ -1,6 +1,13 @@
+use std::sync::OnceLock;
+
 use machineid_rs::{ Encryption, HWIDComponent, IdBuilder };
 use anyhow::Result;
 
+pub const HWID_KEY: &str = "TODO:CHANGE_THIS_KEY";
+
+// Read once, the hardware id is asked for by most commands.
+static HWID: OnceLock<String> = OnceLock::new();
+
 #[derive(Debug, Default)]
 pub struct UniqueId {
     pub id: String,
@@ -8,17 +15,19 @@ pub struct UniqueId {
 
 impl UniqueId {
     pub fn default() -> Result<Self> {
-        const KEY: &str = "TODO:CHANGE_THIS_KEY";
+        if let Some(id) = HWID.get() {
+            return Ok(UniqueId { id: id.clone() });
+        }
         let mut builder = IdBuilder::new(Encryption::SHA256);
         builder
             .add_component(HWIDComponent::SystemID)
             .add_component(HWIDComponent::MachineName)
             .add_component(HWIDComponent::Username);
 
-        let uuid = builder.build(KEY)?;
+        let uuid = builder.build(HWID_KEY)?;
 
         Ok(UniqueId {
-            id: uuid,
+            id: HWID.get_or_init(|| uuid).clone(),
         })
     }
 }
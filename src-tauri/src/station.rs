@@ -0,0 +1,135 @@
+use std::sync::atomic::Ordering;
+
+use serde::{ Deserialize, Serialize };
+use tauri::{ AppHandle, Emitter, Manager };
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::audit::event::AuditEvent;
+use crate::audit::store::AuditLog;
+use crate::db::DatabaseState;
+use crate::member::{ self, MemberError, MemberSlot };
+use crate::message::Inbox;
+use crate::reports::ledger::SalesLedger;
+use crate::{ constants, AppConfigState, MemberSessionState, RemainingTime };
+
+// What a station is doing, as shown on the counter.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct StationStatus {
+    /// Id shown on the station UI.
+    #[schema(example = "station-01")]
+    pub station: String,
+    /// `0` while the station is locked.
+    #[schema(example = 1800)]
+    pub remaining_seconds: u64,
+    /// Member signed in on the station.
+    #[schema(example = "juan")]
+    pub member: Option<String>,
+    /// Ids of the messages from the counter not acknowledged yet.
+    #[serde(default)]
+    pub pending_messages: Vec<String>,
+    /// Ids of the messages from the counter the customer acknowledged.
+    #[serde(default)]
+    pub acknowledged_messages: Vec<String>,
+}
+
+pub fn status(app_handle: &AppHandle) -> anyhow::Result<StationStatus> {
+    let member = app_handle
+        .state::<MemberSessionState>()
+        .lock()
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?
+        .session()
+        .map(|session| session.username.clone());
+    Ok(StationStatus {
+        station: crate::station_id(&app_handle.state::<AppConfigState>())?,
+        remaining_seconds: app_handle.state::<RemainingTime>().load(Ordering::SeqCst),
+        member,
+        pending_messages: app_handle
+            .state::<Inbox>()
+            .pending()
+            .into_iter()
+            .map(|message| message.id)
+            .collect(),
+        acknowledged_messages: app_handle.state::<Inbox>().acknowledged(),
+    })
+}
+
+// Adds (or with a negative value removes) time by hand. Returns the remaining
+// time in seconds.
+pub fn adjust_time(app_handle: &AppHandle, seconds: i64, reason: String, actor: String) -> u64 {
+    let remaining_seconds = app_handle
+        .state::<SalesLedger>()
+        .adjust_time(&app_handle.state::<RemainingTime>(), seconds);
+    app_handle.state::<AuditLog>().record(AuditEvent::ManualAdjustment { seconds, reason, actor });
+    remaining_seconds
+}
+
+// Ends the session by hand, which locks the station. A signed in member keeps
+// the time left, anyone else loses it. Returns the time taken off.
+pub async fn end_session(
+    app_handle: &AppHandle,
+    reason: String,
+    actor: String
+) -> Result<u64, MemberError> {
+    let signed_in = app_handle
+        .state::<MemberSessionState>()
+        .lock()
+        .map_err(MemberError::unavailable)?
+        .session()
+        .is_some();
+    if signed_in {
+        let (seconds, _) = sign_out_member(app_handle).await?;
+        return Ok(seconds);
+    }
+
+    let seconds = app_handle
+        .state::<SalesLedger>()
+        .take_time(&app_handle.state::<RemainingTime>());
+    if seconds > 0 {
+        app_handle.state::<AuditLog>().record(AuditEvent::ManualAdjustment {
+            seconds: 0i64.saturating_sub_unsigned(seconds),
+            reason,
+            actor,
+        });
+    }
+    Ok(seconds)
+}
+
+// Gives the time left back to the signed in member's balance, which locks the
+// station. Returns the time given back and the new balance.
+pub async fn sign_out_member(app_handle: &AppHandle) -> Result<(u64, u64), MemberError> {
+    let station = crate
+        ::station_id(&app_handle.state::<AppConfigState>())
+        .map_err(MemberError::unavailable)?;
+    let session = app_handle.state::<MemberSessionState>();
+    let signed_in = session
+        .lock()
+        .map_err(MemberError::unavailable)?
+        .take()
+        .ok_or(MemberError::NotSignedIn)?;
+
+    let remaining_time = app_handle.state::<RemainingTime>();
+    let ledger = app_handle.state::<SalesLedger>();
+    let seconds = ledger.take_time(&remaining_time);
+    let database = app_handle.state::<DatabaseState>();
+    match member::sign_out(&database, &signed_in, seconds, &station).await {
+        Ok(balance) => {
+            info!(seconds, balance, "Member signed out");
+            app_handle.state::<AuditLog>().record(AuditEvent::MemberSignedOut {
+                username: signed_in.username,
+                station,
+                seconds,
+            });
+            let _ = app_handle.emit(constants::HANDLER_MEMBER, None::<String>);
+            Ok((seconds, balance))
+        }
+        Err(e) => {
+            // Keep the session going rather than losing the time
+            ledger.give_back(&remaining_time, seconds);
+            if let Ok(mut session) = session.lock() {
+                *session = MemberSlot::SignedIn(signed_in);
+            }
+            Err(e)
+        }
+    }
+}
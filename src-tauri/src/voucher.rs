@@ -0,0 +1,337 @@
+use std::fmt;
+use std::fmt::Write;
+
+use chrono::{ DateTime, Utc };
+use ring::rand::{ SecureRandom, SystemRandom };
+use serde::{ Deserialize, Serialize };
+use tracing::warn;
+
+use crate::db::vouchers::{ NewVoucherBatch, Voucher, VoucherBatch };
+use crate::db::Database;
+use crate::http_server;
+
+// No 0/O, 1/I/L so codes read back from a printed card are unambiguous.
+const ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+const CODE_LENGTH: usize = 12;
+const GROUP_LENGTH: usize = 4;
+const SAVED_TIME_VALID_DAYS: i64 = 30;
+
+// Why a voucher could not be redeemed. Serialized as `{ "kind": ..., ... }`
+// for the lock screen and between stations.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VoucherError {
+    NotFound,
+    AlreadyRedeemed {
+        at: DateTime<Utc>,
+    },
+    Expired {
+        at: DateTime<Utc>,
+    },
+    // Too many wrong codes from this station.
+    TooManyAttempts,
+    // Neither this station nor a paired one could be asked.
+    Unavailable {
+        message: String,
+    },
+}
+
+impl VoucherError {
+    pub fn unavailable(message: impl fmt::Display) -> Self {
+        VoucherError::Unavailable { message: message.to_string() }
+    }
+}
+
+impl fmt::Display for VoucherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoucherError::NotFound => write!(f, "Voucher code not found"),
+            VoucherError::AlreadyRedeemed { at } => {
+                write!(f, "Voucher was already used on {}", at.format("%Y-%m-%d %H:%M"))
+            }
+            VoucherError::Expired { at } => {
+                write!(f, "Voucher expired on {}", at.format("%Y-%m-%d"))
+            }
+            VoucherError::TooManyAttempts => {
+                write!(f, "Too many wrong voucher codes, please try again later")
+            }
+            VoucherError::Unavailable { message } => {
+                write!(f, "Voucher could not be checked: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VoucherError {}
+
+pub fn generate_codes(count: usize) -> anyhow::Result<Vec<String>> {
+    let random = SystemRandom::new();
+    (0..count).map(|_| generate_code(&random)).collect()
+}
+
+fn generate_code(random: &SystemRandom) -> anyhow::Result<String> {
+    // Rejection sampling keeps every character equally likely
+    let limit = (256 / ALPHABET.len()) * ALPHABET.len();
+    let mut code = String::with_capacity(CODE_LENGTH);
+    let mut byte = [0u8; 1];
+    while code.len() < CODE_LENGTH {
+        random.fill(&mut byte).map_err(|_| anyhow::anyhow!("Failed to generate voucher code"))?;
+        if (byte[0] as usize) < limit {
+            code.push(ALPHABET[(byte[0] as usize) % ALPHABET.len()] as char);
+        }
+    }
+    Ok(code)
+}
+
+// Accepts what a customer types: any case, with or without dashes and spaces.
+pub fn normalize(code: &str) -> Option<String> {
+    let code: String = code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let valid = code.len() == CODE_LENGTH && code.bytes().all(|c| ALPHABET.contains(&c));
+    valid.then_some(code)
+}
+
+// `ABCD-EFGH-JKMN`, as printed on the cards.
+pub fn display(code: &str) -> String {
+    code.as_bytes()
+        .chunks(GROUP_LENGTH)
+        .map(|group| String::from_utf8_lossy(group))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// Redeems on this station first, then asks the paired stations, since the
+// voucher may have been generated or saved on any of them.
+pub async fn redeem(
+    database: &Database,
+    code: &str,
+    station: &str
+) -> Result<u64, VoucherError> {
+    let code = normalize(code).ok_or(VoucherError::NotFound)?;
+    match redeem_here(database, &code, station) {
+        Err(VoucherError::NotFound) => ask_paired(database, &code, station, None, true).await,
+        result => result,
+    }
+}
+
+// Redeems a normalized code generated on this station, for `station`.
+pub fn redeem_here(database: &Database, code: &str, station: &str) -> Result<u64, VoucherError> {
+    database
+        .vouchers()
+        .redeem(code, station, Utc::now())
+        .unwrap_or_else(|e| Err(VoucherError::unavailable(format!("{:#}", e))))
+}
+
+// Asks the paired stations except `caller` to redeem a normalized code. With
+// `pass_on` they also ask the ones paired with them, which is how a station
+// paired only with the counter reaches time saved on another station. They
+// do not pass it on again, so a request never goes round in circles.
+pub async fn ask_paired(
+    database: &Database,
+    code: &str,
+    station: &str,
+    caller: Option<&str>,
+    pass_on: bool
+) -> Result<u64, VoucherError> {
+    let stations = database.clients().all().map_err(VoucherError::unavailable)?;
+    let mut unavailable = None;
+    for paired in stations.values().filter(|paired| Some(paired.hwid.as_str()) != caller) {
+        match http_server::client::redeem_voucher(database, paired, code, station, pass_on).await {
+            Err(VoucherError::NotFound) => {}
+            Err(VoucherError::Unavailable { message }) => {
+                warn!(station = paired.address, "Failed to ask for voucher: {}", message);
+                unavailable = Some(VoucherError::Unavailable { message });
+            }
+            result => {
+                return result;
+            }
+        }
+    }
+    Err(unavailable.unwrap_or(VoucherError::NotFound))
+}
+
+// Time taken off a station, to be resumed on any station with its code.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SavedTime {
+    pub code: String,
+    pub seconds: u64,
+    pub expires_at: DateTime<Utc>,
+}
+
+// Turns time already taken off `station` into a single voucher. The caller
+// must have zeroed the station first so the time exists only in the code.
+pub fn save_time(
+    database: &Database,
+    station: &str,
+    seconds: u64,
+    now: DateTime<Utc>
+) -> anyhow::Result<SavedTime> {
+    let code = generate_codes(1)?.remove(0);
+    let label = format!("Saved on {}", station);
+    let batch = NewVoucherBatch {
+        label: &label,
+        created_at: now,
+        seconds,
+        expires_at: now + chrono::Duration::days(SAVED_TIME_VALID_DAYS),
+    };
+    database.vouchers().create_batch(&batch, std::slice::from_ref(&code))?;
+    Ok(SavedTime { code: display(&code), seconds, expires_at: batch.expires_at })
+}
+
+// One line per code, ready to be merged into printable cards.
+pub fn batch_to_csv(batch: &VoucherBatch, vouchers: &[Voucher]) -> String {
+    let mut csv = String::from("code,minutes,expires_at,redeemed\n");
+    for voucher in vouchers {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{}",
+            display(&voucher.code),
+            batch.seconds / 60,
+            batch.expires_at.format("%Y-%m-%d"),
+            voucher.redeemed_at.is_some()
+        );
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    use axum::Json;
+
+    use crate::http_server::v2::{ self, models::{ RedeemVoucherRequest, RedeemVoucherResponse } };
+    use crate::settings::appconfigmodels::Client;
+
+    // Answers like `redeem_voucher_handler` does for the paired `caller`.
+    async fn serve(database: Arc<Database>, caller: &'static str) -> String {
+        let redeem = move |Json(request): Json<RedeemVoucherRequest>| async move {
+            let code = normalize(&request.code).unwrap();
+            let mut result = redeem_here(&database, &code, &request.station);
+            if result == Err(VoucherError::NotFound) && request.pass_on {
+                result = ask_paired(&database, &code, &request.station, Some(caller), false).await;
+            }
+            Json(RedeemVoucherResponse {
+                status: result.is_ok(),
+                seconds: result.clone().unwrap_or_default(),
+                error: result.err(),
+                text: String::new(),
+            })
+        };
+        let router = axum::Router
+            ::new()
+            .route(&format!("{}/vouchers/redeem", v2::PREFIX), axum::routing::post(redeem));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        address
+    }
+
+    fn pair(database: &Database, hwid: &str, address: String) {
+        let client = Client { address, hwid: hwid.to_string(), ..Client::default() };
+        database.clients().upsert(&client).unwrap();
+    }
+
+    #[test]
+    fn test_generated_codes_are_unique_and_readable() {
+        let codes = generate_codes(200).unwrap();
+        assert_eq!(codes.iter().collect::<HashSet<_>>().len(), 200);
+        for code in &codes {
+            assert_eq!(normalize(code).as_ref(), Some(code));
+            assert_eq!(normalize(&display(code).to_lowercase()).as_ref(), Some(code));
+        }
+
+        assert_eq!(display("ABCDEFGHJKMN"), "ABCD-EFGH-JKMN");
+        assert_eq!(normalize(" abcd efgh-jkmn "), Some("ABCDEFGHJKMN".to_string()));
+        assert_eq!(normalize("ABCD-EFGH-JKM0"), None);
+        assert_eq!(normalize("ABCD"), None);
+    }
+
+    #[tokio::test]
+    async fn test_redeem_on_this_station() {
+        let database = Database::open_in_memory().unwrap();
+        let now = Utc::now();
+        let batch = NewVoucherBatch {
+            label: "1 hour",
+            created_at: now,
+            seconds: 3600,
+            expires_at: now + chrono::Duration::days(1),
+        };
+        database.vouchers().create_batch(&batch, &["ABCDEFGHJKMN".to_string()]).unwrap();
+
+        assert_eq!(redeem(&database, "abcd-efgh-jkmn", "station-01").await, Ok(3600));
+        assert!(
+            matches!(
+                redeem(&database, "ABCDEFGHJKMN", "station-02").await,
+                Err(VoucherError::AlreadyRedeemed { .. })
+            )
+        );
+        // Unknown here and no paired station to ask
+        for code in ["2345-6789-ABCD", "not a code"] {
+            assert_eq!(redeem(&database, code, "station-01").await, Err(VoucherError::NotFound));
+        }
+
+        let vouchers = database.vouchers().codes(1).unwrap();
+        let batch = database.vouchers().batches().unwrap().remove(0);
+        assert_eq!(
+            batch_to_csv(&batch, &vouchers),
+            format!(
+                "code,minutes,expires_at,redeemed\nABCD-EFGH-JKMN,60,{},true\n",
+                batch.expires_at.format("%Y-%m-%d")
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_saved_time_is_resumed_once() {
+        let database = Database::open_in_memory().unwrap();
+        let saved = save_time(&database, "station-01", 1234, Utc::now()).unwrap();
+        assert_eq!(saved.seconds, 1234);
+
+        assert_eq!(redeem(&database, &saved.code, "station-02").await, Ok(1234));
+        assert!(
+            matches!(
+                redeem(&database, &saved.code, "station-03").await,
+                Err(VoucherError::AlreadyRedeemed { .. })
+            )
+        );
+        assert_eq!(database.vouchers().batches().unwrap()[0].label, "Saved on station-01");
+    }
+
+    #[tokio::test]
+    async fn test_saved_time_is_resumed_on_another_station() {
+        // Both stations are paired only with the counter, which pairs with them
+        let station_a = Arc::new(Database::open_in_memory().unwrap());
+        let counter = Arc::new(Database::open_in_memory().unwrap());
+        let station_b = Database::open_in_memory().unwrap();
+        let saved = save_time(&station_a, "station-a", 1234, Utc::now()).unwrap();
+
+        pair(&counter, "station-a", serve(station_a.clone(), "counter").await);
+        pair(&station_b, "counter", serve(counter, "station-b").await);
+
+        assert_eq!(redeem(&station_b, &saved.code, "station-b").await, Ok(1234));
+        assert!(
+            matches!(
+                redeem(&station_b, &saved.code, "station-b").await,
+                Err(VoucherError::AlreadyRedeemed { .. })
+            )
+        );
+        let code = normalize(&saved.code).unwrap();
+        assert!(
+            matches!(
+                redeem_here(&station_a, &code, "station-a"),
+                Err(VoucherError::AlreadyRedeemed { .. })
+            )
+        );
+        // Unknown on every station
+        assert_eq!(
+            ask_paired(&station_b, "2345678ABCDE", "station-b", None, true).await,
+            Err(VoucherError::NotFound)
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use tauri::{ AppHandle, Manager };
+use tauri::{ AppHandle, Manager, UserAttentionType, WebviewUrl, WebviewWindowBuilder };
 
 pub fn show_small_window(app: &AppHandle) {
     if let Some(window) = app.get_webview_window("main") {
@@ -28,7 +28,7 @@ pub fn show_small_window(app: &AppHandle) {
 
             window.show().unwrap();
         } else {
-            println!("Failed to get primary monitor size.");
+            tracing::warn!("Failed to get primary monitor size");
         }
     }
 }
@@ -40,6 +40,37 @@ pub fn show_main_window(app: &AppHandle) {
     }
 }
 
+// Hides the lock screen and opens the counter window in its place.
+pub fn show_counter_window(app: &AppHandle) -> tauri::Result<()> {
+    if let Some(window) = app.get_webview_window("main") {
+        window.hide()?;
+    }
+
+    let window = match app.get_webview_window("counter") {
+        Some(window) => window,
+        None => {
+            WebviewWindowBuilder::new(app, "counter", WebviewUrl::App("counter".into()))
+                .title("Counter")
+                .inner_size(1024.0, 720.0)
+                .build()?
+        }
+    };
+    window.show()?;
+    window.set_focus()
+}
+
+// Brings the station window to the front and flashes it in the taskbar, so
+// a customer in a full screen game sees a message from the counter.
+pub fn request_attention(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+        if let Err(e) = window.request_user_attention(Some(UserAttentionType::Critical)) {
+            tracing::warn!("Failed to request user attention: {}", e);
+        }
+    }
+}
+
 pub fn is_main_window_active(app: &AppHandle) -> bool {
     if let Some(window) = app.get_webview_window("main") {
         return window.is_visible().unwrap();
@@ -1,5 +1,10 @@
 import { createBrowserRouter, RouterProvider } from "react-router-dom";
-import { MainWindow, SettingsWindow, SmallWindow } from "./window";
+import {
+  CounterWindow,
+  MainWindow,
+  SettingsWindow,
+  SmallWindow,
+} from "./window";
 
 export const router = createBrowserRouter([
   {
@@ -18,6 +23,10 @@ export const router = createBrowserRouter([
     path: "/settings",
     element: <SettingsWindow />,
   },
+  {
+    path: "/counter",
+    element: <CounterWindow />,
+  },
 ]);
 
 export default function AppRouter() {
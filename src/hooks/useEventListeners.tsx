@@ -1,27 +1,58 @@
+import { invoke } from "@tauri-apps/api/core";
 import { listen, Event } from "@tauri-apps/api/event";
 import { useEffect } from "react";
 import { router } from "../Router";
-import { LicenseInformation, useSettingStore } from "../store/Settings";
+import {
+  LicenseError,
+  LicenseInformation,
+  SavedTime,
+  StationMessage,
+  useSettingStore,
+} from "../store/Settings";
 
 export const useEventListeners = () => {
   const setServerStatus = useSettingStore((state) => state.setServerStatus);
   const setCoin = useSettingStore((state) => state.setCoin);
   const setTimerDone = useSettingStore((state) => state.setTimerDone);
   const setRemainingTime = useSettingStore((state) => state.setRemainingTime);
+  const setMember = useSettingStore((state) => state.setMember);
+  const setSavedTime = useSettingStore((state) => state.setSavedTime);
+  const setMessages = useSettingStore((state) => state.setMessages);
+  const addMessage = useSettingStore((state) => state.addMessage);
   const setLicenseInformation = useSettingStore(
     (state) => state.setLicenseInformation,
   );
+  const setLicenseRevocation = useSettingStore(
+    (state) => state.setLicenseRevocation,
+  );
 
   useEffect(() => {
     const unlistenLicenseInformation = listen("handler_initialize_license", (event) => {
-      const { authorized, serialNumber, emailAddress } =
+      const { authorized, serialNumber, emailAddress, lastValidatedAt } =
         event.payload as LicenseInformation;
       console.log("Received license information", event.payload);
-      setLicenseInformation({ authorized, serialNumber, emailAddress });
+      setLicenseInformation({
+        authorized,
+        serialNumber,
+        emailAddress,
+        lastValidatedAt,
+      });
+      if (authorized) {
+        setLicenseRevocation(null);
+      }
     });
 
+    const unlistenLicenseRevoked = listen(
+      "handler_license_revoked",
+      (event: Event<LicenseError>) => {
+        console.log("License revoked", event.payload);
+        setLicenseRevocation(event.payload);
+      },
+    );
+
     return () => {
       unlistenLicenseInformation.then((unlistenFn) => unlistenFn());
+      unlistenLicenseRevoked.then((unlistenFn) => unlistenFn());
     };
   }, []);
 
@@ -52,6 +83,7 @@ export const useEventListeners = () => {
         console.log("Received add time request", event.payload);
         setCoin(event.payload);
         setTimerDone(false);
+        setSavedTime(null);
         router.navigate("/show_small");
       },
     );
@@ -68,11 +100,40 @@ export const useEventListeners = () => {
       router.navigate("/show_main");
     });
 
+    invoke<string | null>("get_signed_in_member").then(setMember);
+    const unlistenMember = listen(
+      "handler_member",
+      (event: Event<string | null>) => {
+        console.log("Received member", event.payload);
+        setMember(event.payload);
+      },
+    );
+
+    const unlistenTimeSaved = listen(
+      "handler_time_saved",
+      (event: Event<SavedTime>) => {
+        console.log("Time saved", event.payload);
+        setSavedTime(event.payload);
+      },
+    );
+
+    invoke<StationMessage[]>("get_messages").then(setMessages);
+    const unlistenMessage = listen(
+      "handler_message",
+      (event: Event<StationMessage>) => {
+        console.log("Received message", event.payload);
+        addMessage(event.payload);
+      },
+    );
+
     return () => {
       unlistenRegister.then((unlistenFn) => unlistenFn());
       unlistenAddTime.then((unlistenFn) => unlistenFn());
       unlistenTimerUpdate.then((unlistenFn) => unlistenFn());
       unlistenTimerDone.then((unlistenFn) => unlistenFn());
+      unlistenMember.then((unlistenFn) => unlistenFn());
+      unlistenTimeSaved.then((unlistenFn) => unlistenFn());
+      unlistenMessage.then((unlistenFn) => unlistenFn());
     };
   }, []);
 
@@ -4,6 +4,37 @@ export interface LicenseInformation {
   authorized: boolean;
   serialNumber: string;
   emailAddress: string;
+  lastValidatedAt?: string | null;
+}
+
+export type LicenseError =
+  | { kind: "not_found" }
+  | { kind: "owner_mismatch" }
+  | { kind: "device_mismatch" }
+  | { kind: "revoked" }
+  | { kind: "transfer_cooldown"; until: string }
+  | { kind: "backend_unavailable"; message: string }
+  | { kind: "malformed_response"; message: string };
+
+export type MemberError =
+  | { kind: "not_found" }
+  | { kind: "wrong_credentials" }
+  | { kind: "too_many_attempts" }
+  | { kind: "signed_in"; station: string }
+  | { kind: "not_signed_in" }
+  | { kind: "unavailable"; message: string };
+
+export interface SavedTime {
+  code: string;
+  seconds: number;
+  expires_at: string;
+}
+
+export interface StationMessage {
+  id: string;
+  text: string;
+  sent_at: string;
+  bring_to_front: boolean;
 }
 
 type SettingState = {
@@ -17,6 +48,15 @@ type SettingState = {
   setTimerDone: (done: boolean) => void;
   licenseInformation: LicenseInformation;
   setLicenseInformation: (info: LicenseInformation) => void;
+  licenseRevocation: LicenseError | null;
+  setLicenseRevocation: (error: LicenseError | null) => void;
+  member: string | null;
+  setMember: (member: string | null) => void;
+  savedTime: SavedTime | null;
+  setSavedTime: (savedTime: SavedTime | null) => void;
+  messages: StationMessage[];
+  setMessages: (messages: StationMessage[]) => void;
+  addMessage: (message: StationMessage) => void;
 };
 
 export const useSettingStore = create<SettingState>((set) => ({
@@ -28,18 +68,30 @@ export const useSettingStore = create<SettingState>((set) => ({
     authorized: false,
     serialNumber: "",
     emailAddress: "",
+    lastValidatedAt: null,
   },
+  licenseRevocation: null,
+  member: null,
+  savedTime: null,
+  messages: [],
   setLicenseInformation: (info: LicenseInformation) =>
     set({
       licenseInformation: {
         authorized: info.authorized,
         emailAddress: info.emailAddress,
         serialNumber: info.serialNumber,
+        lastValidatedAt: info.lastValidatedAt,
       },
     }),
+  setLicenseRevocation: (error) => set({ licenseRevocation: error }),
   setServerStatus: (status) => set({ serverStatus: status }),
   setCoin: (coin) => set({ coin }),
   setRemainingTime: (time) => set({ remainingTime: time }),
   setTimerDone: (done) => set({ timerDone: done }),
+  setMember: (member) => set({ member }),
+  setSavedTime: (savedTime) => set({ savedTime }),
+  setMessages: (messages) => set({ messages }),
+  addMessage: (message) =>
+    set((state) => ({ messages: [...state.messages, message] })),
 }));
 
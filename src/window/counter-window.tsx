@@ -0,0 +1,339 @@
+import { invoke } from "@tauri-apps/api/core";
+import { useEffect, useState } from "react";
+
+interface StationOverview {
+  hwid: string;
+  address: string;
+  station: string | null;
+  remaining_seconds: number;
+  member: string | null;
+  error: string | null;
+}
+
+type Delivery =
+  | { state: "delivered" }
+  | { state: "acknowledged" }
+  | { state: "failed"; error: string };
+
+interface SentMessage {
+  id: string;
+  text: string;
+  sent_at: string;
+  bring_to_front: boolean;
+  recipients: { hwid: string; address: string; delivery: Delivery }[];
+}
+
+function describeDelivery(delivery: Delivery): string {
+  switch (delivery.state) {
+    case "delivered":
+      return "not read yet";
+    case "acknowledged":
+      return "read";
+    case "failed":
+      return `not sent: ${delivery.error}`;
+  }
+}
+
+interface ReportRow {
+  station: string;
+  period: string;
+  payments: number;
+  revenue_centavos: number;
+  promo_centavos: number;
+  seconds_sold: number;
+  sessions: number;
+}
+
+interface CounterSales {
+  rows: ReportRow[];
+  unreachable: string[];
+}
+
+function formatTime(seconds: number): string {
+  const minutes = Math.floor(seconds / 60);
+  return `${Math.floor(minutes / 60)}h ${minutes % 60}m ${seconds % 60}s`;
+}
+
+export const CounterWindow = () => {
+  const [stations, setStations] = useState<StationOverview[]>([]);
+  const [sales, setSales] = useState<CounterSales>({
+    rows: [],
+    unreachable: [],
+  });
+  const [message, setMessage] = useState("");
+  const [sentMessages, setSentMessages] = useState<SentMessage[]>([]);
+  const [messageText, setMessageText] = useState("");
+  const [messageTo, setMessageTo] = useState("");
+  const [messageBringToFront, setMessageBringToFront] = useState(false);
+  const [pairAddress, setPairAddress] = useState("");
+  const [pairId, setPairId] = useState("");
+
+  async function loadStations() {
+    try {
+      setStations(await invoke<StationOverview[]>("list_stations"));
+      setSentMessages(await invoke<SentMessage[]>("list_sent_messages"));
+    } catch (error) {
+      setMessage(`Failed to list stations: ${error}`);
+    }
+  }
+
+  async function loadSales() {
+    const today = new Date();
+    today.setHours(0, 0, 0, 0);
+    try {
+      setSales(
+        await invoke<CounterSales>("get_counter_sales", {
+          granularity: "day",
+          from: today.toISOString(),
+        }),
+      );
+    } catch (error) {
+      setMessage(`Failed to load sales: ${error}`);
+    }
+  }
+
+  async function adjustTime(station: StationOverview, sign: number) {
+    const minutes = Number(
+      prompt(`Minutes to ${sign > 0 ? "add to" : "take off"} ${station.station}`),
+    );
+    if (!minutes) {
+      return;
+    }
+    const reason = prompt("Reason") ?? "";
+    try {
+      const remaining = await invoke<number>("adjust_station_time", {
+        hwid: station.hwid,
+        seconds: sign * Math.round(minutes * 60),
+        reason,
+      });
+      setMessage(`${station.station} now has ${formatTime(remaining)}`);
+      await loadStations();
+    } catch (error) {
+      setMessage(`Failed to change the time: ${error}`);
+    }
+  }
+
+  async function endSession(station: StationOverview) {
+    if (!confirm(`End the session on ${station.station}?`)) {
+      return;
+    }
+    try {
+      await invoke<number>("end_station_session", {
+        hwid: station.hwid,
+        reason: "Ended at the counter",
+      });
+      setMessage(`Session on ${station.station} ended`);
+      await loadStations();
+    } catch (error) {
+      setMessage(`Failed to end the session: ${error}`);
+    }
+  }
+
+  async function sendMessage() {
+    try {
+      await invoke<SentMessage>("send_station_message", {
+        hwid: messageTo || null,
+        text: messageText,
+        bringToFront: messageBringToFront,
+      });
+      setMessageText("");
+      setSentMessages(await invoke<SentMessage[]>("list_sent_messages"));
+    } catch (error) {
+      setMessage(`Failed to send the message: ${error}`);
+    }
+  }
+
+  async function announceClosing() {
+    if (!confirm("Tell every station the cafe closes in 15 minutes?")) {
+      return;
+    }
+    try {
+      await invoke<SentMessage>("announce_closing");
+      setSentMessages(await invoke<SentMessage[]>("list_sent_messages"));
+    } catch (error) {
+      setMessage(`Failed to announce closing: ${error}`);
+    }
+  }
+
+  async function pairStation() {
+    try {
+      await invoke("pair_station", { address: pairAddress, pairId });
+      setMessage(`Paired with ${pairAddress}`);
+      setPairAddress("");
+      setPairId("");
+      await loadStations();
+    } catch (error) {
+      setMessage(`Failed to pair: ${error}`);
+    }
+  }
+
+  useEffect(() => {
+    loadStations();
+    loadSales();
+    const interval = setInterval(loadStations, 5000);
+    return () => clearInterval(interval);
+  }, []);
+
+  return (
+    <div>
+      <h2>Counter</h2>
+      {message && <p>{message}</p>}
+
+      <h3>Stations</h3>
+      <table>
+        <thead>
+          <tr>
+            <th>Station</th>
+            <th>Time left</th>
+            <th>Member</th>
+            <th></th>
+          </tr>
+        </thead>
+        <tbody>
+          {stations.map((station) => (
+            <tr key={station.hwid}>
+              <td>{station.station ?? station.address}</td>
+              {station.error ? (
+                <td colSpan={3}>Offline: {station.error}</td>
+              ) : (
+                <>
+                  <td>
+                    {station.remaining_seconds > 0
+                      ? formatTime(station.remaining_seconds)
+                      : "Locked"}
+                  </td>
+                  <td>{station.member ?? "-"}</td>
+                  <td>
+                    <button onClick={() => adjustTime(station, 1)}>
+                      Add time
+                    </button>
+                    <button onClick={() => adjustTime(station, -1)}>
+                      Subtract time
+                    </button>
+                    <button
+                      onClick={() => endSession(station)}
+                      disabled={station.remaining_seconds === 0}
+                    >
+                      End session
+                    </button>
+                  </td>
+                </>
+              )}
+            </tr>
+          ))}
+        </tbody>
+      </table>
+
+      <form
+        className="row"
+        onSubmit={(e) => {
+          e.preventDefault();
+          pairStation();
+        }}
+      >
+        <input
+          value={pairAddress}
+          onChange={(e) => setPairAddress(e.target.value)}
+          placeholder="https://192.168.1.10:3000"
+        />
+        <input
+          value={pairId}
+          onChange={(e) => setPairId(e.target.value)}
+          placeholder="Pairing code shown on the station..."
+        />
+        <button type="submit">Pair station</button>
+      </form>
+
+      <h3>Messages</h3>
+      <form
+        className="row"
+        onSubmit={(e) => {
+          e.preventDefault();
+          sendMessage();
+        }}
+      >
+        <select value={messageTo} onChange={(e) => setMessageTo(e.target.value)}>
+          <option value="">All stations</option>
+          {stations.map((station) => (
+            <option key={station.hwid} value={station.hwid}>
+              {station.station ?? station.address}
+            </option>
+          ))}
+        </select>
+        <input
+          value={messageText}
+          onChange={(e) => setMessageText(e.target.value)}
+          placeholder="Message..."
+          maxLength={500}
+        />
+        <label>
+          <input
+            type="checkbox"
+            checked={messageBringToFront}
+            onChange={(e) => setMessageBringToFront(e.target.checked)}
+          />
+          Bring to front
+        </label>
+        <button type="submit">Send</button>
+      </form>
+      <button onClick={announceClosing}>Announce closing in 15 minutes</button>
+      <ul>
+        {sentMessages.map((sent) => (
+          <li key={sent.id}>
+            {new Date(sent.sent_at).toLocaleTimeString()} "{sent.text}":{" "}
+            {sent.recipients
+              .map(
+                (recipient) =>
+                  `${
+                    stations.find((station) => station.hwid === recipient.hwid)
+                      ?.station ?? recipient.address
+                  } ${describeDelivery(recipient.delivery)}`,
+              )
+              .join(", ")}
+          </li>
+        ))}
+      </ul>
+
+      <h3>
+        Sales today <button onClick={loadSales}>Refresh</button>
+      </h3>
+      {sales.unreachable.length > 0 && (
+        <p>Not included, offline: {sales.unreachable.join(", ")}</p>
+      )}
+      <table>
+        <thead>
+          <tr>
+            <th>Station</th>
+            <th>Payments</th>
+            <th>Revenue</th>
+            <th>Sessions</th>
+          </tr>
+        </thead>
+        <tbody>
+          {sales.rows.map((row) => (
+            <tr key={`${row.period}-${row.station}`}>
+              <td>{row.station}</td>
+              <td>{row.payments}</td>
+              <td>PHP {(row.revenue_centavos / 100).toFixed(2)}</td>
+              <td>{row.sessions}</td>
+            </tr>
+          ))}
+        </tbody>
+        <tfoot>
+          <tr>
+            <td>Total</td>
+            <td>{sales.rows.reduce((sum, row) => sum + row.payments, 0)}</td>
+            <td>
+              PHP{" "}
+              {(
+                sales.rows.reduce((sum, row) => sum + row.revenue_centavos, 0) /
+                100
+              ).toFixed(2)}
+            </td>
+            <td>{sales.rows.reduce((sum, row) => sum + row.sessions, 0)}</td>
+          </tr>
+        </tfoot>
+      </table>
+    </div>
+  );
+};
@@ -1,3 +1,4 @@
 export * from "./main-window";
 export * from "./small-window";
 export * from "./settings-window";
+export * from "./counter-window";
@@ -1,7 +1,102 @@
 import { invoke } from "@tauri-apps/api/core";
-import { useSettingStore } from "../store/Settings";
+import {
+  LicenseError,
+  MemberError,
+  useSettingStore,
+} from "../store/Settings";
 import { useRef, useState } from "react";
 import { router } from "../Router";
+import { listen } from "@tauri-apps/api/event";
+import { StationMessages } from "./station-messages";
+
+type ActivationResult =
+  | { status: "activated" }
+  | { status: "rejected"; error: LicenseError }
+  | { status: "failed"; message: string }
+  | { status: "timed_out" }
+  | { status: "cancelled" }
+  | { status: "in_progress" };
+
+const activationSteps: Record<string, string> = {
+  reading_hardware: "Reading hardware...",
+  contacting_server: "Contacting license server...",
+  issuing_token: "Issuing license...",
+  saving: "Saving license...",
+};
+
+function describeLicenseError(error: LicenseError): string {
+  switch (error.kind) {
+    case "not_found":
+      return "Serial number not found";
+    case "owner_mismatch":
+      return "Email address is not the owner of this serial number";
+    case "device_mismatch":
+      return "Serial number is already active on another PC";
+    case "revoked":
+      return "License has been revoked, please contact your provider";
+    case "transfer_cooldown":
+      return `Serial number cannot be transferred again before ${error.until}`;
+    case "backend_unavailable":
+      return `Could not reach the license server: ${error.message}`;
+    case "malformed_response":
+      return `The license server sent an unexpected response: ${error.message}`;
+  }
+}
+
+type VoucherError =
+  | { kind: "not_found" }
+  | { kind: "already_redeemed"; at: string }
+  | { kind: "expired"; at: string }
+  | { kind: "too_many_attempts" }
+  | { kind: "unavailable"; message: string };
+
+function describeVoucherError(error: VoucherError): string {
+  switch (error.kind) {
+    case "not_found":
+      return "Voucher code not found";
+    case "already_redeemed":
+      return `Voucher was already used on ${new Date(error.at).toLocaleString()}`;
+    case "expired":
+      return `Voucher expired on ${new Date(error.at).toLocaleDateString()}`;
+    case "too_many_attempts":
+      return "Too many wrong voucher codes, please try again later";
+    case "unavailable":
+      return `Voucher could not be checked: ${error.message}`;
+  }
+}
+
+function describeMemberError(error: MemberError): string {
+  switch (error.kind) {
+    case "not_found":
+    case "wrong_credentials":
+      return "Wrong username or PIN";
+    case "too_many_attempts":
+      return "Too many wrong PINs, please try again later";
+    case "signed_in":
+      return `Already signed in on ${error.station}`;
+    case "not_signed_in":
+      return "Not signed in";
+    case "unavailable":
+      return `Member account could not be reached: ${error.message}`;
+  }
+}
+
+function describeActivation(result: ActivationResult): string {
+  switch (result.status) {
+    case "activated":
+      return "License activated";
+    case "rejected":
+      return describeLicenseError(result.error);
+    case "failed":
+      return `Activation failed: ${result.message}`;
+    case "timed_out":
+      return "The license server did not answer in time";
+    case "cancelled":
+      return "Activation cancelled";
+    case "in_progress":
+      return "Activation is already running";
+  }
+}
 
 export const MainWindow = () => {
   const serverStatus = useSettingStore((state) => state.serverStatus);
@@ -12,7 +107,11 @@ export const MainWindow = () => {
     serialNumber: licenseSerialNumber,
     authorized: isAuthorized,
     emailAddress: licenseEmailAddress,
+    lastValidatedAt: licenseLastValidatedAt,
   } = useSettingStore((state) => state.licenseInformation);
+  const licenseRevocation = useSettingStore(
+    (state) => state.licenseRevocation,
+  );
   const setLicenseInformation = useSettingStore(
     (state) => state.setLicenseInformation,
   );
@@ -20,24 +119,90 @@ export const MainWindow = () => {
   const serialNumberRef = useRef<HTMLInputElement>(null);
   const emailAddressRef = useRef<HTMLInputElement>(null);
 
+  const [isActivating, setIsActivating] = useState(false);
+  const [activationMessage, setActivationMessage] = useState("");
+
   async function authorize() {
     const serialNumber = serialNumberRef.current?.value || "";
     const emailAddress = emailAddressRef.current?.value || "";
 
-    const isAuthorized = await invoke<boolean>("authorize", {
-      serialNumber,
-      emailAddress,
-    });
-
-    console.log("Response from authorize:", isAuthorized);
+    setIsActivating(true);
+    setActivationMessage("");
+    const unlistenProgress = await listen<string>(
+      "handler_activation_progress",
+      (event) => setActivationMessage(activationSteps[event.payload] ?? ""),
+    );
 
-    if (isAuthorized) {
-      console.log("License authorized successfully");
-      setLicenseInformation({
-        authorized: isAuthorized,
-        serialNumber: serialNumber,
-        emailAddress: emailAddress,
+    try {
+      const result = await invoke<ActivationResult>("authorize", {
+        serialNumber,
+        emailAddress,
       });
+
+      console.log("Response from authorize:", result);
+
+      if (result.status === "activated") {
+        console.log("License authorized successfully");
+        setLicenseInformation({
+          authorized: true,
+          serialNumber: serialNumber,
+          emailAddress: emailAddress,
+        });
+      }
+      setActivationMessage(describeActivation(result));
+    } finally {
+      unlistenProgress();
+      setIsActivating(false);
+    }
+  }
+
+  const voucherCodeRef = useRef<HTMLInputElement>(null);
+  const [voucherMessage, setVoucherMessage] = useState("");
+
+  async function redeemVoucher() {
+    const code = voucherCodeRef.current?.value || "";
+    try {
+      const seconds = await invoke<number>("redeem_voucher", { code });
+      setVoucherMessage(`Voucher added ${Math.floor(seconds / 60)} minutes`);
+      if (voucherCodeRef.current) {
+        voucherCodeRef.current.value = "";
+      }
+    } catch (error) {
+      setVoucherMessage(describeVoucherError(error as VoucherError));
+    }
+  }
+
+  const member = useSettingStore((state) => state.member);
+  const savedTime = useSettingStore((state) => state.savedTime);
+  const setSavedTime = useSettingStore((state) => state.setSavedTime);
+  const usernameRef = useRef<HTMLInputElement>(null);
+  const pinRef = useRef<HTMLInputElement>(null);
+  const [memberMessage, setMemberMessage] = useState("");
+
+  async function signInMember() {
+    const username = usernameRef.current?.value || "";
+    const pin = pinRef.current?.value || "";
+    try {
+      const seconds = await invoke<number>("sign_in_member", { username, pin });
+      setMemberMessage(
+        seconds > 0
+          ? `Welcome back, ${Math.floor(seconds / 60)} minutes left`
+          : "Your balance is empty, insert coins to add time",
+      );
+      if (pinRef.current) {
+        pinRef.current.value = "";
+      }
+    } catch (error) {
+      setMemberMessage(describeMemberError(error as MemberError));
+    }
+  }
+
+  async function signOutMember() {
+    try {
+      await invoke<number>("sign_out_member");
+      setMemberMessage("");
+    } catch (error) {
+      setMemberMessage(describeMemberError(error as MemberError));
     }
   }
 
@@ -60,15 +225,83 @@ export const MainWindow = () => {
 
   return (
     <>
+      <StationMessages />
       <h1>Welcome MPG Cafe</h1>
       <h2>{serverStatus}</h2>
       <h2>Serial Number: {licenseSerialNumber}</h2>
       <h2>Owner Address: {licenseEmailAddress}</h2>
       <h2>License Authorized: {isAuthorized ? "True" : "False"}</h2>
+      {licenseLastValidatedAt && (
+        <p>
+          License last validated:{" "}
+          {new Date(licenseLastValidatedAt).toLocaleString()}
+        </p>
+      )}
+      {!isAuthorized && licenseRevocation && (
+        <p>
+          This station has been locked:{" "}
+          {describeLicenseError(licenseRevocation)}
+        </p>
+      )}
 
       {timerDone ? (
         <>
           <h3>Insert Coin</h3>
+          {savedTime && (
+            <p>
+              Your {Math.floor(savedTime.seconds / 60)} minutes were saved as
+              code <strong>{savedTime.code}</strong>. Redeem it on any station
+              before {new Date(savedTime.expires_at).toLocaleDateString()}.{" "}
+              <button onClick={() => setSavedTime(null)}>Done</button>
+            </p>
+          )}
+          {isAuthorized && (
+            <form
+              className="row"
+              onSubmit={(e) => {
+                e.preventDefault();
+                redeemVoucher();
+              }}
+            >
+              <input
+                id="voucher-input"
+                ref={voucherCodeRef}
+                placeholder="Enter Voucher Code..."
+              />
+              <button type="submit">Redeem</button>
+            </form>
+          )}
+          {voucherMessage && <p>{voucherMessage}</p>}
+          {isAuthorized &&
+            (member ? (
+              <p>
+                Signed in as {member}{" "}
+                <button onClick={signOutMember}>Sign out</button>
+              </p>
+            ) : (
+              <form
+                className="row"
+                onSubmit={(e) => {
+                  e.preventDefault();
+                  signInMember();
+                }}
+              >
+                <input
+                  id="username-input"
+                  ref={usernameRef}
+                  placeholder="Username..."
+                />
+                <input
+                  id="pin-input"
+                  ref={pinRef}
+                  type="password"
+                  inputMode="numeric"
+                  placeholder="PIN..."
+                />
+                <button type="submit">Sign in</button>
+              </form>
+            ))}
+          {memberMessage && <p>{memberMessage}</p>}
         </>
       ) : (
         <>
@@ -95,9 +328,17 @@ export const MainWindow = () => {
             ref={emailAddressRef}
             placeholder="Enter Owner Address..."
           />
-          <button type="submit">Connect in rust</button>
+          <button type="submit" disabled={isActivating}>
+            Connect in rust
+          </button>
+          {isActivating && (
+            <button type="button" onClick={() => invoke("cancel_authorize")}>
+              Cancel
+            </button>
+          )}
         </form>
       )}
+      {activationMessage && <p>{activationMessage}</p>}
 
       <button
         onClick={() => {
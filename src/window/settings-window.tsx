@@ -1,5 +1,6 @@
 import { invoke } from "@tauri-apps/api/core";
-import { useEffect } from "react";
+import { listen, Event } from "@tauri-apps/api/event";
+import { useEffect, useState } from "react";
 
 interface UIConfig {
   cafe_name: string;
@@ -11,15 +12,246 @@ interface UIConfig {
   countdown_timer: number;
 }
 
+interface VoucherBatch {
+  id: number;
+  label: string;
+  created_at: string;
+  seconds: number;
+  expires_at: string;
+  codes: number;
+  redeemed: number;
+}
+
+function saveText(fileName: string, text: string) {
+  const link = document.createElement("a");
+  link.href = URL.createObjectURL(new Blob([text], { type: "text/csv" }));
+  link.download = fileName;
+  link.click();
+  URL.revokeObjectURL(link.href);
+}
+
+interface Member {
+  username: string;
+  balance_seconds: number;
+  created_at: string;
+  signed_in_station: string | null;
+}
+
 export const SettingsWindow = () => {
+  const [logExport, setLogExport] = useState("");
+  const [pairingCode, setPairingCode] = useState("");
+  const [voucherBatches, setVoucherBatches] = useState<VoucherBatch[]>([]);
+  const [voucherMessage, setVoucherMessage] = useState("");
+  const [voucherCount, setVoucherCount] = useState(50);
+  const [voucherMinutes, setVoucherMinutes] = useState(60);
+  const [voucherValidDays, setVoucherValidDays] = useState(30);
+  const [voucherLabel, setVoucherLabel] = useState("");
+
+  async function loadVoucherBatches() {
+    setVoucherBatches(await invoke<VoucherBatch[]>("list_voucher_batches"));
+  }
+
+  async function createVoucherBatch() {
+    try {
+      const batch = await invoke<VoucherBatch>("create_voucher_batch", {
+        count: voucherCount,
+        minutes: voucherMinutes,
+        validDays: voucherValidDays,
+        label: voucherLabel,
+      });
+      setVoucherMessage(`Created ${batch.codes} vouchers`);
+      await loadVoucherBatches();
+    } catch (error) {
+      setVoucherMessage(`Failed to create vouchers: ${error}`);
+    }
+  }
+
+  async function exportVoucherBatch(batch: VoucherBatch) {
+    const csv = await invoke<string>("export_voucher_batch", {
+      batchId: batch.id,
+    });
+    saveText(`vouchers-${batch.id}.csv`, csv);
+  }
+
+  const [members, setMembers] = useState<Member[]>([]);
+  const [memberMessage, setMemberMessage] = useState("");
+  const [newUsername, setNewUsername] = useState("");
+  const [newPin, setNewPin] = useState("");
+
+  async function loadMembers() {
+    setMembers(await invoke<Member[]>("list_members"));
+  }
+
+  async function createMember() {
+    try {
+      await invoke("create_member", { username: newUsername, pin: newPin });
+      setMemberMessage(`Created member ${newUsername}`);
+      setNewUsername("");
+      setNewPin("");
+      await loadMembers();
+    } catch (error) {
+      setMemberMessage(`Failed to create member: ${error}`);
+    }
+  }
+
+  async function topUpMember(member: Member) {
+    const pesos = Number(prompt(`Pesos paid by ${member.username}`));
+    if (!pesos) {
+      return;
+    }
+    try {
+      const balance = await invoke<number>("top_up_member", {
+        username: member.username,
+        amountCentavos: Math.round(pesos * 100),
+      });
+      setMemberMessage(
+        `${member.username} now has ${Math.floor(balance / 60)} minutes`,
+      );
+      await loadMembers();
+    } catch (error) {
+      setMemberMessage(`Failed to top up: ${error}`);
+    }
+  }
+
+  async function resetMemberPin(member: Member) {
+    const pin = prompt(`New PIN for ${member.username}`);
+    if (!pin) {
+      return;
+    }
+    try {
+      await invoke("reset_member_pin", { username: member.username, pin });
+      setMemberMessage(`PIN of ${member.username} was reset`);
+    } catch (error) {
+      setMemberMessage(`Failed to reset PIN: ${error}`);
+    }
+  }
+
+  async function exportLogs() {
+    try {
+      const path = await invoke<string>("export_logs");
+      setLogExport(`Logs exported to ${path}`);
+    } catch (error) {
+      setLogExport(`Failed to export logs: ${error}`);
+    }
+  }
+
   useEffect(() => {
     const handleFetchConfig = async () => {
       const response = await invoke<UIConfig>("get_ui_config");
       console.log("Received UI config:", response);
     };
     handleFetchConfig();
+    loadVoucherBatches();
+    loadMembers();
   }, []);
 
-  return <div>Showing Settings Main window</div>;
+  useEffect(() => {
+    invoke<string>("get_pairing_code").then(setPairingCode);
+    const unlistenPairingCode = listen(
+      "handler_pairing_code",
+      (event: Event<string>) => setPairingCode(event.payload),
+    );
+    return () => {
+      unlistenPairingCode.then((unlistenFn) => unlistenFn());
+    };
+  }, []);
+
+  return (
+    <div>
+      Showing Settings Main window
+      <button onClick={exportLogs}>Export logs</button>
+      {logExport && <p>{logExport}</p>}
+
+      <h3>Pairing</h3>
+      <p>
+        Enter <strong>{pairingCode}</strong> on the counter to pair it with
+        this station. The code changes once it is used.
+      </p>
+
+      <h3>Vouchers</h3>
+      <form
+        className="row"
+        onSubmit={(e) => {
+          e.preventDefault();
+          createVoucherBatch();
+        }}
+      >
+        <input
+          type="number"
+          min={1}
+          max={1000}
+          value={voucherCount}
+          onChange={(e) => setVoucherCount(Number(e.target.value))}
+          title="Number of codes"
+        />
+        <input
+          type="number"
+          min={1}
+          value={voucherMinutes}
+          onChange={(e) => setVoucherMinutes(Number(e.target.value))}
+          title="Minutes per code"
+        />
+        <input
+          type="number"
+          min={1}
+          value={voucherValidDays}
+          onChange={(e) => setVoucherValidDays(Number(e.target.value))}
+          title="Valid for days"
+        />
+        <input
+          value={voucherLabel}
+          onChange={(e) => setVoucherLabel(e.target.value)}
+          placeholder="Label..."
+        />
+        <button type="submit">Generate</button>
+      </form>
+      {voucherMessage && <p>{voucherMessage}</p>}
+      <ul>
+        {voucherBatches.map((batch) => (
+          <li key={batch.id}>
+            {batch.label || `Batch ${batch.id}`}: {batch.redeemed}/
+            {batch.codes} used, {batch.seconds / 60} minutes, expires{" "}
+            {new Date(batch.expires_at).toLocaleDateString()}{" "}
+            <button onClick={() => exportVoucherBatch(batch)}>Export</button>
+          </li>
+        ))}
+      </ul>
+
+      <h3>Members</h3>
+      <form
+        className="row"
+        onSubmit={(e) => {
+          e.preventDefault();
+          createMember();
+        }}
+      >
+        <input
+          value={newUsername}
+          onChange={(e) => setNewUsername(e.target.value)}
+          placeholder="Username..."
+        />
+        <input
+          type="password"
+          inputMode="numeric"
+          value={newPin}
+          onChange={(e) => setNewPin(e.target.value)}
+          placeholder="PIN..."
+        />
+        <button type="submit">Create</button>
+      </form>
+      {memberMessage && <p>{memberMessage}</p>}
+      <ul>
+        {members.map((member) => (
+          <li key={member.username}>
+            {member.username}: {Math.floor(member.balance_seconds / 60)} minutes
+            {member.signed_in_station &&
+              `, signed in on ${member.signed_in_station}`}{" "}
+            <button onClick={() => topUpMember(member)}>Top up</button>
+            <button onClick={() => resetMemberPin(member)}>Reset PIN</button>
+          </li>
+        ))}
+      </ul>
+    </div>
+  );
 };
 
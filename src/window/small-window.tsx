@@ -1,12 +1,28 @@
+import { invoke } from "@tauri-apps/api/core";
 import { useSettingStore } from "../store/Settings";
+import { StationMessages } from "./station-messages";
 
 export const SmallWindow = () => {
   const coin = useSettingStore((state) => state.coin);
   const remainingTime = useSettingStore((state) => state.remainingTime);
+  const member = useSettingStore((state) => state.member);
   return (
     <>
+      <StationMessages />
       <h2>Inserted PHP {coin}</h2>
       <h3>Remaining Time: {remainingTime} seconds</h3>
+      {member ? (
+        <>
+          <p>Signed in as {member}</p>
+          <button onClick={() => invoke("sign_out_member")}>
+            Sign out and save time
+          </button>
+        </>
+      ) : (
+        <button onClick={() => invoke("save_time")}>
+          Save time for later
+        </button>
+      )}
     </>
   );
 };
@@ -0,0 +1,25 @@
+import { invoke } from "@tauri-apps/api/core";
+import { useSettingStore } from "../store/Settings";
+
+// Messages from the counter, shown on the lock screen and the small window
+// until the customer acknowledges them.
+export const StationMessages = () => {
+  const messages = useSettingStore((state) => state.messages);
+  const setMessages = useSettingStore((state) => state.setMessages);
+
+  async function acknowledge(id: string) {
+    await invoke("acknowledge_message", { id });
+    setMessages(messages.filter((message) => message.id !== id));
+  }
+
+  return (
+    <>
+      {messages.map((message) => (
+        <div key={message.id} className="station-message">
+          <p>{message.text}</p>
+          <button onClick={() => acknowledge(message.id)}>OK</button>
+        </div>
+      ))}
+    </>
+  );
+};